@@ -5,8 +5,8 @@ async fn main() -> Result<(), DemoError> {
 
     let futures: Vec<_> = things
         .into_iter()
-        .filter(|thing| thing.configuration().deviceType() == &Some(20.0))
-        .map(|thing| dimmer_demo(thing))
+        .filter(|thing| thing.get_type() == Some(lci_gateway::DeviceType::Dimmer))
+        .map(dimmer_demo)
         .collect();
 
     futures::future::join_all(futures).await;
@@ -42,65 +42,67 @@ async fn dimmer_demo(thing: lci_gateway::Thing) -> Result<(), DimmerDemoError> {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum DemoError {
-    ThingError(lci_gateway::ThingError),
-    DimmerDemoError(DimmerDemoError),
+    Thing(lci_gateway::ThingError),
+    Dimmer(Box<DimmerDemoError>),
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum DimmerDemoError {
-    BrightnessError(lci_gateway::DimmerBrightnessError),
-    OnlineError(lci_gateway::OnlineStateConversionError),
-    DimmerError(lci_gateway::DimmerError),
-    SetError(lci_gateway::SetError),
-    SetBrightnessError(lci_gateway::SetBrightnessError),
+    Brightness(lci_gateway::DimmerBrightnessError),
+    Online(lci_gateway::OnlineStateConversionError),
+    Dimmer(lci_gateway::DimmerError),
+    Set(lci_gateway::SetError),
+    SetBrightness(lci_gateway::SetBrightnessError),
 }
 
 impl From<DimmerDemoError> for DemoError {
     fn from(error: DimmerDemoError) -> Self {
         log::error!("DimmerDemoError: {:?}", error);
-        Self::DimmerDemoError(error)
+        Self::Dimmer(Box::new(error))
     }
 }
 
 impl From<lci_gateway::ThingError> for DemoError {
     fn from(error: lci_gateway::ThingError) -> Self {
         log::error!("ThingError: {:?}", error);
-        Self::ThingError(error)
+        Self::Thing(error)
     }
 }
 
 impl From<lci_gateway::DimmerBrightnessError> for DimmerDemoError {
     fn from(error: lci_gateway::DimmerBrightnessError) -> Self {
         log::error!("DimmerBrightnessError: {:?}", error);
-        Self::BrightnessError(error)
+        Self::Brightness(error)
     }
 }
 
 impl From<lci_gateway::OnlineStateConversionError> for DimmerDemoError {
     fn from(error: lci_gateway::OnlineStateConversionError) -> Self {
         log::error!("OnlineStateConversionError: {:?}", error);
-        Self::OnlineError(error)
+        Self::Online(error)
     }
 }
 
 impl From<lci_gateway::DimmerError> for DimmerDemoError {
     fn from(error: lci_gateway::DimmerError) -> Self {
         log::error!("DimmerError: {:?}", error);
-        Self::DimmerError(error)
+        Self::Dimmer(error)
     }
 }
 
 impl From<lci_gateway::SetError> for DimmerDemoError {
     fn from(error: lci_gateway::SetError) -> Self {
         log::error!("SetError: {:?}", error);
-        Self::SetError(error)
+        Self::Set(error)
     }
 }
 
 impl From<lci_gateway::SetBrightnessError> for DimmerDemoError {
     fn from(error: lci_gateway::SetBrightnessError) -> Self {
         log::error!("SetBrightnessError: {:?}", error);
-        Self::SetBrightnessError(error)
+        Self::SetBrightness(error)
     }
 }
@@ -0,0 +1,22 @@
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let options = lci_gateway::discovery::DiscoveryOptions {
+        subnets: vec![(std::net::Ipv4Addr::new(192, 168, 1, 0), 24)],
+        authentication: lci_gateway::Client::from_env()?.authentication().clone(),
+        ..Default::default()
+    };
+
+    for candidate in lci_gateway::discovery::discover(&options).await? {
+        println!(
+            "{} ({:?}) via {:?}",
+            candidate.address(),
+            candidate.hostname(),
+            candidate.source()
+        );
+        if let Ok(things) = candidate.client().get_things().await {
+            println!("  {} things", things.len());
+        }
+    }
+    Ok(())
+}
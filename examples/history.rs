@@ -0,0 +1,40 @@
+use lci_gateway::history::History;
+use lci_gateway::{DeviceState, Watcher};
+use std::time::{Duration, SystemTime};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let mut history = History::open("lci-history.sqlite")?;
+    let mut watcher = Watcher::new(lci_gateway::get_devices().await?);
+
+    for _ in 0..6 {
+        let changes = watcher.poll().await;
+        history.record(&changes, SystemTime::now())?;
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+
+    let now = SystemTime::now();
+    let day_ago = now - Duration::from_secs(24 * 60 * 60);
+    for device in watcher.devices() {
+        let uid = device.uid();
+        if let Some(last) = history.last_change(&uid)? {
+            println!(
+                "{} last changed {:?} ago to {}",
+                device.label(),
+                now.duration_since(*last.time()).unwrap_or_default(),
+                last.snapshot().state()
+            );
+        }
+        let statuses = history.durations(&uid, day_ago, now, |state| match state {
+            DeviceState::Hvac { status, .. } => Some(*status),
+            _ => None,
+        })?;
+        for (status, duration) in statuses {
+            if let Some(status) = status {
+                println!("  {} for {:?} in the last day", status, duration);
+            }
+        }
+    }
+    Ok(())
+}
@@ -12,7 +12,7 @@ async fn control_fan() -> Result<(), FanDemoError> {
         .expect("Couldn't get things");
     let hvacs: Vec<_> = things
         .into_iter()
-        .filter(|thing| thing.configuration().deviceType() == &Some(16.0))
+        .filter(|thing| thing.get_type() == Some(lci_gateway::DeviceType::Hvac))
         .collect();
     // Turn the fans on High then Auto.
     for hvac in hvacs {
@@ -32,7 +32,7 @@ async fn print_status() -> Result<(), StatusDemoError> {
 
     let hvacs: Vec<_> = things
         .into_iter()
-        .filter(|thing| thing.configuration().deviceType() == &Some(16.0))
+        .filter(|thing| thing.get_type() == Some(lci_gateway::DeviceType::Hvac))
         .collect();
 
     // Display some friendly data
@@ -55,17 +55,20 @@ async fn print_status() -> Result<(), StatusDemoError> {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum DemoError {
-    StatusDemoError(StatusDemoError),
-    FanDemoError(FanDemoError),
+    StatusDemoError(Box<StatusDemoError>),
+    FanDemoError(Box<FanDemoError>),
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum FanDemoError {
     SetError(lci_gateway::SetError),
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum StatusDemoError {
     ThingError(lci_gateway::ThingError),
     HvacStatusFailure(lci_gateway::HvacStatusFailure),
@@ -80,14 +83,14 @@ enum StatusDemoError {
 impl From<FanDemoError> for DemoError {
     fn from(error: FanDemoError) -> Self {
         log::error!("FanDemoError: {:?}", error);
-        Self::FanDemoError(error)
+        Self::FanDemoError(Box::new(error))
     }
 }
 
 impl From<StatusDemoError> for DemoError {
     fn from(error: StatusDemoError) -> Self {
         log::error!("StatusDemoError: {:?}", error);
-        Self::StatusDemoError(error)
+        Self::StatusDemoError(Box::new(error))
     }
 }
 
@@ -28,53 +28,54 @@ async fn main() -> Result<(), DemoError> {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum DemoError {
-    SwitchError(lci_gateway::SwitchError),
-    ThingError(lci_gateway::ThingError),
-    OnlineStateConversionError(lci_gateway::OnlineStateConversionError),
-    SwitchStateConversionError(lci_gateway::SwitchStateConversionError),
-    SwitchRelayCurrentError(lci_gateway::SwitchRelayCurrentError),
-    SetError(lci_gateway::SetError),
+    Switch(lci_gateway::SwitchError),
+    Thing(lci_gateway::ThingError),
+    OnlineStateConversion(lci_gateway::OnlineStateConversionError),
+    SwitchStateConversion(lci_gateway::SwitchStateConversionError),
+    SwitchRelayCurrent(lci_gateway::SwitchRelayCurrentError),
+    Set(Box<lci_gateway::SetError>),
 }
 
 impl From<lci_gateway::ThingError> for DemoError {
     fn from(error: lci_gateway::ThingError) -> Self {
         log::error!("ThingError: {:?}", error);
-        Self::ThingError(error)
+        Self::Thing(error)
     }
 }
 
 impl From<lci_gateway::SwitchError> for DemoError {
     fn from(error: lci_gateway::SwitchError) -> Self {
         log::error!("SwitchError: {:?}", error);
-        Self::SwitchError(error)
+        Self::Switch(error)
     }
 }
 
 impl From<lci_gateway::OnlineStateConversionError> for DemoError {
     fn from(error: lci_gateway::OnlineStateConversionError) -> Self {
         log::error!("OnlineStateConversionError: {:?}", error);
-        Self::OnlineStateConversionError(error)
+        Self::OnlineStateConversion(error)
     }
 }
 
 impl From<lci_gateway::SwitchStateConversionError> for DemoError {
     fn from(error: lci_gateway::SwitchStateConversionError) -> Self {
         log::error!("SwitchStateConversionError: {:?}", error);
-        Self::SwitchStateConversionError(error)
+        Self::SwitchStateConversion(error)
     }
 }
 
 impl From<lci_gateway::SwitchRelayCurrentError> for DemoError {
     fn from(error: lci_gateway::SwitchRelayCurrentError) -> Self {
         log::error!("SwitchRelayCurrentError: {:?}", error);
-        Self::SwitchRelayCurrentError(error)
+        Self::SwitchRelayCurrent(error)
     }
 }
 
 impl From<lci_gateway::SetError> for DemoError {
     fn from(error: lci_gateway::SetError) -> Self {
         log::error!("SetError: {:?}", error);
-        Self::SetError(error)
+        Self::Set(Box::new(error))
     }
 }
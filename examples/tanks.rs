@@ -5,7 +5,7 @@ async fn main() -> Result<(), DemoError> {
 
     let tanks = things
         .into_iter()
-        .filter(|thing| thing.configuration().deviceType() == &Some(10.0))
+        .filter(|thing| thing.get_type() == Some(lci_gateway::DeviceType::Tank))
         .collect::<Vec<_>>();
 
     for tank in tanks {
@@ -21,26 +21,27 @@ async fn main() -> Result<(), DemoError> {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 enum DemoError {
-    ThingError(lci_gateway::ThingError),
-    OnlineStateConversionError(lci_gateway::OnlineStateConversionError),
-    TankLevelError(lci_gateway::TankLevelError),
+    Thing(lci_gateway::ThingError),
+    OnlineStateConversion(lci_gateway::OnlineStateConversionError),
+    TankLevel(lci_gateway::TankLevelError),
 }
 
 impl From<lci_gateway::ThingError> for DemoError {
     fn from(error: lci_gateway::ThingError) -> Self {
-        Self::ThingError(error)
+        Self::Thing(error)
     }
 }
 
 impl From<lci_gateway::OnlineStateConversionError> for DemoError {
     fn from(error: lci_gateway::OnlineStateConversionError) -> Self {
-        Self::OnlineStateConversionError(error)
+        Self::OnlineStateConversion(error)
     }
 }
 
 impl From<lci_gateway::TankLevelError> for DemoError {
     fn from(error: lci_gateway::TankLevelError) -> Self {
-        Self::TankLevelError(error)
+        Self::TankLevel(error)
     }
 }
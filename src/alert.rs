@@ -0,0 +1,752 @@
+use super::{Device, DeviceSnapshot, DeviceState, DeviceType, OnlineState, Predicate, TankKind};
+use derive_getters::Getters;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Which devices a rule applies to.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selector {
+    /// Every device.
+    #[default]
+    All,
+    /// One device, by label or UID.
+    Device(String),
+    /// Every device of a type, ex: every HVAC.
+    DeviceType(DeviceType),
+    /// Every tank of a kind, ex: the fresh tank.
+    TankKind(TankKind),
+}
+
+/// What makes a rule fire: a predicate on the device's state, ex: `{"tank_below": 15}` or `"hvac_failure"`,
+/// or the device being offline for a while, ex: `{"offline_for_seconds": 300}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Condition {
+    /// The device's state passes the predicate.
+    State(Predicate),
+    /// A device has not been online for this many seconds.
+    OfflineFor {
+        /// How long the device has been offline.
+        #[serde(rename = "offline_for_seconds")]
+        seconds: u64,
+    },
+}
+
+impl From<Predicate> for Condition {
+    fn from(predicate: Predicate) -> Self {
+        Self::State(predicate)
+    }
+}
+
+/// A named condition on a set of devices, usually loaded from a config file, ex:
+/// `{"name": "Fresh low", "devices": {"tank_kind": "Fresh"}, "condition": {"tank_below": 15}}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    /// A name for the rule, included in every alert.
+    pub name: String,
+    /// The devices the rule applies to.
+    #[serde(default)]
+    pub devices: Selector,
+    /// What makes the rule fire.
+    pub condition: Condition,
+}
+
+/// If an alert started or stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AlertState {
+    /// The condition became true.
+    Firing,
+    /// The condition is no longer true.
+    Resolved,
+}
+
+/// A rule that started or stopped matching a device.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize)]
+pub struct Alert {
+    /// The name of the rule.
+    rule: String,
+    /// If the alert started or stopped.
+    state: AlertState,
+    /// A sentence describing the alert, ex: "Fresh Water is at 12%, below 15%."
+    message: String,
+    /// Seconds since the Unix epoch when the alert was raised.
+    timestamp: u64,
+    /// The device as it was when the alert was raised.
+    device: DeviceSnapshot,
+}
+
+/// Somewhere alerts are delivered.
+pub trait Sink: Send + Sync {
+    /// Delivers the alert.
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), SinkError>>;
+}
+
+/// Prints alerts to stdout.
+pub struct StdoutSink;
+
+impl Sink for StdoutSink {
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            println!("[{:?}] {}: {}", alert.state, alert.rule, alert.message);
+            Ok(())
+        })
+    }
+}
+
+/// POSTs each alert as JSON to a URL.
+pub struct WebhookSink {
+    url: String,
+    http: reqwest::Client,
+}
+
+impl WebhookSink {
+    /// Create a sink that POSTs to the URL.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+impl Sink for WebhookSink {
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            let body = serde_json::to_string(alert).map_err(SinkError::SerdeJsonConversion)?;
+            let response = self
+                .http
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .map_err(SinkError::Http)?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(SinkError::Status(response.status().as_u16()))
+            }
+        })
+    }
+}
+
+/// Calls a function with each alert.
+pub struct CallbackSink<F>(pub F);
+
+impl<F: Fn(&Alert) + Send + Sync> Sink for CallbackSink<F> {
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), SinkError>> {
+        (self.0)(alert);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Emails each alert through an SMTP server.
+#[cfg(feature = "smtp")]
+pub struct SmtpSink {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+    to: Vec<lettre::message::Mailbox>,
+}
+
+#[cfg(feature = "smtp")]
+impl SmtpSink {
+    /// Create a sink that sends through the SMTP server using STARTTLS, ex: "smtp.example.com" on port 587.
+    pub fn new(
+        host: &str,
+        port: u16,
+        from: &str,
+        to: &[&str],
+        credentials: Option<(String, String)>,
+    ) -> Result<Self, SinkError> {
+        let mut builder =
+            lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::starttls_relay(host)
+                .map_err(|err| SinkError::Smtp(err.to_string()))?
+                .port(port);
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(
+                lettre::transport::smtp::authentication::Credentials::new(username, password),
+            );
+        }
+        Self::with_transport(builder.build(), from, to)
+    }
+
+    /// Create a sink that sends without encryption or credentials, ex: to a relay on the coach LAN.
+    pub fn unencrypted(host: &str, port: u16, from: &str, to: &[&str]) -> Result<Self, SinkError> {
+        let transport =
+            lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::builder_dangerous(host)
+                .port(port)
+                .build();
+        Self::with_transport(transport, from, to)
+    }
+
+    fn with_transport(
+        transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+        from: &str,
+        to: &[&str],
+    ) -> Result<Self, SinkError> {
+        let mailbox = |address: &str| {
+            address
+                .parse()
+                .map_err(|err: lettre::address::AddressError| SinkError::Smtp(err.to_string()))
+        };
+        Ok(Self {
+            transport,
+            from: mailbox(from)?,
+            to: to
+                .iter()
+                .map(|address| mailbox(address))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(feature = "smtp")]
+impl Sink for SmtpSink {
+    fn send<'a>(&'a self, alert: &'a Alert) -> BoxFuture<'a, Result<(), SinkError>> {
+        use lettre::AsyncTransport;
+        Box::pin(async move {
+            let mut builder = lettre::Message::builder()
+                .from(self.from.clone())
+                .subject(format!("[{:?}] {}", alert.state, alert.rule));
+            for to in &self.to {
+                builder = builder.to(to.clone());
+            }
+            let message = builder
+                .body(alert.message.clone())
+                .map_err(|err| SinkError::Smtp(err.to_string()))?;
+            self.transport
+                .send(message)
+                .await
+                .map_err(|err| SinkError::Smtp(err.to_string()))?;
+            Ok(())
+        })
+    }
+}
+
+/// Evaluates rules against device snapshots and delivers alerts to the sinks.
+///
+/// An alert fires once when its condition becomes true for a device and resolves once when it stops being true.
+/// After firing, the same rule and device stay quiet for the cooldown, even if the condition flaps.
+pub struct AlertEngine {
+    rules: Vec<Rule>,
+    sinks: Vec<Box<dyn Sink>>,
+    cooldown: Duration,
+    tank_kinds: HashMap<String, TankKind>,
+    /// Matching rule and device UIDs, and if the match was notified.
+    active: HashMap<(usize, String), bool>,
+    last_fired: HashMap<(usize, String), SystemTime>,
+    offline_since: HashMap<String, SystemTime>,
+}
+
+impl AlertEngine {
+    /// Create an engine for the rules with no sinks and a 15 minute cooldown.
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            rules,
+            sinks: Vec::new(),
+            cooldown: Duration::from_secs(15 * 60),
+            tank_kinds: HashMap::new(),
+            active: HashMap::new(),
+            last_fired: HashMap::new(),
+            offline_since: HashMap::new(),
+        }
+    }
+
+    /// Adds a sink alerts are delivered to.
+    pub fn with_sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Sets how long the same rule and device stay quiet after firing.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Sets the kind of the tank with the UID, overriding its label, ex: from `Tank::kind`.
+    pub fn set_tank_kind(&mut self, uid: impl Into<String>, kind: TankKind) {
+        self.tank_kinds.insert(uid.into(), kind);
+    }
+
+    /// Evaluates every rule against the snapshots, delivers the resulting alerts and returns them.
+    /// Failed deliveries are logged.
+    pub async fn evaluate(&mut self, snapshots: &[DeviceSnapshot], time: SystemTime) -> Vec<Alert> {
+        self.evaluate_with_unread(snapshots, &[], time).await
+    }
+
+    /// Like `evaluate`, also counting the devices that have never been read as offline, ex: from
+    /// `Watcher::unread`, so `offline_for_seconds` fires for a device that has been down since startup.
+    /// They have no readings, so only the online, offline and `offline_for_seconds` conditions are checked
+    /// for them, and their alerts carry empty readings.
+    pub async fn evaluate_with_unread(
+        &mut self,
+        snapshots: &[DeviceSnapshot],
+        unread: &[&Device],
+        time: SystemTime,
+    ) -> Vec<Alert> {
+        let unread: Vec<DeviceSnapshot> = unread
+            .iter()
+            .map(|device| DeviceSnapshot::unread(device))
+            .collect();
+        let read = snapshots.len();
+        let snapshots: Vec<&DeviceSnapshot> = snapshots.iter().chain(&unread).collect();
+        for snapshot in &snapshots {
+            if *snapshot.online() == OnlineState::Online {
+                self.offline_since.remove(snapshot.uid());
+            } else {
+                self.offline_since
+                    .entry(snapshot.uid().clone())
+                    .or_insert(time);
+            }
+        }
+
+        let mut alerts = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            for (position, snapshot) in snapshots.iter().enumerate() {
+                if !self.selects(&rule.devices, snapshot) {
+                    continue;
+                }
+                let key = (index, snapshot.uid().clone());
+                let matched = match &rule.condition {
+                    Condition::State(predicate) if position >= read => {
+                        matches!(predicate, Predicate::Online | Predicate::Offline)
+                            && predicate.test(snapshot)
+                    }
+                    condition => self.matches(condition, snapshot, time),
+                };
+                let state = match (matched, self.active.get(&key).copied()) {
+                    // A match suppressed by the cooldown is checked again until the cooldown ends.
+                    (true, None) | (true, Some(false)) => {
+                        let quiet = self.last_fired.get(&key).is_some_and(|fired| {
+                            time.duration_since(*fired).unwrap_or_default() < self.cooldown
+                        });
+                        self.active.insert(key.clone(), !quiet);
+                        if quiet {
+                            continue;
+                        }
+                        self.last_fired.insert(key, time);
+                        AlertState::Firing
+                    }
+                    (false, Some(notified)) => {
+                        self.active.remove(&key);
+                        if !notified {
+                            continue;
+                        }
+                        AlertState::Resolved
+                    }
+                    _ => continue,
+                };
+                alerts.push(Alert {
+                    rule: rule.name.clone(),
+                    state,
+                    message: describe(&rule.condition, snapshot, state),
+                    timestamp: time
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    device: (*snapshot).clone(),
+                });
+            }
+        }
+
+        for alert in &alerts {
+            let results =
+                futures::future::join_all(self.sinks.iter().map(|sink| sink.send(alert))).await;
+            for err in results.into_iter().filter_map(Result::err) {
+                log::error!("Could not deliver alert '{}': {}", alert.rule, err);
+            }
+        }
+        alerts
+    }
+
+    fn selects(&self, selector: &Selector, snapshot: &DeviceSnapshot) -> bool {
+        match selector {
+            Selector::All => true,
+            Selector::Device(name) => snapshot.matches(name),
+            Selector::DeviceType(device_type) => snapshot.device_type() == device_type,
+            Selector::TankKind(kind) => {
+                *snapshot.device_type() == DeviceType::Tank
+                    && self
+                        .tank_kinds
+                        .get(snapshot.uid())
+                        .copied()
+                        .or_else(|| TankKind::from_label(snapshot.label()))
+                        == Some(*kind)
+            }
+        }
+    }
+
+    fn matches(&self, condition: &Condition, snapshot: &DeviceSnapshot, time: SystemTime) -> bool {
+        match condition {
+            Condition::State(predicate) => predicate.test(snapshot),
+            Condition::OfflineFor { seconds } => {
+                self.offline_since.get(snapshot.uid()).is_some_and(|since| {
+                    time.duration_since(*since).unwrap_or_default() >= Duration::from_secs(*seconds)
+                })
+            }
+        }
+    }
+}
+
+fn describe(condition: &Condition, snapshot: &DeviceSnapshot, state: AlertState) -> String {
+    let label = snapshot.label();
+    let reading = snapshot.state();
+    let description = match condition {
+        Condition::State(Predicate::TankBelow(percent)) => {
+            format!("{} is at {}, below {}%", label, reading, percent)
+        }
+        Condition::State(Predicate::TankAbove(percent)) => {
+            format!("{} is at {}, above {}%", label, reading, percent)
+        }
+        Condition::State(Predicate::HvacFailure | Predicate::HvacStatus(_)) => match reading {
+            DeviceState::Hvac { status, .. } => format!("{} reports {}", label, status),
+            _ => format!("{} is {}", label, reading),
+        },
+        Condition::State(
+            Predicate::InsideTemperatureAbove(limit) | Predicate::InsideTemperatureBelow(limit),
+        ) => match reading {
+            DeviceState::Hvac {
+                inside_temperature, ..
+            } => format!(
+                "{} is {:.1}° inside, past the limit of {:.1}°",
+                label, inside_temperature, limit
+            ),
+            _ => format!("{} is {}", label, reading),
+        },
+        Condition::State(
+            Predicate::OutsideTemperatureAbove(limit) | Predicate::OutsideTemperatureBelow(limit),
+        ) => match reading {
+            DeviceState::Hvac {
+                outside_temperature,
+                ..
+            } => format!(
+                "{} is {:.1}° outside, past the limit of {:.1}°",
+                label, outside_temperature, limit
+            ),
+            _ => format!("{} is {}", label, reading),
+        },
+        Condition::State(Predicate::SwitchFault) => format!("{} reports a fault", label),
+        Condition::State(Predicate::Online | Predicate::Offline) => {
+            format!("{} is {}", label, snapshot.online())
+        }
+        Condition::State(_) => format!("{} is {}", label, reading),
+        Condition::OfflineFor { seconds } => {
+            format!("{} has been {} for {}s", label, snapshot.online(), seconds)
+        }
+    };
+    match state {
+        AlertState::Firing => format!("{}.", description),
+        AlertState::Resolved => format!("Resolved: {} is back to {}.", label, reading),
+    }
+}
+
+/// An alert could not be delivered.
+#[derive(Debug, Error)]
+pub enum SinkError {
+    /// The webhook could not be reached.
+    #[error("The webhook could not be reached. {0}")]
+    Http(reqwest::Error),
+    /// The alert could not be converted to JSON.
+    #[error("The alert could not be converted to JSON. {0}")]
+    SerdeJsonConversion(serde_json::Error),
+    /// The webhook returned an error status.
+    #[error("The webhook returned status {0}.")]
+    Status(u16),
+    /// The email could not be built or sent.
+    #[error("The email could not be sent. {0}")]
+    Smtp(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn fresh(level: u8) -> DeviceSnapshot {
+        serde_json::from_value(serde_json::json!({
+            "label": "Fresh Water",
+            "uid": "tank-1",
+            "device_type": "Tank",
+            "online": "Online",
+            "state": {"type": "tank", "level": level},
+        }))
+        .unwrap()
+    }
+
+    fn engine() -> AlertEngine {
+        AlertEngine::new(vec![Rule {
+            name: "Fresh low".to_string(),
+            devices: Selector::TankKind(TankKind::Fresh),
+            condition: Predicate::TankBelow(15).into(),
+        }])
+        .with_cooldown(Duration::from_secs(15 * 60))
+    }
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000 + minutes * 60)
+    }
+
+    fn states(alerts: &[Alert]) -> Vec<AlertState> {
+        alerts.iter().map(|alert| alert.state).collect()
+    }
+
+    #[tokio::test]
+    async fn fires_once_while_matching() {
+        let mut engine = engine();
+        assert_eq!(
+            states(&engine.evaluate(&[fresh(10)], at(0)).await),
+            [AlertState::Firing]
+        );
+        assert!(engine.evaluate(&[fresh(9)], at(1)).await.is_empty());
+        assert!(engine.evaluate(&[fresh(8)], at(30)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolves_once() {
+        let mut engine = engine();
+        engine.evaluate(&[fresh(10)], at(0)).await;
+        assert_eq!(
+            states(&engine.evaluate(&[fresh(20)], at(1)).await),
+            [AlertState::Resolved]
+        );
+        assert!(engine.evaluate(&[fresh(25)], at(2)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fires_again_after_the_cooldown() {
+        let mut engine = engine();
+        engine.evaluate(&[fresh(10)], at(0)).await;
+        engine.evaluate(&[fresh(20)], at(1)).await;
+        assert!(engine.evaluate(&[fresh(10)], at(2)).await.is_empty());
+        assert!(engine.evaluate(&[fresh(10)], at(14)).await.is_empty());
+        assert_eq!(
+            states(&engine.evaluate(&[fresh(10)], at(15)).await),
+            [AlertState::Firing]
+        );
+        assert!(engine.evaluate(&[fresh(10)], at(600)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn flapping_within_the_cooldown_is_quiet() {
+        let mut engine = engine();
+        engine.evaluate(&[fresh(10)], at(0)).await;
+        engine.evaluate(&[fresh(20)], at(1)).await;
+        for minute in 2..10 {
+            let level = if minute % 2 == 0 { 10 } else { 20 };
+            assert!(engine
+                .evaluate(&[fresh(level)], at(minute))
+                .await
+                .is_empty());
+        }
+        // Resolved while quiet, so nothing was left firing.
+        assert!(engine.evaluate(&[fresh(20)], at(20)).await.is_empty());
+        assert_eq!(
+            states(&engine.evaluate(&[fresh(10)], at(21)).await),
+            [AlertState::Firing]
+        );
+    }
+
+    #[tokio::test]
+    async fn times_devices_never_read_from_when_first_seen() {
+        let mut thing: crate::Thing = serde_json::from_value(serde_json::json!({
+            "label": "Fresh Water",
+            "UID": "tank-1",
+            "configuration": {"deviceType": 10},
+        }))
+        .unwrap();
+        thing.set_client(crate::Client::new("http://127.0.0.1:1"));
+        let tank = Device::from_thing(thing).unwrap();
+        let mut engine = AlertEngine::new(vec![
+            Rule {
+                name: "Offline".to_string(),
+                devices: Selector::All,
+                condition: Condition::OfflineFor { seconds: 300 },
+            },
+            Rule {
+                name: "Fresh low".to_string(),
+                devices: Selector::All,
+                condition: Predicate::TankBelow(15).into(),
+            },
+        ]);
+
+        assert!(engine
+            .evaluate_with_unread(&[], &[&tank], at(0))
+            .await
+            .is_empty());
+        assert!(engine
+            .evaluate_with_unread(&[], &[&tank], at(4))
+            .await
+            .is_empty());
+        let alerts = engine.evaluate_with_unread(&[], &[&tank], at(5)).await;
+        assert_eq!(states(&alerts), [AlertState::Firing]);
+        assert_eq!(alerts[0].rule, "Offline");
+        assert_eq!(alerts[0].message, "Fresh Water has been Offline for 300s.");
+
+        let alerts = engine.evaluate(&[fresh(40)], at(6)).await;
+        assert_eq!(states(&alerts), [AlertState::Resolved]);
+        assert_eq!(alerts[0].rule, "Offline");
+    }
+
+    #[tokio::test]
+    async fn delivers_to_callbacks() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink_received = received.clone();
+        let mut engine = engine().with_sink(CallbackSink(move |alert: &Alert| {
+            sink_received.lock().unwrap().push(alert.message.clone())
+        }));
+        engine.evaluate(&[fresh(10)], at(0)).await;
+        assert_eq!(
+            *received.lock().unwrap(),
+            ["Fresh Water is at 10%, below 15%."]
+        );
+    }
+
+    #[tokio::test]
+    async fn webhook_posts_the_alert() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(socket);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).await.unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        });
+
+        let alert = engine().evaluate(&[fresh(10)], at(0)).await.remove(0);
+        WebhookSink::new(url).send(&alert).await.unwrap();
+        let body = server.await.unwrap();
+        assert_eq!(body["rule"], "Fresh low");
+        assert_eq!(body["state"], "Firing");
+        assert_eq!(body["device"]["uid"], "tank-1");
+    }
+
+    #[tokio::test]
+    async fn webhook_reports_error_statuses() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 4096];
+            let _ = socket.read(&mut buffer).await;
+            socket
+                .write_all(b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let alert = engine().evaluate(&[fresh(10)], at(0)).await.remove(0);
+        let result = WebhookSink::new(url).send(&alert).await;
+        assert!(matches!(result, Err(SinkError::Status(500))));
+    }
+
+    #[cfg(feature = "smtp")]
+    #[tokio::test]
+    async fn smtp_sends_the_alert() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let mut message = String::new();
+            let mut recipients = Vec::new();
+            write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let command = line.to_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    b"250 localhost\r\n"
+                } else if command.starts_with("RCPT TO:") {
+                    recipients.push(line[8..].to_string());
+                    b"250 OK\r\n"
+                } else if command.starts_with("DATA") {
+                    write.write_all(b"354 Go ahead\r\n").await.unwrap();
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        if line == "." {
+                            break;
+                        }
+                        message.push_str(&line);
+                        message.push('\n');
+                    }
+                    b"250 Queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    write.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+            (recipients, message)
+        });
+
+        let alert = engine().evaluate(&[fresh(10)], at(0)).await.remove(0);
+        let sink = SmtpSink::unencrypted(
+            "127.0.0.1",
+            port,
+            "coach@example.com",
+            &["owner@example.com"],
+        )
+        .unwrap();
+        sink.send(&alert).await.unwrap();
+        drop(sink);
+        let (recipients, message) = server.await.unwrap();
+        assert_eq!(recipients, ["<owner@example.com>"]);
+        assert!(message.contains("Subject: [Firing] Fresh low"));
+        assert!(message.contains("Fresh Water is at 10%, below 15%."));
+    }
+
+    #[test]
+    fn conditions_deserialize() {
+        let rules: Vec<Rule> = serde_json::from_value(serde_json::json!([
+            {"name": "Fresh low", "devices": {"tank_kind": "Fresh"}, "condition": {"tank_below": 15}},
+            {"name": "HVAC", "condition": "hvac_failure"},
+            {"name": "Offline", "condition": {"offline_for_seconds": 300}},
+        ]))
+        .unwrap();
+        let conditions: Vec<Condition> = rules.into_iter().map(|rule| rule.condition).collect();
+        assert_eq!(
+            conditions,
+            [
+                Predicate::TankBelow(15).into(),
+                Predicate::HvacFailure.into(),
+                Condition::OfflineFor { seconds: 300 },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&conditions[2]).unwrap(),
+            serde_json::json!({"offline_for_seconds": 300})
+        );
+    }
+
+    #[test]
+    fn selects_devices_by_object_id() {
+        let engine = AlertEngine::new(Vec::new());
+        assert!(engine.selects(&Selector::Device("tank_1".to_string()), &fresh(10)));
+        assert!(engine.selects(&Selector::Device("fresh water".to_string()), &fresh(10)));
+        assert!(!engine.selects(&Selector::Device("Gray Water".to_string()), &fresh(10)));
+    }
+}
@@ -0,0 +1,763 @@
+use super::{
+    Client, Device, DeviceCommand, DeviceCommandError, DeviceSnapshot, DeviceStateError,
+    DeviceType, GetFailure, SetBrightnessError, SetError, ThingError,
+};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The setpoints of an HVAC, as served on `/hvac/{id}/setpoints`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Setpoints {
+    /// The temperature the unit starts cooling at.
+    pub high_temperature: f32,
+    /// The temperature the unit starts heating at.
+    pub low_temperature: f32,
+}
+
+/// A change to an HVAC's setpoints. Missing setpoints are left as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetpointsUpdate {
+    /// The temperature to start cooling at.
+    #[serde(default)]
+    pub high_temperature: Option<isize>,
+    /// The temperature to start heating at.
+    #[serde(default)]
+    pub low_temperature: Option<isize>,
+}
+
+/// Returns the API's routes for the gateway behind the client, to serve or nest in another router.
+///
+/// * `GET /devices` - a snapshot of every device
+/// * `GET /devices/{id}` - a snapshot of one device, by UID, object ID or label
+/// * `POST /devices/{id}/commands` - carries out a `DeviceCommand`
+/// * `GET /tanks` - a snapshot of every tank
+/// * `GET /hvac/{id}/setpoints` and `PUT /hvac/{id}/setpoints` - reads or changes the setpoints
+/// * `GET /openapi.json` - the OpenAPI document, see `openapi`
+pub fn router(client: Client) -> Router {
+    Router::new()
+        .route("/devices", get(devices))
+        .route("/devices/{id}", get(device))
+        .route("/devices/{id}/commands", post(command))
+        .route("/tanks", get(tanks))
+        .route("/hvac/{id}/setpoints", get(setpoints).put(set_setpoints))
+        .route("/openapi.json", get(|| async { Json(openapi()) }))
+        .with_state(client)
+}
+
+/// Serves the API at the given address, ex: "0.0.0.0:8090".
+pub async fn serve(client: Client, address: &str) -> Result<(), ApiError> {
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(ApiError::Bind)?;
+    log::info!("Serving the API on http://{}", address);
+    axum::serve(listener, router(client))
+        .await
+        .map_err(ApiError::Serve)
+}
+
+async fn devices(State(client): State<Client>) -> Result<Json<Vec<DeviceSnapshot>>, RequestError> {
+    let devices = client.devices().await?;
+    Ok(Json(snapshots(&devices).await))
+}
+
+async fn device(
+    State(client): State<Client>,
+    Path(id): Path<String>,
+) -> Result<Json<DeviceSnapshot>, RequestError> {
+    let device = find(&client, &id, None).await?;
+    Ok(Json(device.snapshot().await?))
+}
+
+async fn command(
+    State(client): State<Client>,
+    Path(id): Path<String>,
+    Json(command): Json<DeviceCommand>,
+) -> Result<StatusCode, RequestError> {
+    let mut device = find(&client, &id, None).await?;
+    device.execute(&command).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn tanks(State(client): State<Client>) -> Result<Json<Vec<DeviceSnapshot>>, RequestError> {
+    let tanks: Vec<Device> = client
+        .devices()
+        .await?
+        .into_iter()
+        .filter(|device| device.device_type() == DeviceType::Tank)
+        .collect();
+    Ok(Json(snapshots(&tanks).await))
+}
+
+async fn setpoints(
+    State(client): State<Client>,
+    Path(id): Path<String>,
+) -> Result<Json<Setpoints>, RequestError> {
+    match find(&client, &id, Some(DeviceType::Hvac)).await? {
+        Device::Hvac(hvac) => Ok(Json(Setpoints {
+            high_temperature: hvac
+                .high_temperature()
+                .await
+                .map_err(DeviceStateError::from)?,
+            low_temperature: hvac
+                .low_temperature()
+                .await
+                .map_err(DeviceStateError::from)?,
+        })),
+        _ => Err(RequestError::NotFound(id)),
+    }
+}
+
+async fn set_setpoints(
+    State(client): State<Client>,
+    Path(id): Path<String>,
+    Json(update): Json<SetpointsUpdate>,
+) -> Result<StatusCode, RequestError> {
+    let mut device = find(&client, &id, Some(DeviceType::Hvac)).await?;
+    if let Some(temperature) = update.high_temperature {
+        device
+            .execute(&DeviceCommand::SetHighTemperature(temperature))
+            .await?;
+    }
+    if let Some(temperature) = update.low_temperature {
+        device
+            .execute(&DeviceCommand::SetLowTemperature(temperature))
+            .await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reads every device, leaving out the ones that fail.
+async fn snapshots(devices: &[Device]) -> Vec<DeviceSnapshot> {
+    let results = futures::future::join_all(devices.iter().map(Device::snapshot)).await;
+    let mut snapshots = Vec::new();
+    for (device, result) in devices.iter().zip(results) {
+        match result {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(err) => log::warn!("Could not read {}: {}", device.label(), err),
+        }
+    }
+    snapshots
+}
+
+async fn find(
+    client: &Client,
+    id: &str,
+    device_type: Option<DeviceType>,
+) -> Result<Device, RequestError> {
+    client
+        .devices()
+        .await?
+        .into_iter()
+        .filter(|device| device_type.is_none() || Some(device.device_type()) == device_type)
+        .find(|device| device.matches(id))
+        .ok_or_else(|| RequestError::NotFound(id.to_string()))
+}
+
+/// Returns the OpenAPI 3 document describing the API, including the JSON schemas of every body.
+pub fn openapi() -> serde_json::Value {
+    let id = serde_json::json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "description": "The device's UID, object ID or label.",
+        "schema": { "type": "string" }
+    });
+    let error = |description: &str| {
+        serde_json::json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } }
+        })
+    };
+    let json =
+        |schema: serde_json::Value| serde_json::json!({ "application/json": { "schema": schema } });
+    let snapshot = serde_json::json!({ "$ref": "#/components/schemas/DeviceSnapshot" });
+    let snapshots = serde_json::json!({ "type": "array", "items": snapshot });
+    let percentage = serde_json::json!({ "type": "integer", "minimum": 0, "maximum": 100 });
+    let on_off = serde_json::json!({ "type": "string", "enum": ["On", "Off"] });
+
+    serde_json::json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "LCI gateway",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Typed access to the devices behind an LCI gateway."
+        },
+        "paths": {
+            "/devices": {
+                "get": {
+                    "summary": "Read every device. Devices that can not be read are left out.",
+                    "responses": {
+                        "200": { "description": "Every device.", "content": json(snapshots.clone()) },
+                        "403": error("The gateway rejected the credentials."),
+                        "502": error("The gateway could not be read.")
+                    }
+                }
+            },
+            "/devices/{id}": {
+                "get": {
+                    "summary": "Read one device.",
+                    "parameters": [id],
+                    "responses": {
+                        "200": { "description": "The device.", "content": json(snapshot) },
+                        "403": error("The gateway rejected the credentials."),
+                        "404": error("No device matches the ID, or it lacks a channel."),
+                        "502": error("The gateway could not be read.")
+                    }
+                }
+            },
+            "/devices/{id}/commands": {
+                "post": {
+                    "summary": "Send a command to a device.",
+                    "parameters": [id],
+                    "requestBody": {
+                        "required": true,
+                        "content": json(serde_json::json!({ "$ref": "#/components/schemas/DeviceCommand" }))
+                    },
+                    "responses": {
+                        "204": { "description": "The command was carried out." },
+                        "403": error("The gateway rejected the credentials."),
+                        "404": error("No device matches the ID."),
+                        "422": error("The device does not support the command, lacks the channel, or the value is out of range."),
+                        "502": error("The gateway failed the command.")
+                    }
+                }
+            },
+            "/tanks": {
+                "get": {
+                    "summary": "Read every tank.",
+                    "responses": {
+                        "200": { "description": "Every tank.", "content": json(snapshots) },
+                        "403": error("The gateway rejected the credentials."),
+                        "502": error("The gateway could not be read.")
+                    }
+                }
+            },
+            "/hvac/{id}/setpoints": {
+                "get": {
+                    "summary": "Read an HVAC's setpoints.",
+                    "parameters": [id],
+                    "responses": {
+                        "200": {
+                            "description": "The setpoints.",
+                            "content": json(serde_json::json!({ "$ref": "#/components/schemas/Setpoints" }))
+                        },
+                        "403": error("The gateway rejected the credentials."),
+                        "404": error("No HVAC matches the ID, or it lacks a setpoint channel."),
+                        "502": error("The gateway could not be read.")
+                    }
+                },
+                "put": {
+                    "summary": "Change either of an HVAC's setpoints.",
+                    "parameters": [id],
+                    "requestBody": {
+                        "required": true,
+                        "content": json(serde_json::json!({ "$ref": "#/components/schemas/SetpointsUpdate" }))
+                    },
+                    "responses": {
+                        "204": { "description": "The setpoints were changed." },
+                        "403": error("The gateway rejected the credentials."),
+                        "404": error("No HVAC matches the ID."),
+                        "422": error("The HVAC lacks a setpoint channel."),
+                        "502": error("The gateway failed the change.")
+                    }
+                }
+            }
+        },
+        "components": {
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "required": ["error"],
+                    "properties": { "error": { "type": "string" } }
+                },
+                "DeviceSnapshot": {
+                    "type": "object",
+                    "required": ["label", "uid", "device_type", "online", "state"],
+                    "properties": {
+                        "label": { "type": "string" },
+                        "uid": { "type": "string" },
+                        "device_type": {
+                            "type": "string",
+                            "enum": ["Gateway", "Tank", "RgbLights", "Hvac", "Dimmer", "Generator", "Switch"]
+                        },
+                        "online": { "type": "string", "enum": ["Online", "Offline", "Locked"] },
+                        "state": { "$ref": "#/components/schemas/DeviceState" }
+                    }
+                },
+                "DeviceState": {
+                    "oneOf": [
+                        {
+                            "type": "object",
+                            "required": ["type", "level"],
+                            "properties": { "type": { "enum": ["tank"] }, "level": percentage }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["type", "brightness"],
+                            "properties": { "type": { "enum": ["dimmer"] }, "brightness": percentage }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["type", "state", "fault", "relay_current"],
+                            "properties": {
+                                "type": { "enum": ["switch"] },
+                                "state": on_off,
+                                "fault": on_off,
+                                "relay_current": { "type": "string" }
+                            }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["type", "state"],
+                            "properties": {
+                                "type": { "enum": ["generator"] },
+                                "state": { "type": "string", "enum": ["Off", "Priming", "Starting", "Running"] }
+                            }
+                        },
+                        {
+                            "type": "object",
+                            "required": [
+                                "type", "status", "mode", "fan", "inside_temperature",
+                                "outside_temperature", "high_temperature", "low_temperature"
+                            ],
+                            "properties": {
+                                "type": { "enum": ["hvac"] },
+                                "status": { "$ref": "#/components/schemas/HvacStatus" },
+                                "mode": { "$ref": "#/components/schemas/HvacMode" },
+                                "fan": { "$ref": "#/components/schemas/HvacFanMode" },
+                                "inside_temperature": { "type": "number" },
+                                "outside_temperature": { "type": "number" },
+                                "high_temperature": { "type": "number" },
+                                "low_temperature": { "type": "number" }
+                            }
+                        }
+                    ],
+                    "discriminator": { "propertyName": "type" }
+                },
+                "DeviceCommand": {
+                    "oneOf": [
+                        {
+                            "type": "object",
+                            "required": ["command"],
+                            "properties": { "command": { "enum": ["on", "off"] } }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["command", "value"],
+                            "properties": { "command": { "enum": ["set_brightness"] }, "value": percentage }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["command", "value"],
+                            "properties": {
+                                "command": { "enum": ["set_mode"] },
+                                "value": { "$ref": "#/components/schemas/HvacMode" }
+                            }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["command", "value"],
+                            "properties": {
+                                "command": { "enum": ["set_fan"] },
+                                "value": { "$ref": "#/components/schemas/HvacFanMode" }
+                            }
+                        },
+                        {
+                            "type": "object",
+                            "required": ["command", "value"],
+                            "properties": {
+                                "command": { "enum": ["set_high_temperature", "set_low_temperature"] },
+                                "value": { "type": "integer" }
+                            }
+                        }
+                    ]
+                },
+                "Setpoints": {
+                    "type": "object",
+                    "required": ["high_temperature", "low_temperature"],
+                    "properties": {
+                        "high_temperature": { "type": "number" },
+                        "low_temperature": { "type": "number" }
+                    }
+                },
+                "SetpointsUpdate": {
+                    "type": "object",
+                    "properties": {
+                        "high_temperature": { "type": "integer", "nullable": true },
+                        "low_temperature": { "type": "integer", "nullable": true }
+                    }
+                },
+                "HvacMode": { "type": "string", "enum": ["Off", "Heat", "Cool", "HeatCool"] },
+                "HvacFanMode": { "type": "string", "enum": ["Auto", "Low", "High"] },
+                "HvacStatus": {
+                    "type": "string",
+                    "enum": [
+                        "Off", "Idle", "Cooling", "HeatPump", "ElectricFurnace", "GasFurnace",
+                        "GasOverride", "DeadTime", "LoadShedding", "FailOff", "FailIdle",
+                        "FailCooling", "FailHeatPump", "FailElectricFurnace", "FailGasFurnace",
+                        "FailGasOverride", "FailDeadTime", "FailShedding"
+                    ]
+                }
+            }
+        }
+    })
+}
+
+/// A request to the API failed. Served as `{"error": "..."}` with a status code for the cause.
+#[derive(Debug, Error)]
+pub enum RequestError {
+    /// No device matches the ID. Served as 404.
+    #[error("No device matches \"{0}\".")]
+    NotFound(String),
+    /// The devices could not be listed. Served as 403 when the gateway rejected the credentials, otherwise 502.
+    #[error("{0}")]
+    Things(ThingError),
+    /// The device could not be read. Served as 403 when the gateway rejected the credentials, 404 when the
+    /// device lacks the channel, otherwise 502.
+    #[error("{0}")]
+    State(DeviceStateError),
+    /// The command failed. Served as 403 when the gateway rejected the credentials, 422 when the device, its
+    /// channels or the value do not fit the command, otherwise 502.
+    #[error("{0}")]
+    Command(DeviceCommandError),
+}
+
+impl RequestError {
+    /// Returns the status code the error is served with.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RequestError::NotFound(_) => StatusCode::NOT_FOUND,
+            RequestError::Things(ThingError::Unauthorized) => StatusCode::FORBIDDEN,
+            RequestError::Things(_) => StatusCode::BAD_GATEWAY,
+            RequestError::State(error) => match error.get_failure() {
+                Some(GetFailure::Unauthorized) => StatusCode::FORBIDDEN,
+                Some(GetFailure::MissingChannel(_)) => StatusCode::NOT_FOUND,
+                _ => StatusCode::BAD_GATEWAY,
+            },
+            RequestError::Command(
+                DeviceCommandError::Unsupported(_, _)
+                | DeviceCommandError::SetBrightness(SetBrightnessError::InvalidValue(_)),
+            ) => StatusCode::UNPROCESSABLE_ENTITY,
+            RequestError::Command(error) => match error.set_error() {
+                Some(SetError::Unauthorized) => StatusCode::FORBIDDEN,
+                Some(SetError::MissingChannel(_)) => StatusCode::UNPROCESSABLE_ENTITY,
+                _ => StatusCode::BAD_GATEWAY,
+            },
+        }
+    }
+}
+
+impl IntoResponse for RequestError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.to_string() });
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+impl From<ThingError> for RequestError {
+    fn from(error: ThingError) -> Self {
+        Self::Things(error)
+    }
+}
+
+impl From<DeviceStateError> for RequestError {
+    fn from(error: DeviceStateError) -> Self {
+        Self::State(error)
+    }
+}
+
+impl From<DeviceCommandError> for RequestError {
+    fn from(error: DeviceCommandError) -> Self {
+        Self::Command(error)
+    }
+}
+
+/// Returned when the API server stops.
+#[derive(Debug, Error)]
+pub enum ApiError {
+    /// The address could not be listened on.
+    #[error("The API address could not be bound. {0}")]
+    Bind(std::io::Error),
+    /// The server failed while running.
+    #[error("The API server failed. {0}")]
+    Serve(std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DeviceState, GeneratorState, HvacFanMode, HvacMode, HvacStatus, OnlineState, Percentage,
+        SwitchState, TankLevelError,
+    };
+    use serde_json::Value;
+
+    /// Checks the value against the schema, following `$ref`s into the document. Properties the schema does
+    /// not list are rejected, so a serde field the document is missing fails too.
+    fn check(document: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            return check(document, &document["components"]["schemas"][name], value);
+        }
+        if let Some(options) = schema.get("oneOf").and_then(Value::as_array) {
+            let passing = options
+                .iter()
+                .filter(|option| check(document, option, value).is_ok())
+                .count();
+            return match passing {
+                1 => Ok(()),
+                _ => Err(format!("{} matches {} options", value, passing)),
+            };
+        }
+        if value.is_null() && schema["nullable"] == true {
+            return Ok(());
+        }
+        if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+            if !values.contains(value) {
+                return Err(format!("{} is not one of {:?}", value, values));
+            }
+        }
+        let fits = match schema.get("type").and_then(Value::as_str) {
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_i64() || value.is_u64(),
+            Some("number") => value.is_number(),
+            _ => true,
+        };
+        if !fits {
+            return Err(format!("{} is not of type {}", value, schema["type"]));
+        }
+        let number = value.as_f64();
+        if schema.get("minimum").and_then(Value::as_f64) > number
+            || schema
+                .get("maximum")
+                .and_then(Value::as_f64)
+                .is_some_and(|maximum| number > Some(maximum))
+        {
+            return Err(format!("{} is out of range", value));
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            for item in values {
+                check(document, items, item)?;
+            }
+        }
+        if let Some(object) = value.as_object() {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                if !object.contains_key(required.as_str().unwrap()) {
+                    return Err(format!("{} is missing {}", value, required));
+                }
+            }
+            if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                for (key, field) in object {
+                    match properties.get(key) {
+                        Some(property) => check(document, property, field)?,
+                        None => return Err(format!("{} is not in the schema", key)),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_component(name: &str, value: impl Serialize) {
+        let document = openapi();
+        let schema = serde_json::json!({ "$ref": format!("#/components/schemas/{}", name) });
+        let value = serde_json::to_value(value).unwrap();
+        if let Err(err) = check(&document, &schema, &value) {
+            panic!("{} does not fit the {} schema: {}", value, name, err);
+        }
+    }
+
+    fn states() -> Vec<DeviceState> {
+        vec![
+            DeviceState::Tank {
+                level: Percentage::new(40),
+            },
+            DeviceState::Dimmer {
+                brightness: Percentage::new(100),
+            },
+            DeviceState::Switch {
+                state: SwitchState::On,
+                fault: SwitchState::Off,
+                relay_current: "0.5".to_string(),
+            },
+            DeviceState::Generator {
+                state: GeneratorState::Priming,
+            },
+            DeviceState::Hvac {
+                status: HvacStatus::FailHeatPump,
+                mode: HvacMode::HeatCool,
+                fan: HvacFanMode::Low,
+                inside_temperature: 68.5,
+                outside_temperature: 40.0,
+                high_temperature: 76.0,
+                low_temperature: 66.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn snapshots_fit_the_schema() {
+        let device_types = [
+            DeviceType::Gateway,
+            DeviceType::Tank,
+            DeviceType::RgbLights,
+            DeviceType::Hvac,
+            DeviceType::Dimmer,
+            DeviceType::Generator,
+            DeviceType::Switch,
+        ];
+        let online = [
+            OnlineState::Online,
+            OnlineState::Offline,
+            OnlineState::Locked,
+        ];
+        for (index, state) in states().into_iter().enumerate() {
+            let snapshot: DeviceSnapshot = serde_json::from_value(serde_json::json!({
+                "label": "Device",
+                "uid": "idsmyrv:device:0001",
+                "device_type": device_types[index],
+                "online": online[index % online.len()],
+                "state": state,
+            }))
+            .unwrap();
+            check_component("DeviceSnapshot", &snapshot);
+        }
+    }
+
+    #[test]
+    fn checks_reject_unlisted_properties() {
+        let document = openapi();
+        let schema = serde_json::json!({ "$ref": "#/components/schemas/Setpoints" });
+        let value =
+            serde_json::json!({ "high_temperature": 76, "low_temperature": 66, "mode": "Cool" });
+        assert!(check(&document, &schema, &value).is_err());
+        let value = serde_json::json!({ "high_temperature": 76 });
+        assert!(check(&document, &schema, &value).is_err());
+    }
+
+    #[test]
+    fn commands_fit_the_schema() {
+        for command in [
+            DeviceCommand::On,
+            DeviceCommand::Off,
+            DeviceCommand::SetBrightness(40),
+            DeviceCommand::SetMode(HvacMode::Cool),
+            DeviceCommand::SetFan(HvacFanMode::Auto),
+            DeviceCommand::SetHighTemperature(76),
+            DeviceCommand::SetLowTemperature(-4),
+        ] {
+            check_component("DeviceCommand", &command);
+        }
+    }
+
+    #[test]
+    fn setpoints_fit_the_schema() {
+        check_component("SetpointsUpdate", SetpointsUpdate::default());
+        check_component(
+            "SetpointsUpdate",
+            SetpointsUpdate {
+                high_temperature: Some(76),
+                low_temperature: Some(66),
+            },
+        );
+        check_component(
+            "Setpoints",
+            Setpoints {
+                high_temperature: 76.0,
+                low_temperature: 66.0,
+            },
+        );
+        let update: SetpointsUpdate =
+            serde_json::from_value(serde_json::json!({ "low_temperature": 64 })).unwrap();
+        assert_eq!(update.high_temperature, None);
+    }
+
+    #[test]
+    fn schema_enums_deserialize() {
+        let document = openapi();
+        let schemas = &document["components"]["schemas"];
+        for value in schemas["HvacMode"]["enum"].as_array().unwrap() {
+            serde_json::from_value::<HvacMode>(value.clone()).unwrap();
+        }
+        for value in schemas["HvacFanMode"]["enum"].as_array().unwrap() {
+            serde_json::from_value::<HvacFanMode>(value.clone()).unwrap();
+        }
+        for value in schemas["HvacStatus"]["enum"].as_array().unwrap() {
+            serde_json::from_value::<HvacStatus>(value.clone()).unwrap();
+        }
+        let device_types = &schemas["DeviceSnapshot"]["properties"]["device_type"]["enum"];
+        for value in device_types.as_array().unwrap() {
+            serde_json::from_value::<DeviceType>(value.clone()).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn maps_errors_to_statuses() {
+        let unauthorized =
+            DeviceStateError::TankLevel(TankLevelError::GetFailure(GetFailure::Unauthorized));
+        let missing = DeviceStateError::TankLevel(TankLevelError::GetFailure(
+            GetFailure::MissingChannel("tank_level".to_string()),
+        ));
+        let unparsed = DeviceStateError::TankLevel(TankLevelError::Parse(
+            "x".to_string(),
+            "x".parse::<u8>().unwrap_err(),
+        ));
+        let unsent = reqwest::Client::new()
+            .get("not a url")
+            .send()
+            .await
+            .unwrap_err();
+        let cases = [
+            (RequestError::NotFound("Bedroom".to_string()), 404),
+            (RequestError::Things(ThingError::Unauthorized), 403),
+            (
+                RequestError::Things(ThingError::ConvertingJson(
+                    serde_json::from_str::<Value>("{").unwrap_err(),
+                )),
+                502,
+            ),
+            (RequestError::State(unauthorized), 403),
+            (RequestError::State(missing), 404),
+            (RequestError::State(unparsed), 502),
+            (
+                RequestError::Command(DeviceCommandError::Unsupported(
+                    DeviceType::Tank,
+                    DeviceCommand::On,
+                )),
+                422,
+            ),
+            (
+                RequestError::Command(DeviceCommandError::SetBrightness(
+                    SetBrightnessError::InvalidValue(101),
+                )),
+                422,
+            ),
+            (
+                RequestError::Command(DeviceCommandError::SetError(SetError::Unauthorized)),
+                403,
+            ),
+            (
+                RequestError::Command(DeviceCommandError::SetBrightness(
+                    SetBrightnessError::SetError(SetError::MissingChannel(
+                        "brightness".to_string(),
+                    )),
+                )),
+                422,
+            ),
+            (
+                RequestError::Command(DeviceCommandError::SetError(SetError::Send(unsent))),
+                502,
+            ),
+        ];
+        for (error, status) in cases {
+            assert_eq!(error.status().as_u16(), status, "{:?}", error);
+        }
+    }
+}
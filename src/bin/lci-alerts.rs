@@ -0,0 +1,100 @@
+//! Raises alerts when devices cross thresholds, ex: the fresh tank running low or an HVAC failing.
+//!
+//! Configured through environment variables:
+//! * `LCI_ADDRESS` - the gateway, defaults to "http://192.168.1.4:8080"
+//! * `LCI_TOKEN` - an API token for the gateway, if required
+//! * `LCI_USERNAME` and `LCI_PASSWORD` - HTTP basic auth for the gateway, used without `LCI_TOKEN`
+//! * `LCI_TANKS` - a JSON file of tank settings keyed by label or UID, if any
+//! * `ALERT_RULES` - a JSON file with a list of rules, see `lci_gateway::alert::Rule`
+//! * `ALERT_COOLDOWN_SECONDS` - how long an alert stays quiet after firing, defaults to 900
+//! * `ALERT_WEBHOOK` - a URL to POST each alert to as JSON
+//! * `SMTP_HOST`, `SMTP_PORT`, `SMTP_FROM`, `SMTP_TO`, `SMTP_USERNAME`, `SMTP_PASSWORD` - email each alert,
+//!   with the `smtp` feature. STARTTLS is used when a username is set, otherwise the host is trusted as a plain relay.
+//!   `SMTP_PORT` defaults to 587 with STARTTLS and 25 without.
+//! * `POLL_SECONDS` - how often every device is read, defaults to 30, at least 1
+//!
+//! Devices that can't be read at startup count as offline from then on, so `offline_for_seconds` rules cover them.
+//!
+//! Alerts are always printed to stdout, along with HVAC diagnostics: failures that start, persist and clear,
+//! and cooling that doesn't lower the inside temperature.
+
+use lci_gateway::alert::{AlertEngine, Rule, StdoutSink, WebhookSink};
+use lci_gateway::{Device, DeviceType, HvacMonitor, OnlineState, Watcher};
+use std::collections::HashMap;
+use std::env;
+use std::time::{Duration, SystemTime};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let client = lci_gateway::Client::from_env()?;
+    let rules: Vec<Rule> =
+        serde_json::from_str(&std::fs::read_to_string(env::var("ALERT_RULES")?)?)?;
+    let number = |name: &str, default: u64| {
+        env::var(name)
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(default)
+    };
+
+    let mut engine = AlertEngine::new(rules)
+        .with_cooldown(Duration::from_secs(number("ALERT_COOLDOWN_SECONDS", 900)))
+        .with_sink(StdoutSink);
+    if let Ok(url) = env::var("ALERT_WEBHOOK") {
+        engine = engine.with_sink(WebhookSink::new(url));
+    }
+    #[cfg(feature = "smtp")]
+    if let Ok(host) = env::var("SMTP_HOST") {
+        let from = env::var("SMTP_FROM")?;
+        let to = env::var("SMTP_TO")?;
+        let to: Vec<&str> = to.split(',').map(str::trim).collect();
+        let port = |default: u16| match env::var("SMTP_PORT") {
+            Ok(port) => port
+                .parse::<u16>()
+                .map_err(|err| format!("SMTP_PORT '{}' is not a port. {}", port, err)),
+            Err(_) => Ok(default),
+        };
+        let sink = match env::var("SMTP_USERNAME") {
+            Ok(username) => lci_gateway::alert::SmtpSink::new(
+                &host,
+                port(587)?,
+                &from,
+                &to,
+                Some((username, env::var("SMTP_PASSWORD").unwrap_or_default())),
+            )?,
+            Err(_) => lci_gateway::alert::SmtpSink::unencrypted(&host, port(25)?, &from, &to)?,
+        };
+        engine = engine.with_sink(sink);
+    }
+
+    let devices = client.devices().await?;
+    for device in &devices {
+        if let Device::Tank(tank) = device {
+            if let Some(kind) = tank.kind() {
+                engine.set_tank_kind(tank.uid(), kind);
+            }
+        }
+    }
+    let mut watcher = Watcher::new(devices);
+    let mut monitors: HashMap<String, HvacMonitor> = HashMap::new();
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(number("POLL_SECONDS", 30).max(1)));
+    loop {
+        interval.tick().await;
+        watcher.poll().await;
+        let now = SystemTime::now();
+        let snapshots: Vec<_> = watcher.snapshots().into_iter().cloned().collect();
+        for snapshot in snapshots.iter().filter(|snapshot| {
+            *snapshot.device_type() == DeviceType::Hvac && *snapshot.online() == OnlineState::Online
+        }) {
+            let monitor = monitors.entry(snapshot.uid().clone()).or_default();
+            for event in monitor.observe(now, snapshot) {
+                println!("{} {}.", snapshot.label(), event);
+            }
+        }
+        engine
+            .evaluate_with_unread(&snapshots, &watcher.unread(), now)
+            .await;
+    }
+}
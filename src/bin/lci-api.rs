@@ -0,0 +1,21 @@
+//! Serves a typed REST/JSON API for the devices in the LCI gateway.
+//!
+//! Configured through environment variables:
+//! * `LCI_ADDRESS` - the gateway, defaults to "http://192.168.1.4:8080"
+//! * `LCI_TOKEN` - an API token for the gateway, if required
+//! * `LCI_USERNAME` and `LCI_PASSWORD` - HTTP basic auth for the gateway, used without `LCI_TOKEN`
+//! * `LCI_TANKS` - a JSON file of tank settings keyed by label or UID, if any
+//! * `LISTEN` - the address to serve the API on, defaults to "0.0.0.0:8090"
+
+use std::env;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let client = lci_gateway::Client::from_env()?;
+    let listen = env::var("LISTEN").unwrap_or("0.0.0.0:8090".to_string());
+
+    lci_gateway::api::serve(client, &listen).await?;
+    Ok(())
+}
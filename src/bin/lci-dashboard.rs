@@ -0,0 +1,342 @@
+//! A terminal dashboard for the coach: tanks, HVAC zones, lights, switches and the generator.
+//!
+//! Keys:
+//! * Up/Down - select a device
+//! * Space/Enter - toggle the selected light, switch or generator, or cycle the HVAC mode
+//! * +/- - change the selected dimmer's brightness, or the HVAC's cooling setpoint
+//! * ]/[ - change the HVAC's heating setpoint
+//! * f - cycle the HVAC's fan mode
+//! * r - refresh now
+//! * q/Esc - quit
+//!
+//! Configured through `LCI_ADDRESS`, `LCI_TOKEN`, `LCI_USERNAME`, `LCI_PASSWORD`, `LCI_TANKS` and `REFRESH_SECONDS` (defaults to 5, at least 1).
+
+use crossterm::event::{Event, EventStream, KeyCode, KeyEventKind};
+use futures::StreamExt;
+use lci_gateway::{
+    DeviceCommand, DeviceSnapshot, DeviceState, DeviceType, GeneratorState, HvacFanMode, HvacMode,
+    SwitchState, Watcher,
+};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::env;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = lci_gateway::Client::from_env()?;
+    let refresh = env::var("REFRESH_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(5)
+        .max(1);
+
+    let mut dashboard = Dashboard::new(Watcher::new(client.devices().await?));
+    let mut terminal = ratatui::init();
+    let result = dashboard
+        .run(&mut terminal, Duration::from_secs(refresh))
+        .await;
+    ratatui::restore();
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+    }
+    Ok(())
+}
+
+struct Dashboard {
+    watcher: Watcher,
+    /// Indexes into the watcher's devices of everything that can be selected, lights and switches first.
+    controls: Vec<usize>,
+    selected: usize,
+    message: String,
+}
+
+impl Dashboard {
+    fn new(watcher: Watcher) -> Self {
+        let devices = watcher.devices();
+        let mut controls: Vec<usize> = (0..devices.len())
+            .filter(|index| {
+                matches!(
+                    devices[*index].device_type(),
+                    DeviceType::Dimmer | DeviceType::Switch | DeviceType::Generator
+                )
+            })
+            .collect();
+        controls.extend(
+            (0..devices.len()).filter(|index| devices[*index].device_type() == DeviceType::Hvac),
+        );
+        Self {
+            watcher,
+            controls,
+            selected: 0,
+            message: "Loading...".to_string(),
+        }
+    }
+
+    async fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        refresh: Duration,
+    ) -> std::io::Result<()> {
+        let mut events = EventStream::new();
+        let mut ticker = tokio::time::interval(refresh);
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            tokio::select! {
+                _ = ticker.tick() => {
+                    self.watcher.poll().await;
+                    if self.message == "Loading..." {
+                        self.message = String::new();
+                    }
+                }
+                event = events.next() => match event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                        match key.code {
+                            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                            KeyCode::Down => {
+                                self.selected = (self.selected + 1).min(self.controls.len().saturating_sub(1))
+                            }
+                            KeyCode::Char('r') => {
+                                self.watcher.poll().await;
+                            }
+                            code => {
+                                if let Some(command) = self.command_for(code) {
+                                    self.execute(command).await;
+                                }
+                            }
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => return Err(err),
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+
+    fn selected_snapshot(&self) -> Option<&DeviceSnapshot> {
+        let index = *self.controls.get(self.selected)?;
+        self.watcher.snapshot(&self.watcher.devices()[index].uid())
+    }
+
+    /// Maps a key to a command for the selected device, based on its last snapshot.
+    fn command_for(&self, code: KeyCode) -> Option<DeviceCommand> {
+        let state = self.selected_snapshot()?.state();
+        match (state, code) {
+            (DeviceState::Dimmer { brightness }, KeyCode::Char(' ') | KeyCode::Enter) => {
+                Some(if brightness.value() > 0 {
+                    DeviceCommand::Off
+                } else {
+                    DeviceCommand::On
+                })
+            }
+            (DeviceState::Dimmer { brightness }, KeyCode::Char('+') | KeyCode::Char('=')) => Some(
+                DeviceCommand::SetBrightness((brightness.value() + 10).min(100)),
+            ),
+            (DeviceState::Dimmer { brightness }, KeyCode::Char('-')) => Some(
+                DeviceCommand::SetBrightness(brightness.value().saturating_sub(10)),
+            ),
+            (
+                DeviceState::Switch { .. } | DeviceState::Generator { .. },
+                KeyCode::Char(' ') | KeyCode::Enter,
+            ) => Some(match state.is_on() {
+                Some(true) => DeviceCommand::Off,
+                _ => DeviceCommand::On,
+            }),
+            (DeviceState::Hvac { mode, .. }, KeyCode::Char(' ') | KeyCode::Enter) => {
+                Some(DeviceCommand::SetMode(match mode {
+                    HvacMode::Off => HvacMode::Heat,
+                    HvacMode::Heat => HvacMode::Cool,
+                    HvacMode::Cool => HvacMode::HeatCool,
+                    HvacMode::HeatCool => HvacMode::Off,
+                }))
+            }
+            (DeviceState::Hvac { fan, .. }, KeyCode::Char('f')) => {
+                Some(DeviceCommand::SetFan(match fan {
+                    HvacFanMode::Auto => HvacFanMode::Low,
+                    HvacFanMode::Low => HvacFanMode::High,
+                    HvacFanMode::High => HvacFanMode::Auto,
+                }))
+            }
+            (
+                DeviceState::Hvac {
+                    high_temperature, ..
+                },
+                KeyCode::Char('+') | KeyCode::Char('='),
+            ) => Some(DeviceCommand::SetHighTemperature(
+                high_temperature.round() as isize + 1,
+            )),
+            (
+                DeviceState::Hvac {
+                    high_temperature, ..
+                },
+                KeyCode::Char('-'),
+            ) => Some(DeviceCommand::SetHighTemperature(
+                high_temperature.round() as isize - 1,
+            )),
+            (
+                DeviceState::Hvac {
+                    low_temperature, ..
+                },
+                KeyCode::Char(']'),
+            ) => Some(DeviceCommand::SetLowTemperature(
+                low_temperature.round() as isize + 1,
+            )),
+            (
+                DeviceState::Hvac {
+                    low_temperature, ..
+                },
+                KeyCode::Char('['),
+            ) => Some(DeviceCommand::SetLowTemperature(
+                low_temperature.round() as isize - 1,
+            )),
+            _ => None,
+        }
+    }
+
+    async fn execute(&mut self, command: DeviceCommand) {
+        let Some(index) = self.controls.get(self.selected).copied() else {
+            return;
+        };
+        let device = &mut self.watcher.devices_mut()[index];
+        self.message = match device.execute(&command).await {
+            Ok(()) => format!("{}: {}", device.label(), command),
+            Err(err) => format!("{}: {}", device.label(), err),
+        };
+        self.watcher.poll().await;
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let snapshots = self.watcher.snapshots();
+        let tanks: Vec<&DeviceSnapshot> = snapshots
+            .iter()
+            .copied()
+            .filter(|snapshot| *snapshot.device_type() == DeviceType::Tank)
+            .collect();
+
+        let [tank_area, main_area, help_area] = Layout::vertical([
+            Constraint::Length(3 * tanks.len().max(1) as u16),
+            Constraint::Min(6),
+            Constraint::Length(2),
+        ])
+        .areas(frame.area());
+        let [controls_area, hvac_area] =
+            Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)])
+                .areas(main_area);
+
+        self.draw_tanks(frame, tank_area, &tanks);
+        self.draw_controls(frame, controls_area, false);
+        self.draw_controls(frame, hvac_area, true);
+
+        let help = "↑/↓ select  space toggle/mode  +/- brightness/cool  [/] heat  f fan  r refresh  q quit";
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(self.message.as_str()), Line::from(help)])
+                .style(Style::default().fg(Color::DarkGray)),
+            help_area,
+        );
+    }
+
+    fn draw_tanks(&self, frame: &mut Frame, area: Rect, tanks: &[&DeviceSnapshot]) {
+        if tanks.is_empty() {
+            frame.render_widget(Paragraph::new("No tanks").block(Block::bordered()), area);
+            return;
+        }
+        let areas = Layout::vertical(vec![Constraint::Length(3); tanks.len()]).split(area);
+        for (tank, area) in tanks.iter().zip(areas.iter()) {
+            let level = match tank.state() {
+                DeviceState::Tank { level } => level.value().min(100),
+                _ => 0,
+            };
+            let color = if level < 20 { Color::Red } else { Color::Blue };
+            frame.render_widget(
+                Gauge::default()
+                    .block(Block::bordered().title(tank.label().as_str()))
+                    .gauge_style(Style::default().fg(color))
+                    .percent(level as u16),
+                *area,
+            );
+        }
+    }
+
+    /// Draws either the lights/switches/generator panel or the HVAC panel.
+    fn draw_controls(&self, frame: &mut Frame, area: Rect, hvac: bool) {
+        let devices = self.watcher.devices();
+        let mut items = Vec::new();
+        let mut state = ListState::default();
+        for (position, index) in self.controls.iter().enumerate() {
+            let device = &devices[*index];
+            if (device.device_type() == DeviceType::Hvac) != hvac {
+                continue;
+            }
+            if position == self.selected {
+                state.select(Some(items.len()));
+            }
+            let text = match self.watcher.snapshot(&device.uid()) {
+                Some(snapshot) => describe(snapshot),
+                None => format!("{}  …", device.label()),
+            };
+            items.push(ListItem::new(text));
+        }
+        let title = if hvac {
+            "HVAC"
+        } else {
+            "Lights, switches & generator"
+        };
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+fn describe(snapshot: &DeviceSnapshot) -> String {
+    let offline = if *snapshot.online() == lci_gateway::OnlineState::Online {
+        ""
+    } else {
+        " (offline)"
+    };
+    let state = match snapshot.state() {
+        DeviceState::Dimmer { brightness } if brightness.value() == 0 => "[ ] Off".to_string(),
+        DeviceState::Dimmer { brightness } => format!("[x] {}", brightness),
+        DeviceState::Switch { state, fault, .. } => {
+            let toggle = if *state == SwitchState::On {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            let fault = if *fault == SwitchState::On {
+                " FAULT"
+            } else {
+                ""
+            };
+            format!("{} {}{}", toggle, state, fault)
+        }
+        DeviceState::Generator { state } => {
+            let toggle = if *state == GeneratorState::Off {
+                "[ ]"
+            } else {
+                "[x]"
+            };
+            format!("{} {}", toggle, state)
+        }
+        DeviceState::Hvac {
+            status,
+            mode,
+            fan,
+            inside_temperature,
+            low_temperature,
+            high_temperature,
+            ..
+        } => format!(
+            "{:.0}° {} | mode {} fan {} | heat {:.0}° cool {:.0}°",
+            inside_temperature, status, mode, fan, low_temperature, high_temperature
+        ),
+        state => state.to_string(),
+    };
+    format!("{:<20} {}{}", snapshot.label(), state, offline)
+}
@@ -0,0 +1,68 @@
+//! Bridges the LCI gateway to an MQTT broker.
+//!
+//! Configured through environment variables:
+//! * `LCI_ADDRESS` - the gateway, defaults to "http://192.168.1.4:8080"
+//! * `LCI_TOKEN` - an API token for the gateway, if required
+//! * `LCI_USERNAME` and `LCI_PASSWORD` - HTTP basic auth for the gateway, used without `LCI_TOKEN`
+//! * `LCI_TANKS` - a JSON file of tank settings keyed by label or UID, if any
+//! * `MQTT_HOST` / `MQTT_PORT` - the broker, defaults to "localhost" and 1883
+//! * `MQTT_CLIENT_ID` - defaults to "lci-gateway"
+//! * `MQTT_USERNAME` / `MQTT_PASSWORD` - broker credentials, if required
+//! * `MQTT_BASE_TOPIC` - the topic prefix, defaults to "lci"
+//! * `MQTT_RETAIN` - "false" to publish without the retain flag
+//! * `POLL_SECONDS` - how often devices are read, defaults to 30, at least 1
+//! * `HA_DISCOVERY_PREFIX` - publishes Home Assistant discovery configs under this prefix, ex: "homeassistant"
+//! * `HA_TEMPERATURE_UNIT` - the unit shown in Home Assistant, defaults to "F"
+
+use lci_gateway::home_assistant::HomeAssistantConfig;
+use lci_gateway::mqtt::{Bridge, BridgeConfig};
+use std::env;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let client = lci_gateway::Client::from_env()?;
+
+    let mut config = BridgeConfig::default();
+    if let Ok(host) = env::var("MQTT_HOST") {
+        config.host = host;
+    }
+    if let Some(port) = env::var("MQTT_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+    {
+        config.port = port;
+    }
+    if let Ok(client_id) = env::var("MQTT_CLIENT_ID") {
+        config.client_id = client_id;
+    }
+    config.username = env::var("MQTT_USERNAME").ok();
+    config.password = env::var("MQTT_PASSWORD").ok();
+    if let Ok(base) = env::var("MQTT_BASE_TOPIC") {
+        config.topics.base = base;
+    }
+    if let Ok(retain) = env::var("MQTT_RETAIN") {
+        config.retain = retain != "false";
+    }
+    if let Some(seconds) = env::var("POLL_SECONDS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+    {
+        config.poll_interval = Duration::from_secs(seconds.max(1));
+    }
+    if let Ok(prefix) = env::var("HA_DISCOVERY_PREFIX") {
+        let mut home_assistant = HomeAssistantConfig {
+            prefix,
+            ..Default::default()
+        };
+        if let Ok(unit) = env::var("HA_TEMPERATURE_UNIT") {
+            home_assistant.temperature_unit = unit;
+        }
+        config.home_assistant = Some(home_assistant);
+    }
+
+    Bridge::new(config, client).run().await?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Sends device commands when device states change, ex: turns the water heater on when the generator starts.
+//!
+//! Configured through environment variables:
+//! * `LCI_ADDRESS` - the gateway, defaults to "http://192.168.1.4:8080"
+//! * `LCI_TOKEN` - an API token for the gateway, if required
+//! * `LCI_USERNAME` and `LCI_PASSWORD` - HTTP basic auth for the gateway, used without `LCI_TOKEN`
+//! * `LCI_TANKS` - a JSON file of tank settings keyed by label or UID, if any
+//! * `RULES_FILE` - a JSON file with a list of rules, see `lci_gateway::rules::Rule`
+//! * `POLL_SECONDS` - how often every device is read, defaults to 10, at least 1
+
+use lci_gateway::rules::{Rule, RulesEngine};
+use lci_gateway::Watcher;
+use std::env;
+use std::time::{Duration, SystemTime};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let client = lci_gateway::Client::from_env()?;
+    let rules: Vec<Rule> =
+        serde_json::from_str(&std::fs::read_to_string(env::var("RULES_FILE")?)?)?;
+    let poll_seconds = env::var("POLL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(10)
+        .max(1);
+
+    let mut engine = RulesEngine::new(rules);
+    let mut watcher = Watcher::new(client.devices().await?);
+    let mut interval = tokio::time::interval(Duration::from_secs(poll_seconds));
+    loop {
+        interval.tick().await;
+        let changes = watcher.poll().await;
+        for run in engine
+            .process(&changes, watcher.devices_mut(), SystemTime::now())
+            .await
+        {
+            let became = if *run.matched() { "true" } else { "false" };
+            for result in run.results() {
+                match result.result() {
+                    Ok(()) => log::info!(
+                        "\"{}\" became {}, set {}.",
+                        run.rule(),
+                        became,
+                        result.device()
+                    ),
+                    Err(err) => log::error!(
+                        "\"{}\" became {}, but {} could not be set. {}",
+                        run.rule(),
+                        became,
+                        result.device(),
+                        err
+                    ),
+                }
+            }
+        }
+    }
+}
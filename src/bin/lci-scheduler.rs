@@ -0,0 +1,53 @@
+//! Runs device commands on a schedule kept in a JSON file, loading it again whenever it changes.
+//!
+//! Configured through environment variables:
+//! * `LCI_ADDRESS` - the gateway, defaults to "http://192.168.1.4:8080"
+//! * `LCI_TOKEN` - an API token for the gateway, if required
+//! * `LCI_USERNAME` and `LCI_PASSWORD` - HTTP basic auth for the gateway, used without `LCI_TOKEN`
+//! * `LCI_TANKS` - a JSON file of tank settings keyed by label or UID, if any
+//! * `SCHEDULE_FILE` - the schedule, defaults to "schedule.json"
+//!
+//! ```json
+//! {
+//!   "timezone": "America/Denver",
+//!   "location": {"latitude": 39.74, "longitude": -104.99},
+//!   "jobs": [
+//!     {"name": "Water heater on", "when": {"type": "cron", "cron": "0 6 * * *"},
+//!      "device": "Water Heater", "commands": [{"command": "on"}]},
+//!     {"name": "Water heater off", "when": {"type": "cron", "cron": "0 8 * * *"},
+//!      "device": "Water Heater", "commands": [{"command": "off"}]},
+//!     {"name": "Porch light", "when": {"type": "sun", "event": "sunset"},
+//!      "device": "Porch Light", "commands": [{"command": "on"}]},
+//!     {"name": "Exercise generator", "when": {"type": "cron", "cron": "0 10 * * SUN#1"},
+//!      "device": "Generator", "commands": [{"command": "on"}]}
+//!   ]
+//! }
+//! ```
+
+use lci_gateway::scheduler::Scheduler;
+use std::env;
+use std::time::SystemTime;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let client = lci_gateway::Client::from_env()?;
+
+    let path = env::var("SCHEDULE_FILE").unwrap_or("schedule.json".to_string());
+    let scheduler = Scheduler::from_file(&path, client)?;
+    let schedule = scheduler.schedule();
+    let now = SystemTime::now();
+    for job in schedule.jobs() {
+        match schedule.next_run(job, now) {
+            Some(time) => log::info!(
+                "\"{}\" runs next at {}.",
+                job.name(),
+                chrono::DateTime::<chrono::Utc>::from(time).with_timezone(schedule.timezone())
+            ),
+            None => log::warn!("\"{}\" will never run.", job.name()),
+        }
+    }
+    scheduler.run().await;
+    Ok(())
+}
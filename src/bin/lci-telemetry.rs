@@ -0,0 +1,70 @@
+//! Logs every device reading as InfluxDB line protocol and/or rotating CSV files.
+//!
+//! Configured through environment variables:
+//! * `LCI_ADDRESS` - the gateway, defaults to "http://192.168.1.4:8080"
+//! * `LCI_TOKEN` - an API token for the gateway, if required
+//! * `LCI_USERNAME` and `LCI_PASSWORD` - HTTP basic auth for the gateway, used without `LCI_TOKEN`
+//! * `LCI_TANKS` - a JSON file of tank settings keyed by label or UID, if any
+//! * `INTERVAL_SECONDS` - how often every device is read, defaults to 60, at least 1
+//! * `ON_CHANGE` - set to "true" to only record devices that changed
+//! * `INFLUX_URL` - an InfluxDB write URL with nanosecond precision
+//! * `INFLUX_TOKEN` - an InfluxDB API token, if required
+//! * `INFLUX_FILE` - a file to append line protocol to, instead of `INFLUX_URL`
+//! * `CSV_DIRECTORY` - a directory to write CSV files to
+//! * `CSV_MAX_BYTES` - the size a CSV file is rotated at, defaults to 10 MiB
+//! * `CSV_MAX_FILES` - how many CSV files are kept, defaults to 10
+
+use lci_gateway::telemetry::{CsvConfig, InfluxDestination, Logger, Sampling, TelemetryConfig};
+use std::env;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let client = lci_gateway::Client::from_env()?;
+
+    let mut config = TelemetryConfig::default();
+    if let Some(seconds) = env::var("INTERVAL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+    {
+        config.interval = Duration::from_secs(seconds.max(1));
+    }
+    if env::var("ON_CHANGE").is_ok_and(|value| value == "true") {
+        config.sampling = Sampling::Changes;
+    }
+    if let Ok(url) = env::var("INFLUX_URL") {
+        config.influx = Some(InfluxDestination::Http {
+            url,
+            token: env::var("INFLUX_TOKEN").ok(),
+        });
+    } else if let Ok(path) = env::var("INFLUX_FILE") {
+        config.influx = Some(InfluxDestination::File(path.into()));
+    }
+    if let Ok(directory) = env::var("CSV_DIRECTORY") {
+        let mut csv = CsvConfig {
+            directory: directory.into(),
+            ..CsvConfig::default()
+        };
+        if let Some(max_bytes) = env::var("CSV_MAX_BYTES")
+            .ok()
+            .and_then(|bytes| bytes.parse().ok())
+        {
+            csv.max_bytes = max_bytes;
+        }
+        if let Some(max_files) = env::var("CSV_MAX_FILES")
+            .ok()
+            .and_then(|files| files.parse().ok())
+        {
+            csv.max_files = max_files;
+        }
+        config.csv = Some(csv);
+    }
+    if config.influx.is_none() && config.csv.is_none() {
+        log::warn!("Set INFLUX_URL, INFLUX_FILE or CSV_DIRECTORY to record anything");
+    }
+
+    Logger::new(config, client).run().await?;
+    Ok(())
+}
@@ -0,0 +1,30 @@
+//! Pushes device changes to browsers over a WebSocket and accepts commands from them.
+//!
+//! Configured through environment variables:
+//! * `LCI_ADDRESS` - the gateway, defaults to "http://192.168.1.4:8080"
+//! * `LCI_TOKEN` - an API token for the gateway, if required
+//! * `LCI_USERNAME` and `LCI_PASSWORD` - HTTP basic auth for the gateway, used without `LCI_TOKEN`
+//! * `LCI_TANKS` - a JSON file of tank settings keyed by label or UID, if any
+//! * `LISTEN` - the address to serve `/ws` on, defaults to "0.0.0.0:8091"
+//! * `POLL_SECONDS` - how often every device is read, defaults to 5, at least 1
+
+use lci_gateway::websocket::PushServer;
+use std::env;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let client = lci_gateway::Client::from_env()?;
+    let listen = env::var("LISTEN").unwrap_or("0.0.0.0:8091".to_string());
+    let poll_seconds = env::var("POLL_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(5)
+        .max(1);
+
+    let server = PushServer::new(client).with_poll_interval(Duration::from_secs(poll_seconds));
+    lci_gateway::websocket::serve(server, &listen).await?;
+    Ok(())
+}
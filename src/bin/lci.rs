@@ -0,0 +1,547 @@
+//! Control the coach from the command line.
+//!
+//! ```sh
+//! lci list
+//! lci get "Fresh Water"
+//! lci switch "Water Pump" on
+//! lci dimmer "Kitchen Lights" 40
+//! lci hvac Bedroom set-mode cool
+//! lci scene capture Movie "Living Lights" "Water Pump" Bedroom > movie.json
+//! lci scene apply movie.json
+//! lci group Kitchen off
+//! lci --groups groups.json group Exterior brightness 30
+//! lci --json watch
+//! ```
+
+use clap::{Parser, Subcommand};
+use lci_gateway::{
+    Client, Device, DeviceCommand, DeviceType, Group, GroupResult, HvacFanMode, HvacMode, Scene,
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Parser)]
+#[command(name = "lci", about = "Control devices through an LCI gateway.")]
+struct Cli {
+    /// The address of the gateway, instead of `LCI_ADDRESS`.
+    #[arg(long, global = true)]
+    address: Option<String>,
+    /// An API token for the gateway, instead of `LCI_TOKEN`.
+    #[arg(long, global = true)]
+    token: Option<String>,
+    /// A user for HTTP basic auth, instead of `LCI_USERNAME`.
+    #[arg(long, global = true)]
+    username: Option<String>,
+    /// The password for HTTP basic auth.
+    #[arg(long, env = "LCI_PASSWORD", global = true)]
+    password: Option<String>,
+    /// A JSON file of tank settings keyed by label or UID, instead of `LCI_TANKS`, ex: `{"Fresh Water": {"calibration": [[0, 0], [50, 40], [100, 100]]}}`.
+    #[arg(long, global = true)]
+    tanks: Option<PathBuf>,
+    /// A JSON file of groups, ex: `[{"name": "Exterior", "members": ["Porch Light", "Awning"]}]`.
+    #[arg(long, env = "LCI_GROUPS")]
+    groups: Option<PathBuf>,
+    /// Print JSON instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List every thing with its type and online state.
+    List,
+    /// Print everything a device reports.
+    Get {
+        /// The device's label or UID.
+        device: String,
+    },
+    /// Turn a switch on or off.
+    Switch {
+        /// The switch's label or UID.
+        device: String,
+        /// "on" or "off".
+        #[arg(value_parser = parse_on_off)]
+        state: DeviceCommand,
+    },
+    /// Turn a dimmer on or off, or set its brightness.
+    Dimmer {
+        /// The dimmer's label or UID.
+        device: String,
+        /// "on", "off" or a brightness between 0 and 100.
+        #[arg(value_parser = parse_dimmer)]
+        state: DeviceCommand,
+    },
+    /// Start or stop a generator.
+    Generator {
+        /// The generator's label or UID.
+        device: String,
+        /// "on" or "off".
+        #[arg(value_parser = parse_on_off)]
+        state: DeviceCommand,
+    },
+    /// Change an HVAC's mode, fan or setpoints.
+    Hvac {
+        /// The HVAC's label or UID.
+        device: String,
+        #[command(subcommand)]
+        action: HvacAction,
+    },
+    /// Save or restore the state of many devices.
+    Scene {
+        #[command(subcommand)]
+        action: SceneAction,
+    },
+    /// Control or read every device in a group, or in a room when no group has the name.
+    Group {
+        /// The group's name, or a room name that device labels contain.
+        group: String,
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+    /// Print devices as they change.
+    Watch {
+        /// Seconds between reads of every device.
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::enum_variant_names)]
+enum HvacAction {
+    /// "off", "heat", "cool" or "heatcool".
+    SetMode {
+        #[arg(value_parser = parse_mode)]
+        mode: HvacMode,
+    },
+    /// "auto", "low" or "high".
+    SetFan {
+        #[arg(value_parser = parse_fan)]
+        fan: HvacFanMode,
+    },
+    /// The temperature to start cooling at.
+    SetHigh { temperature: isize },
+    /// The temperature to start heating at.
+    SetLow { temperature: isize },
+}
+
+#[derive(Subcommand)]
+enum SceneAction {
+    /// Print a scene of the devices as they are now, as JSON.
+    Capture {
+        /// The scene's name.
+        name: String,
+        /// The labels or UIDs of the dimmers, switches and HVACs to capture.
+        #[arg(required = true)]
+        devices: Vec<String>,
+    },
+    /// Put the devices in a scene file in their scene state.
+    Apply {
+        /// The JSON file written by capture.
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum GroupAction {
+    /// Turn every switch and dimmer on.
+    On,
+    /// Turn every switch and dimmer off.
+    Off,
+    /// Set the brightness of every dimmer.
+    Brightness {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=100))]
+        brightness: u8,
+    },
+    /// Print every member's state.
+    State,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    if let Err(err) = run(cli).await {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), CliError> {
+    let mut client = Client::from_env()?;
+    if let Some(address) = &cli.address {
+        client = client.with_address(address.clone());
+    }
+    if let Some(token) = &cli.token {
+        client = client.with_token(token.clone());
+    } else if let Some(username) = &cli.username {
+        client = client.with_basic_auth(username.clone(), cli.password.clone());
+    }
+    if let Some(tanks) = &cli.tanks {
+        client = client.with_tank_configs(lci_gateway::TankConfigs::load(tanks)?);
+    }
+
+    match cli.command {
+        Command::List => list(&client, cli.json).await,
+        Command::Get { device } => {
+            let device = find(&client, &device, None).await?;
+            let snapshot = device.snapshot().await?;
+            if cli.json {
+                println!("{}", to_json(&snapshot)?);
+            } else {
+                println!(
+                    "{} [{}] = {}",
+                    snapshot.label(),
+                    snapshot.online(),
+                    snapshot.state()
+                );
+            }
+            Ok(())
+        }
+        Command::Switch { device, state } => {
+            control(&client, &device, DeviceType::Switch, state, cli.json).await
+        }
+        Command::Dimmer { device, state } => {
+            control(&client, &device, DeviceType::Dimmer, state, cli.json).await
+        }
+        Command::Generator { device, state } => {
+            control(&client, &device, DeviceType::Generator, state, cli.json).await
+        }
+        Command::Hvac { device, action } => {
+            let command = match action {
+                HvacAction::SetMode { mode } => DeviceCommand::SetMode(mode),
+                HvacAction::SetFan { fan } => DeviceCommand::SetFan(fan),
+                HvacAction::SetHigh { temperature } => {
+                    DeviceCommand::SetHighTemperature(temperature)
+                }
+                HvacAction::SetLow { temperature } => DeviceCommand::SetLowTemperature(temperature),
+            };
+            control(&client, &device, DeviceType::Hvac, command, cli.json).await
+        }
+        Command::Scene { action } => match action {
+            SceneAction::Capture { name, devices } => capture(&client, name, &devices).await,
+            SceneAction::Apply { file } => apply(&client, &file, cli.json).await,
+        },
+        Command::Group { group, action } => {
+            group_command(&client, cli.groups.as_deref(), &group, action, cli.json).await
+        }
+        Command::Watch { interval } => watch(&client, interval, cli.json).await,
+    }
+}
+
+async fn list(client: &Client, json: bool) -> Result<(), CliError> {
+    let things = client.get_things().await?;
+    let onlines = futures::future::join_all(things.iter().map(|thing| thing.online())).await;
+    let mut rows = Vec::new();
+    for (thing, online) in things.iter().zip(onlines) {
+        let device_type = thing
+            .get_type()
+            .map(|device_type| device_type.to_string())
+            .unwrap_or("Unknown".to_string());
+        let online = online
+            .map(|online| online.to_string())
+            .unwrap_or("Unknown".to_string());
+        if json {
+            rows.push(serde_json::json!({
+                "label": thing.label(),
+                "uid": thing.UID(),
+                "type": device_type,
+                "online": online,
+            }));
+        } else {
+            println!(
+                "{:<24} {:<10} {:<8} {}",
+                thing.label(),
+                device_type,
+                online,
+                thing.UID()
+            );
+        }
+    }
+    if json {
+        println!("{}", to_json(&rows)?);
+    }
+    Ok(())
+}
+
+async fn control(
+    client: &Client,
+    device: &str,
+    device_type: DeviceType,
+    command: DeviceCommand,
+    json: bool,
+) -> Result<(), CliError> {
+    let mut device = find(client, device, Some(device_type)).await?;
+    device.execute(&command).await?;
+    if json {
+        println!(
+            "{}",
+            to_json(&serde_json::json!({
+                "label": device.label(),
+                "uid": device.uid(),
+                "command": command,
+            }))?
+        );
+    } else {
+        println!("{}: {}", device.label(), command);
+    }
+    Ok(())
+}
+
+async fn capture(client: &Client, name: String, names: &[String]) -> Result<(), CliError> {
+    let mut devices = client.devices().await?;
+    for name in names {
+        if !devices.iter().any(|device| device.matches(name)) {
+            return Err(CliError::NotFound(name.clone()));
+        }
+    }
+    devices.retain(|device| names.iter().any(|name| device.matches(name)));
+    let (scene, results) = Scene::capture(name, &devices).await;
+    println!("{}", to_json(&scene)?);
+    let mut captured = true;
+    for result in results {
+        if let Err(err) = result.result() {
+            eprintln!("{}", err);
+            captured = false;
+        }
+    }
+    if captured {
+        Ok(())
+    } else {
+        Err(CliError::Capture(scene.name().clone()))
+    }
+}
+
+async fn apply(client: &Client, file: &Path, json: bool) -> Result<(), CliError> {
+    let scene: Scene =
+        serde_json::from_str(&std::fs::read_to_string(file)?).map_err(CliError::Json)?;
+    let mut devices = client.devices().await?;
+    let results = scene.apply(&mut devices).await;
+    if json {
+        let rows: Vec<_> = results
+            .iter()
+            .map(|result| {
+                serde_json::json!({
+                    "device": result.device(),
+                    "error": result.result().as_ref().err().map(|err| err.to_string()),
+                })
+            })
+            .collect();
+        println!("{}", to_json(&rows)?);
+    } else {
+        for result in &results {
+            match result.result() {
+                Ok(()) => println!("{}: ok", result.device()),
+                Err(err) => println!("{}: {}", result.device(), err),
+            }
+        }
+    }
+    if results.iter().all(|result| result.is_ok()) {
+        Ok(())
+    } else {
+        Err(CliError::Scene(scene.name().clone()))
+    }
+}
+
+async fn group_command(
+    client: &Client,
+    groups: Option<&Path>,
+    name: &str,
+    action: GroupAction,
+    json: bool,
+) -> Result<(), CliError> {
+    let mut devices = client.devices().await?;
+    let groups: Vec<Group> = match groups {
+        Some(file) => {
+            serde_json::from_str(&std::fs::read_to_string(file)?).map_err(CliError::Json)?
+        }
+        None => Vec::new(),
+    };
+    let group = groups
+        .into_iter()
+        .find(|group| group.name().eq_ignore_ascii_case(name))
+        .unwrap_or_else(|| Group::room(name, &devices));
+    if group.members().is_empty() {
+        return Err(CliError::NotFound(name.to_string()));
+    }
+
+    let results = match action {
+        GroupAction::On => group.on(&mut devices).await,
+        GroupAction::Off => group.off(&mut devices).await,
+        GroupAction::Brightness { brightness } => {
+            group.set_brightness(&mut devices, brightness).await
+        }
+        GroupAction::State => {
+            let state = group.state(&devices).await;
+            if json {
+                let rows: Vec<_> = state
+                    .members()
+                    .iter()
+                    .map(|member| match member.result() {
+                        Ok(state) => serde_json::json!({"device": member.device(), "state": state}),
+                        Err(err) => {
+                            serde_json::json!({"device": member.device(), "error": err.to_string()})
+                        }
+                    })
+                    .collect();
+                println!("{}", to_json(&rows)?);
+            } else {
+                for member in state.members() {
+                    match member.result() {
+                        Ok(state) => println!("{} = {}", label(&devices, member.device()), state),
+                        Err(err) => println!("{}: {}", member.device(), err),
+                    }
+                }
+                println!("{} on, {} off", state.on(), state.off());
+                if let Some(brightness) = state.average_brightness() {
+                    println!("average brightness {}%", brightness);
+                }
+            }
+            return Ok(());
+        }
+    };
+    report(&devices, group.name(), &results, json)
+}
+
+/// Prints each member's result and fails unless every member succeeded.
+fn report(
+    devices: &[Device],
+    group: &str,
+    results: &[GroupResult<()>],
+    json: bool,
+) -> Result<(), CliError> {
+    if json {
+        let rows: Vec<_> = results
+            .iter()
+            .map(|result| {
+                serde_json::json!({
+                    "device": result.device(),
+                    "error": result.result().as_ref().err().map(|err| err.to_string()),
+                })
+            })
+            .collect();
+        println!("{}", to_json(&rows)?);
+    } else {
+        for result in results {
+            match result.result() {
+                Ok(()) => println!("{}: ok", label(devices, result.device())),
+                Err(err) => println!("{}: {}", label(devices, result.device()), err),
+            }
+        }
+    }
+    if results.iter().all(|result| result.is_ok()) {
+        Ok(())
+    } else {
+        Err(CliError::Group(group.to_string()))
+    }
+}
+
+/// Returns the label of the device with the label or UID, or the name as given.
+fn label(devices: &[Device], name: &str) -> String {
+    devices
+        .iter()
+        .find(|device| device.matches(name))
+        .map(|device| device.label())
+        .unwrap_or(name.to_string())
+}
+
+async fn watch(client: &Client, interval: u64, json: bool) -> Result<(), CliError> {
+    let mut watcher = lci_gateway::Watcher::new(client.devices().await?);
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+    loop {
+        ticker.tick().await;
+        for change in watcher.poll().await {
+            let current = change.current();
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&change).map_err(CliError::Json)?
+                );
+            } else {
+                println!(
+                    "{} [{}] = {}",
+                    current.label(),
+                    current.online(),
+                    current.state()
+                );
+            }
+        }
+    }
+}
+
+async fn find(
+    client: &Client,
+    name: &str,
+    device_type: Option<DeviceType>,
+) -> Result<Device, CliError> {
+    client
+        .devices()
+        .await?
+        .into_iter()
+        .filter(|device| device_type.is_none() || Some(device.device_type()) == device_type)
+        .find(|device| device.matches(name))
+        .ok_or_else(|| CliError::NotFound(name.to_string()))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, CliError> {
+    serde_json::to_string_pretty(value).map_err(CliError::Json)
+}
+
+fn parse_on_off(value: &str) -> Result<DeviceCommand, String> {
+    match value.to_lowercase().as_str() {
+        "on" => Ok(DeviceCommand::On),
+        "off" => Ok(DeviceCommand::Off),
+        _ => Err(format!("expected \"on\" or \"off\", got \"{}\"", value)),
+    }
+}
+
+fn parse_dimmer(value: &str) -> Result<DeviceCommand, String> {
+    parse_on_off(value).or_else(|_| match value.trim_end_matches('%').parse::<u8>() {
+        Ok(brightness) if brightness <= 100 => Ok(DeviceCommand::SetBrightness(brightness)),
+        _ => Err(format!(
+            "expected \"on\", \"off\" or 0-100, got \"{}\"",
+            value
+        )),
+    })
+}
+
+fn parse_mode(value: &str) -> Result<HvacMode, String> {
+    HvacMode::from_string(value.replace(['_', '-'], "")).map_err(|err| err.to_string())
+}
+
+fn parse_fan(value: &str) -> Result<HvacFanMode, String> {
+    HvacFanMode::from_string(value.to_string()).map_err(|err| err.to_string())
+}
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error("No device matches \"{0}\".")]
+    NotFound(String),
+    #[error("{0}")]
+    Things(#[from] lci_gateway::ThingError),
+    #[error("{0}")]
+    State(#[from] lci_gateway::DeviceStateError),
+    #[error("{0}")]
+    Command(Box<lci_gateway::DeviceCommandError>),
+    #[error("{0}")]
+    Json(serde_json::Error),
+    #[error("Not every device in \"{0}\" could be read.")]
+    Capture(String),
+    #[error("Not every device in \"{0}\" could be set.")]
+    Scene(String),
+    #[error("Not every device in \"{0}\" succeeded.")]
+    Group(String),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Tanks(#[from] lci_gateway::TankConfigError),
+}
+
+impl From<lci_gateway::DeviceCommandError> for CliError {
+    fn from(err: lci_gateway::DeviceCommandError) -> Self {
+        Self::Command(Box::new(err))
+    }
+}
@@ -0,0 +1,244 @@
+use super::{TankConfigError, TankConfigs, Thing, ThingError};
+use std::sync::Arc;
+
+/// The address used by `Client::default()` and `get_things()`.
+pub const DEFAULT_ADDRESS: &str = "http://192.168.1.4:8080";
+
+/// Credentials sent to the LCI gateway with every request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Authentication {
+    /// No credentials are sent. Works for the stock gateway firmware.
+    None,
+    /// An API token, sent as a bearer token.
+    Token(String),
+    /// HTTP basic auth, for example when the gateway is behind a reverse proxy.
+    Basic {
+        /// The user to authenticate as.
+        username: String,
+        /// The password, if one is required.
+        password: Option<String>,
+    },
+}
+
+/// A connection to an LCI gateway.
+/// Every "thing" fetched through a client uses the same address and credentials.
+#[derive(Clone, Debug)]
+pub struct Client {
+    address: String,
+    authentication: Authentication,
+    http: reqwest::Client,
+    tanks: Arc<TankConfigs>,
+}
+
+impl Client {
+    /// Create a new client for the gateway at the given address, ex: "http://192.168.1.4:8080".
+    pub fn new(address: impl Into<String>) -> Self {
+        Self::with_http(address, reqwest::Client::new())
+    }
+
+    /// Create a client that sends its requests through the given HTTP client, ex: one shared by many probes.
+    pub(crate) fn with_http(address: impl Into<String>, http: reqwest::Client) -> Self {
+        let address = address.into().trim_end_matches('/').to_string();
+        Self {
+            address,
+            authentication: Authentication::None,
+            http,
+            tanks: Arc::default(),
+        }
+    }
+
+    /// Create a client configured by the environment: `LCI_ADDRESS` for the gateway, defaulting to
+    /// `DEFAULT_ADDRESS`, then `LCI_TOKEN` for an API token, or else `LCI_USERNAME` and `LCI_PASSWORD`
+    /// for HTTP basic auth, and `LCI_TANKS` for a JSON file of `TankConfigs`.
+    pub fn from_env() -> Result<Self, TankConfigError> {
+        let mut client =
+            Self::new(std::env::var("LCI_ADDRESS").unwrap_or(DEFAULT_ADDRESS.to_string()));
+        if let Ok(token) = std::env::var("LCI_TOKEN") {
+            client = client.with_token(token);
+        } else if let Ok(username) = std::env::var("LCI_USERNAME") {
+            client = client.with_basic_auth(username, std::env::var("LCI_PASSWORD").ok());
+        }
+        if let Ok(path) = std::env::var("LCI_TANKS") {
+            client = client.with_tank_configs(TankConfigs::load(path)?);
+        }
+        Ok(client)
+    }
+
+    /// Sends every request to the gateway at the given address instead.
+    pub fn with_address(mut self, address: impl Into<String>) -> Self {
+        self.address = address.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the credentials to send with every request.
+    pub fn with_authentication(mut self, authentication: Authentication) -> Self {
+        self.authentication = authentication;
+        self
+    }
+
+    /// Sends the given API token as a bearer token with every request.
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        self.with_authentication(Authentication::Token(token.into()))
+    }
+
+    /// Sends the given user and password as HTTP basic auth with every request.
+    pub fn with_basic_auth(self, username: impl Into<String>, password: Option<String>) -> Self {
+        self.with_authentication(Authentication::Basic {
+            username: username.into(),
+            password,
+        })
+    }
+
+    /// Applies the tank settings to the tanks returned by `devices`.
+    pub fn with_tank_configs(mut self, tanks: TankConfigs) -> Self {
+        self.tanks = Arc::new(tanks);
+        self
+    }
+
+    /// Returns the tank settings applied by `devices`.
+    pub fn tank_configs(&self) -> &TankConfigs {
+        &self.tanks
+    }
+
+    /// Gives up on any request to the gateway that takes longer than the given duration.
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.http = http_with_timeout(timeout);
+        self
+    }
+
+    /// Returns the base address of the gateway.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Returns the credentials sent with every request.
+    pub fn authentication(&self) -> &Authentication {
+        &self.authentication
+    }
+
+    /// Returns the "things" availabe in the LCI Gateway.
+    pub async fn get_things(&self) -> Result<Vec<Thing>, ThingError> {
+        log::trace!("Fetching things from {}", self.address);
+        let response = self.get("/rest/things/").send().await.map_err(|err| {
+            log::error!("Failed to request {:?}", err);
+            ThingError::Getting(err)
+        })?;
+        let status = response.status();
+        if is_unauthorized(status) {
+            return Err(ThingError::Unauthorized);
+        }
+        if !status.is_success() {
+            log::error!("Fetching things returned {}", status);
+            return Err(ThingError::Status(status.as_u16()));
+        }
+        let body = response.text().await.map_err(|err| {
+            log::error!("Failed to pull text {:?}", err);
+            ThingError::Text(err)
+        })?;
+        log::trace!("Converting with serde.");
+        let mut things: Vec<Thing> =
+            serde_json::from_str(&body).map_err(ThingError::ConvertingJson)?;
+        for thing in things.iter_mut() {
+            thing.set_client(self.clone());
+        }
+        log::trace!("returning things");
+        Ok(things)
+    }
+
+    /// Builds a GET request for the given path, ex: "/rest/things/".
+    pub(crate) fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.authenticate(self.http.get(self.url(path)))
+    }
+
+    /// Builds a POST request for the given path.
+    pub(crate) fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.authenticate(self.http.post(self.url(path)))
+    }
+
+    /// Builds a PUT request for the given path.
+    pub(crate) fn put(&self, path: &str) -> reqwest::RequestBuilder {
+        self.authenticate(self.http.put(self.url(path)))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.address, path)
+    }
+
+    fn authenticate(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.authentication {
+            Authentication::None => request,
+            Authentication::Token(token) => request.bearer_auth(token),
+            Authentication::Basic { username, password } => {
+                request.basic_auth(username, password.as_ref())
+            }
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(DEFAULT_ADDRESS)
+    }
+}
+
+/// True when the gateway (or a proxy in front of it) rejected the credentials.
+pub(crate) fn is_unauthorized(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN
+}
+
+/// Builds an HTTP client that gives up on requests after the timeout.
+pub(crate) fn http_with_timeout(timeout: std::time::Duration) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .unwrap_or_else(|err| {
+            log::error!("Failed to build a client with a timeout {:?}", err);
+            reqwest::Client::new()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers every request with the status and body.
+    async fn gateway(status: u16, body: &'static str) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let read = socket.read(&mut buffer).await.unwrap();
+                assert!(read > 0);
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    #[tokio::test]
+    async fn reports_error_statuses() {
+        let things = gateway(200, "[]").await.get_things().await.unwrap();
+        assert!(things.is_empty());
+        assert!(matches!(
+            gateway(401, "").await.get_things().await,
+            Err(ThingError::Unauthorized)
+        ));
+        assert!(matches!(
+            gateway(500, "Internal Server Error")
+                .await
+                .get_things()
+                .await,
+            Err(ThingError::Status(500))
+        ));
+    }
+}
@@ -1,10 +1,10 @@
-use super::Thing;
+use super::{client, Thing};
 use derive_getters::Getters;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// A device's online state in the router. Offline meaning it can not be communicated with.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum OnlineState {
     /// Device is not responsive.
     Offline,
@@ -15,7 +15,8 @@ pub enum OnlineState {
 }
 
 /// A 0-100 percentage.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
 pub struct Percentage {
     value: u8,
 }
@@ -26,9 +27,19 @@ pub(crate) struct LinkState {
 }
 
 pub(crate) async fn get_field(thing: &Thing, field: &str) -> Result<String, GetFailure> {
-    let item_id = thing.UID().replace(':', "_").replace('-', "_");
-    let url = format!("http://192.168.1.4:8080/rest/items/{}_{}", item_id, field);
-    let res = reqwest::get(&url).await.map_err(GetFailure::Request)?;
+    let item = thing
+        .item_name(field)
+        .ok_or_else(|| GetFailure::MissingChannel(field.to_string()))?;
+    let path = format!("/rest/items/{}", item);
+    let res = thing
+        .client()
+        .get(&path)
+        .send()
+        .await
+        .map_err(GetFailure::Request)?;
+    if client::is_unauthorized(res.status()) {
+        return Err(GetFailure::Unauthorized);
+    }
     let txt = res.text().await.map_err(GetFailure::Text)?;
     let state: LinkState = serde_json::from_str(&txt).map_err(GetFailure::SerdeJsonConversion)?;
     Ok(state.state().to_string())
@@ -39,18 +50,22 @@ pub(crate) async fn set_field(
     field: &str,
     value: String,
 ) -> Result<reqwest::Response, SetError> {
-    let item_id = thing.UID().replace(':', "_").replace('-', "_");
-    let url = format!("http://192.168.1.4:8080/rest/items/{}_{}", item_id, field);
-    let client = reqwest::Client::new();
-    let response = client
-        .post(&url)
+    let item = thing
+        .item_name(field)
+        .ok_or_else(|| SetError::MissingChannel(field.to_string()))?;
+    let path = format!("/rest/items/{}", item);
+    let response = thing
+        .client()
+        .post(&path)
         .header("Accept", "application/json")
         .body(value)
         .send()
         .await
         .map_err(SetError::Send)?;
 
-    if response.status().is_success() {
+    if client::is_unauthorized(response.status()) {
+        Err(SetError::Unauthorized)
+    } else if response.status().is_success() {
         Ok(response)
     } else {
         Err(SetError::Status(response.status().as_u16(), response))
@@ -101,12 +116,37 @@ impl std::fmt::Display for OnlineState {
     }
 }
 
+impl TryFrom<u8> for Percentage {
+    type Error = PercentageConversionError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value > 100 {
+            return Err(PercentageConversionError::OutOfRange(value));
+        }
+        Ok(Self { value })
+    }
+}
+
+impl From<Percentage> for u8 {
+    fn from(percentage: Percentage) -> Self {
+        percentage.value
+    }
+}
+
 impl std::fmt::Display for Percentage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}%", self.value())
     }
 }
 
+/// Returned when a number is not a percentage.
+#[derive(Debug, Error)]
+pub enum PercentageConversionError {
+    /// The value is above 100.
+    #[error("The given value '{0}' is not between 0 and 100.")]
+    OutOfRange(u8),
+}
+
 /// When calling the LCI gateway can not be reached or returns bad data.
 #[derive(Debug, Error)]
 pub enum GetFailure {
@@ -119,6 +159,12 @@ pub enum GetFailure {
     /// The LCI gateway returned unexpected or invalid JSON.
     #[error("The JSON response could not be parsed. {0}")]
     SerdeJsonConversion(serde_json::Error),
+    /// The LCI gateway rejected the credentials, or credentials are required.
+    #[error("The LCI gateway rejected the request as unauthorized.")]
+    Unauthorized,
+    /// The device does not have the requested channel.
+    #[error("The device does not have a '{0}' channel.")]
+    MissingChannel(String),
 }
 
 /// Failed to get the online state of the device.
@@ -138,7 +184,33 @@ pub enum SetError {
     /// The request failed to send to the gateway.
     #[error("The request failed to send to the gateway. {0}")]
     Send(reqwest::Error),
+    /// The LCI gateway rejected the credentials, or credentials are required.
+    #[error("The LCI gateway rejected the request as unauthorized.")]
+    Unauthorized,
+    /// The device does not have the requested channel.
+    #[error("The device does not have a '{0}' channel.")]
+    MissingChannel(String),
     /// The response from the gateway did not indicate acceptance.
     #[error("The http response code from the gateway, {0}, did not indicate success.")]
     Status(u16, reqwest::Response),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentages_deserialize_from_0_to_100() {
+        assert_eq!(
+            serde_json::from_str::<Percentage>("100").unwrap(),
+            Percentage::new(100)
+        );
+        assert_eq!(serde_json::to_string(&Percentage::new(40)).unwrap(), "40");
+        assert!(serde_json::from_str::<Percentage>("101").is_err());
+        assert!(serde_json::from_str::<Percentage>("-1").is_err());
+        assert!(matches!(
+            Percentage::try_from(200),
+            Err(PercentageConversionError::OutOfRange(200))
+        ));
+    }
+}
@@ -0,0 +1,615 @@
+use super::{
+    common, Client, DeviceType, Dimmer, DimmerBrightnessError, Generator, GeneratorState,
+    GeneratorStateConversionError, HvacFanMode, HvacFanModeConversionError,
+    HvacHighTemperatureFailure, HvacInsideTemperatureFailure, HvacLowTemperatureFailure, HvacMode,
+    HvacModeConversionError, HvacOutsideTemperatureFailure, HvacStatus, HvacStatusConversionError,
+    SetBrightnessError, Switch, SwitchRelayCurrentError, SwitchState, SwitchStateConversionError,
+    Tank, TankLevelError, Thing, ThingError, HVAC,
+};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use thiserror::Error;
+
+/// Any of the supported devices.
+pub enum Device {
+    /// A tank.
+    Tank(Tank),
+    /// A dimmable light.
+    Dimmer(Dimmer),
+    /// An on/off relay.
+    Switch(Switch),
+    /// A generator.
+    Generator(Generator),
+    /// An A/C unit.
+    Hvac(HVAC),
+}
+
+/// A reading of everything a device reports, varying by the type of device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeviceState {
+    /// See `Tank::level`.
+    Tank {
+        /// The tank level.
+        level: common::Percentage,
+    },
+    /// See `Dimmer::brightness`.
+    Dimmer {
+        /// The brightness, 0% when off.
+        brightness: common::Percentage,
+    },
+    /// See `Switch::state`, `Switch::fault` and `Switch::relay_current`.
+    Switch {
+        /// If the relay is on.
+        state: SwitchState,
+        /// If the relay reports a fault.
+        fault: SwitchState,
+        /// The current used by the relay, as reported by the gateway.
+        relay_current: String,
+    },
+    /// See `Generator::state`.
+    Generator {
+        /// The generator's state.
+        state: GeneratorState,
+    },
+    /// See the getters on `HVAC`.
+    Hvac {
+        /// What the unit is doing.
+        status: HvacStatus,
+        /// What the unit is asked to do.
+        mode: HvacMode,
+        /// The fan setting.
+        fan: HvacFanMode,
+        /// The temperature inside the room.
+        inside_temperature: f32,
+        /// The "outside temperature".
+        outside_temperature: f32,
+        /// The temperature the unit starts cooling at.
+        high_temperature: f32,
+        /// The temperature the unit starts heating at.
+        low_temperature: f32,
+    },
+}
+
+/// A device's identity, online state and readings at one point in time.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    /// The label of the device.
+    label: String,
+    /// The unique ID of the device.
+    uid: String,
+    /// The type of device.
+    device_type: DeviceType,
+    /// The device's online state.
+    online: common::OnlineState,
+    /// The device's readings.
+    state: DeviceState,
+}
+
+/// A command any device wrapper can carry out, see `Device::execute`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", content = "value", rename_all = "snake_case")]
+pub enum DeviceCommand {
+    /// Turns a switch, dimmer or generator on.
+    On,
+    /// Turns a switch, dimmer or generator off.
+    Off,
+    /// Sets a dimmer's brightness between 0 and 100.
+    SetBrightness(u8),
+    /// Sets an HVAC's mode.
+    SetMode(HvacMode),
+    /// Sets an HVAC's fan mode.
+    SetFan(HvacFanMode),
+    /// Sets the temperature an HVAC starts cooling at.
+    SetHighTemperature(isize),
+    /// Sets the temperature an HVAC starts heating at.
+    SetLowTemperature(isize),
+}
+
+impl Device {
+    /// Wraps the thing in the matching device type. Returns None for the gateway and unsupported types.
+    pub fn from_thing(thing: Thing) -> Option<Self> {
+        match thing.get_type() {
+            Some(DeviceType::Tank) => Tank::new(thing).ok().map(Device::Tank),
+            Some(DeviceType::Dimmer) => Dimmer::new(thing).ok().map(Device::Dimmer),
+            Some(DeviceType::Switch) => Switch::new(thing).ok().map(Device::Switch),
+            Some(DeviceType::Generator) => Generator::new(thing).ok().map(Device::Generator),
+            Some(DeviceType::Hvac) => HVAC::new(thing).ok().map(Device::Hvac),
+            _ => None,
+        }
+    }
+
+    /// Returns the label of the device.
+    pub fn label(&self) -> String {
+        match self {
+            Device::Tank(tank) => tank.label(),
+            Device::Dimmer(dimmer) => dimmer.label(),
+            Device::Switch(switch) => switch.label(),
+            Device::Generator(generator) => generator.label(),
+            Device::Hvac(hvac) => hvac.label(),
+        }
+    }
+
+    /// Returns the unique ID of the device in the LCI gateway.
+    pub fn uid(&self) -> String {
+        match self {
+            Device::Tank(tank) => tank.uid(),
+            Device::Dimmer(dimmer) => dimmer.uid(),
+            Device::Switch(switch) => switch.uid(),
+            Device::Generator(generator) => generator.uid(),
+            Device::Hvac(hvac) => hvac.uid(),
+        }
+    }
+
+    /// Returns the type of the device.
+    pub fn device_type(&self) -> DeviceType {
+        match self {
+            Device::Tank(_) => DeviceType::Tank,
+            Device::Dimmer(_) => DeviceType::Dimmer,
+            Device::Switch(_) => DeviceType::Switch,
+            Device::Generator(_) => DeviceType::Generator,
+            Device::Hvac(_) => DeviceType::Hvac,
+        }
+    }
+
+    /// Returns the UID made safe for topics, file names and URLs, ex: "idsmyrv_switch_0000_01".
+    pub fn object_id(&self) -> String {
+        object_id(&self.uid())
+    }
+
+    /// Returns true when the label or UID matches, ignoring case for the label.
+    pub fn matches(&self, label_or_uid: &str) -> bool {
+        matches_name(&self.label(), &self.uid(), label_or_uid)
+    }
+
+    /// Returns the device's online state.
+    pub async fn online(&self) -> Result<common::OnlineState, common::OnlineStateConversionError> {
+        match self {
+            Device::Tank(tank) => tank.online().await,
+            Device::Dimmer(dimmer) => dimmer.online().await,
+            Device::Switch(switch) => switch.online().await,
+            Device::Generator(generator) => generator.online().await,
+            Device::Hvac(hvac) => hvac.online().await,
+        }
+    }
+
+    /// Reads everything the device reports.
+    pub async fn state(&self) -> Result<DeviceState, DeviceStateError> {
+        let state = match self {
+            Device::Tank(tank) => DeviceState::Tank {
+                level: tank.level().await?,
+            },
+            Device::Dimmer(dimmer) => DeviceState::Dimmer {
+                brightness: dimmer.brightness().await?,
+            },
+            Device::Switch(switch) => DeviceState::Switch {
+                state: switch.state().await?,
+                fault: switch.fault().await?,
+                relay_current: switch.relay_current().await?,
+            },
+            Device::Generator(generator) => DeviceState::Generator {
+                state: generator.state().await?,
+            },
+            Device::Hvac(hvac) => DeviceState::Hvac {
+                status: hvac.status().await?,
+                mode: hvac.mode().await?,
+                fan: hvac.fan().await?,
+                inside_temperature: hvac.inside_temperature().await?,
+                outside_temperature: hvac.outside_temperature().await?,
+                high_temperature: hvac.high_temperature().await?,
+                low_temperature: hvac.low_temperature().await?,
+            },
+        };
+        Ok(state)
+    }
+
+    /// Reads the device's identity, online state and readings.
+    pub async fn snapshot(&self) -> Result<DeviceSnapshot, DeviceStateError> {
+        Ok(DeviceSnapshot {
+            label: self.label(),
+            uid: self.uid(),
+            device_type: self.device_type(),
+            online: self.online().await?,
+            state: self.state().await?,
+        })
+    }
+
+    /// Carries out the command on the device.
+    pub async fn execute(&mut self, command: &DeviceCommand) -> Result<(), DeviceCommandError> {
+        match (self, command) {
+            (Device::Switch(switch), DeviceCommand::On) => switch.on().await?,
+            (Device::Switch(switch), DeviceCommand::Off) => switch.off().await?,
+            (Device::Dimmer(dimmer), DeviceCommand::On) => dimmer.on().await?,
+            (Device::Dimmer(dimmer), DeviceCommand::Off) => dimmer.off().await?,
+            (Device::Dimmer(dimmer), DeviceCommand::SetBrightness(brightness)) => {
+                dimmer.set_brightness(*brightness).await?
+            }
+            (Device::Generator(generator), DeviceCommand::On) => generator.on().await?,
+            (Device::Generator(generator), DeviceCommand::Off) => generator.off().await?,
+            (Device::Hvac(hvac), DeviceCommand::SetMode(mode)) => hvac.set_mode(mode).await?,
+            (Device::Hvac(hvac), DeviceCommand::SetFan(fan)) => hvac.set_fan(fan).await?,
+            (Device::Hvac(hvac), DeviceCommand::SetHighTemperature(temp)) => {
+                hvac.set_high_temperature(*temp).await?
+            }
+            (Device::Hvac(hvac), DeviceCommand::SetLowTemperature(temp)) => {
+                hvac.set_low_temperature(*temp).await?
+            }
+            (device, command) => {
+                return Err(DeviceCommandError::Unsupported(
+                    device.device_type(),
+                    command.clone(),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DeviceSnapshot {
+    /// Returns the UID made safe for topics, file names and URLs, see `Device::object_id`.
+    pub fn object_id(&self) -> String {
+        object_id(&self.uid)
+    }
+
+    /// Returns true when the label or UID matches, like `Device::matches`.
+    pub fn matches(&self, label_or_uid: &str) -> bool {
+        matches_name(&self.label, &self.uid, label_or_uid)
+    }
+
+    /// Returns an offline snapshot of a device that has never been read, with empty readings.
+    #[cfg(feature = "alerts")]
+    pub(crate) fn unread(device: &Device) -> Self {
+        let state = match device {
+            Device::Tank(_) => DeviceState::Tank {
+                level: common::Percentage::new(0),
+            },
+            Device::Dimmer(_) => DeviceState::Dimmer {
+                brightness: common::Percentage::new(0),
+            },
+            Device::Switch(_) => DeviceState::Switch {
+                state: SwitchState::Off,
+                fault: SwitchState::Off,
+                relay_current: String::new(),
+            },
+            Device::Generator(_) => DeviceState::Generator {
+                state: GeneratorState::Off,
+            },
+            Device::Hvac(_) => DeviceState::Hvac {
+                status: HvacStatus::Off,
+                mode: HvacMode::Off,
+                fan: HvacFanMode::Auto,
+                inside_temperature: 0.0,
+                outside_temperature: 0.0,
+                high_temperature: 0.0,
+                low_temperature: 0.0,
+            },
+        };
+        Self {
+            label: device.label(),
+            uid: device.uid(),
+            device_type: device.device_type(),
+            online: common::OnlineState::Offline,
+            state,
+        }
+    }
+
+    /// Returns the snapshot with the device offline, keeping its last readings.
+    pub(crate) fn offline(&self) -> Self {
+        Self {
+            online: common::OnlineState::Offline,
+            ..self.clone()
+        }
+    }
+}
+
+fn object_id(uid: &str) -> String {
+    uid.replace([':', '-', ' ', '/'], "_")
+}
+
+fn matches_name(label: &str, uid: &str, label_or_uid: &str) -> bool {
+    uid == label_or_uid
+        || object_id(uid) == label_or_uid
+        || label.eq_ignore_ascii_case(label_or_uid)
+}
+
+impl Client {
+    /// Returns every supported device in the LCI gateway, with the client's tank settings applied.
+    pub async fn devices(&self) -> Result<Vec<Device>, ThingError> {
+        let things = self.get_things().await?;
+        let mut devices: Vec<Device> = things.into_iter().filter_map(Device::from_thing).collect();
+        for device in &mut devices {
+            if let Device::Tank(tank) = device {
+                self.tank_configs().configure(tank);
+            }
+        }
+        Ok(devices)
+    }
+}
+
+impl DeviceState {
+    /// Returns the switch, dimmer or generator state as on or off. None for tanks and HVACs.
+    pub fn is_on(&self) -> Option<bool> {
+        match self {
+            DeviceState::Dimmer { brightness } => Some(brightness.value() > 0),
+            DeviceState::Switch { state, .. } => Some(state == &SwitchState::On),
+            DeviceState::Generator { state } => Some(state != &GeneratorState::Off),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let device_type = match self {
+            DeviceType::Gateway => "Gateway".to_string(),
+            DeviceType::Tank => "Tank".to_string(),
+            DeviceType::RgbLights => "RGB Lights".to_string(),
+            DeviceType::Hvac => "HVAC".to_string(),
+            DeviceType::Dimmer => "Dimmer".to_string(),
+            DeviceType::Generator => "Generator".to_string(),
+            DeviceType::Switch => "Switch".to_string(),
+        };
+        write!(f, "{}", device_type)
+    }
+}
+
+impl std::fmt::Display for DeviceState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceState::Tank { level } => write!(f, "{}", level),
+            DeviceState::Dimmer { brightness } => write!(f, "{}", brightness),
+            DeviceState::Switch {
+                state,
+                fault,
+                relay_current,
+            } => write!(
+                f,
+                "{} (fault: {}, relay current: {})",
+                state, fault, relay_current
+            ),
+            DeviceState::Generator { state } => write!(f, "{}", state),
+            DeviceState::Hvac {
+                status,
+                mode,
+                fan,
+                inside_temperature,
+                outside_temperature,
+                high_temperature,
+                low_temperature,
+            } => write!(
+                f,
+                "{} (mode: {}, fan: {}, inside: {}°, outside: {}°, heat at {}°, cool at {}°)",
+                status,
+                mode,
+                fan,
+                inside_temperature,
+                outside_temperature,
+                low_temperature,
+                high_temperature
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceCommand::On => write!(f, "On"),
+            DeviceCommand::Off => write!(f, "Off"),
+            DeviceCommand::SetBrightness(brightness) => write!(f, "Set brightness {}%", brightness),
+            DeviceCommand::SetMode(mode) => write!(f, "Set mode {}", mode),
+            DeviceCommand::SetFan(fan) => write!(f, "Set fan {}", fan),
+            DeviceCommand::SetHighTemperature(temp) => write!(f, "Set high temperature {}", temp),
+            DeviceCommand::SetLowTemperature(temp) => write!(f, "Set low temperature {}", temp),
+        }
+    }
+}
+
+/// Pairs each name with the first device whose label or UID matches it and that no earlier name took.
+/// Works on shared and mutable references alike.
+pub(crate) fn match_devices<D: Borrow<Device>>(
+    devices: impl IntoIterator<Item = D>,
+    names: &[&str],
+) -> Vec<Option<D>> {
+    let mut matched: Vec<Option<D>> = names.iter().map(|_| None).collect();
+    for device in devices {
+        let found = names
+            .iter()
+            .enumerate()
+            .find(|(index, name)| matched[*index].is_none() && device.borrow().matches(name));
+        if let Some((index, _)) = found {
+            matched[index] = Some(device);
+        }
+    }
+    matched
+}
+
+/// Returns every supported device in the LCI Gateway at the default address.
+pub async fn get_devices() -> Result<Vec<Device>, ThingError> {
+    Client::default().devices().await
+}
+
+/// A device's readings could not be fetched.
+#[derive(Debug, Error)]
+pub enum DeviceStateError {
+    /// See `OnlineStateConversionError`.
+    #[error("The online state could not be read. {0}")]
+    Online(common::OnlineStateConversionError),
+    /// See `TankLevelError`.
+    #[error("The tank level could not be read. {0}")]
+    TankLevel(TankLevelError),
+    /// See `DimmerBrightnessError`.
+    #[error("The dimmer brightness could not be read. {0}")]
+    DimmerBrightness(DimmerBrightnessError),
+    /// See `SwitchStateConversionError`.
+    #[error("The switch state could not be read. {0}")]
+    SwitchState(SwitchStateConversionError),
+    /// See `SwitchRelayCurrentError`.
+    #[error("The switch relay current could not be read. {0}")]
+    SwitchRelayCurrent(SwitchRelayCurrentError),
+    /// See `GeneratorStateConversionError`.
+    #[error("The generator state could not be read. {0}")]
+    GeneratorState(GeneratorStateConversionError),
+    /// See `HvacStatusConversionError`.
+    #[error("The HVAC status could not be read. {0}")]
+    HvacStatus(HvacStatusConversionError),
+    /// See `HvacModeConversionError`.
+    #[error("The HVAC mode could not be read. {0}")]
+    HvacMode(HvacModeConversionError),
+    /// See `HvacFanModeConversionError`.
+    #[error("The HVAC fan mode could not be read. {0}")]
+    HvacFanMode(HvacFanModeConversionError),
+    /// See `HvacInsideTemperatureFailure`.
+    #[error("The HVAC inside temperature could not be read. {0}")]
+    HvacInsideTemperature(HvacInsideTemperatureFailure),
+    /// See `HvacOutsideTemperatureFailure`.
+    #[error("The HVAC outside temperature could not be read. {0}")]
+    HvacOutsideTemperature(HvacOutsideTemperatureFailure),
+    /// See `HvacHighTemperatureFailure`.
+    #[error("The HVAC high temperature could not be read. {0}")]
+    HvacHighTemperature(HvacHighTemperatureFailure),
+    /// See `HvacLowTemperatureFailure`.
+    #[error("The HVAC low temperature could not be read. {0}")]
+    HvacLowTemperature(HvacLowTemperatureFailure),
+}
+
+/// A command could not be carried out on a device.
+#[derive(Debug, Error)]
+pub enum DeviceCommandError {
+    /// The device does not support the command, ex: setting the brightness of a switch.
+    #[error("A {0} does not support the command '{1}'.")]
+    Unsupported(DeviceType, DeviceCommand),
+    /// The LCI gateway request failed.
+    #[error("The set command failed to process. {0}")]
+    SetError(common::SetError),
+    /// The brightness could not be set.
+    #[error("The brightness could not be set. {0}")]
+    SetBrightness(SetBrightnessError),
+}
+
+impl DeviceStateError {
+    /// Returns the failed gateway request behind the error, or None when a value could not be parsed.
+    pub fn get_failure(&self) -> Option<&common::GetFailure> {
+        match self {
+            Self::Online(common::OnlineStateConversionError::GetFailure(failure))
+            | Self::TankLevel(TankLevelError::GetFailure(failure))
+            | Self::DimmerBrightness(DimmerBrightnessError::GetFailure(failure))
+            | Self::SwitchState(SwitchStateConversionError::GetFailure(failure))
+            | Self::SwitchRelayCurrent(SwitchRelayCurrentError::GetFailure(failure))
+            | Self::GeneratorState(GeneratorStateConversionError::GetFailure(failure))
+            | Self::HvacStatus(HvacStatusConversionError::GetFailure(failure))
+            | Self::HvacMode(HvacModeConversionError::GetFailure(failure))
+            | Self::HvacFanMode(HvacFanModeConversionError::GetFailure(failure))
+            | Self::HvacInsideTemperature(HvacInsideTemperatureFailure::GetFailure(failure))
+            | Self::HvacOutsideTemperature(HvacOutsideTemperatureFailure::GetFailure(failure))
+            | Self::HvacHighTemperature(HvacHighTemperatureFailure::GetFailure(failure))
+            | Self::HvacLowTemperature(HvacLowTemperatureFailure::GetFailure(failure)) => {
+                Some(failure)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl DeviceCommandError {
+    /// Returns the failed gateway request behind the error, or None when the command was rejected before sending.
+    pub fn set_error(&self) -> Option<&common::SetError> {
+        match self {
+            Self::SetError(error) | Self::SetBrightness(SetBrightnessError::SetError(error)) => {
+                Some(error)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<common::OnlineStateConversionError> for DeviceStateError {
+    fn from(error: common::OnlineStateConversionError) -> Self {
+        Self::Online(error)
+    }
+}
+
+impl From<TankLevelError> for DeviceStateError {
+    fn from(error: TankLevelError) -> Self {
+        Self::TankLevel(error)
+    }
+}
+
+impl From<DimmerBrightnessError> for DeviceStateError {
+    fn from(error: DimmerBrightnessError) -> Self {
+        Self::DimmerBrightness(error)
+    }
+}
+
+impl From<SwitchStateConversionError> for DeviceStateError {
+    fn from(error: SwitchStateConversionError) -> Self {
+        Self::SwitchState(error)
+    }
+}
+
+impl From<SwitchRelayCurrentError> for DeviceStateError {
+    fn from(error: SwitchRelayCurrentError) -> Self {
+        Self::SwitchRelayCurrent(error)
+    }
+}
+
+impl From<GeneratorStateConversionError> for DeviceStateError {
+    fn from(error: GeneratorStateConversionError) -> Self {
+        Self::GeneratorState(error)
+    }
+}
+
+impl From<HvacStatusConversionError> for DeviceStateError {
+    fn from(error: HvacStatusConversionError) -> Self {
+        Self::HvacStatus(error)
+    }
+}
+
+impl From<HvacModeConversionError> for DeviceStateError {
+    fn from(error: HvacModeConversionError) -> Self {
+        Self::HvacMode(error)
+    }
+}
+
+impl From<HvacFanModeConversionError> for DeviceStateError {
+    fn from(error: HvacFanModeConversionError) -> Self {
+        Self::HvacFanMode(error)
+    }
+}
+
+impl From<HvacInsideTemperatureFailure> for DeviceStateError {
+    fn from(error: HvacInsideTemperatureFailure) -> Self {
+        Self::HvacInsideTemperature(error)
+    }
+}
+
+impl From<HvacOutsideTemperatureFailure> for DeviceStateError {
+    fn from(error: HvacOutsideTemperatureFailure) -> Self {
+        Self::HvacOutsideTemperature(error)
+    }
+}
+
+impl From<HvacHighTemperatureFailure> for DeviceStateError {
+    fn from(error: HvacHighTemperatureFailure) -> Self {
+        Self::HvacHighTemperature(error)
+    }
+}
+
+impl From<HvacLowTemperatureFailure> for DeviceStateError {
+    fn from(error: HvacLowTemperatureFailure) -> Self {
+        Self::HvacLowTemperature(error)
+    }
+}
+
+impl From<common::SetError> for DeviceCommandError {
+    fn from(error: common::SetError) -> Self {
+        Self::SetError(error)
+    }
+}
+
+impl From<SetBrightnessError> for DeviceCommandError {
+    fn from(error: SetBrightnessError) -> Self {
+        Self::SetBrightness(error)
+    }
+}
@@ -22,6 +22,11 @@ impl Dimmer {
         self.thing.label().clone()
     }
 
+    /// Returns the unique ID of the device in the LCI gateway.
+    pub fn uid(&self) -> String {
+        self.thing.UID().clone()
+    }
+
     /// Returns the device's online state.
     pub async fn online(&self) -> Result<common::OnlineState, common::OnlineStateConversionError> {
         common::get_online_state(&self.thing).await
@@ -35,7 +40,7 @@ impl Dimmer {
         let val = string
             .parse::<u8>()
             .map_err(|e| DimmerBrightnessError::Parse(string, e))?;
-        Ok(common::Percentage::new(val))
+        common::Percentage::try_from(val).map_err(DimmerBrightnessError::OutOfRange)
     }
 
     /// Turns on the dimmer to 100%
@@ -77,6 +82,9 @@ pub enum DimmerBrightnessError {
     /// The response from the LCI gateway could not be parsed.
     #[error("The response from the LCI gateway could not be parsed, {1}")]
     Parse(String, std::num::ParseIntError),
+    /// The LCI gateway reported a brightness above 100.
+    #[error("{0}")]
+    OutOfRange(common::PercentageConversionError),
 }
 
 /// The brightness of the dimmer could not be set.
@@ -95,3 +103,55 @@ impl From<common::SetError> for SetBrightnessError {
         Self::SetError(error)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Client;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers every item read with the state.
+    async fn gateway(state: &'static str) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let read = socket.read(&mut buffer).await.unwrap();
+                assert!(read > 0);
+                let body = format!("{{\"state\": \"{}\"}}", state);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    async fn dimmer(state: &'static str) -> Dimmer {
+        let mut thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": "Porch Light",
+            "UID": "idsmyrv:dimmer:0002",
+            "configuration": {"deviceType": 20},
+        }))
+        .unwrap();
+        thing.set_client(gateway(state).await);
+        Dimmer::new(thing).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_brightness_up_to_100() {
+        assert_eq!(dimmer("0").await.brightness().await.unwrap().value(), 0);
+        assert!(matches!(
+            dimmer("101").await.brightness().await,
+            Err(DimmerBrightnessError::OutOfRange(
+                common::PercentageConversionError::OutOfRange(101)
+            ))
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+use super::client::http_with_timeout;
+use super::{Authentication, Client, DeviceType, DEFAULT_ADDRESS};
+use derive_getters::Getters;
+use futures::StreamExt;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The DNS-SD service type the gateway's openHAB server announces itself as.
+pub const SERVICE_TYPE: &str = "_openhab-server._tcp.local.";
+
+/// The port the gateway's REST API listens on.
+pub const DEFAULT_PORT: u16 = 8080;
+
+/// How a gateway candidate was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiscoverySource {
+    /// The gateway announced itself over mDNS / DNS-SD.
+    Mdns,
+    /// The gateway answered a probe of its REST port.
+    Probe,
+}
+
+/// A possible LCI gateway on the local network.
+#[derive(Clone, Debug, Getters, PartialEq, Eq)]
+pub struct Candidate {
+    /// The base address of the gateway, ex: "http://192.168.1.4:8080".
+    address: String,
+    /// The host name announced over mDNS, if any.
+    hostname: Option<String>,
+    /// How the gateway was found.
+    source: DiscoverySource,
+    /// The credentials the gateway was checked with.
+    authentication: Authentication,
+}
+
+/// What to look for and how long to wait.
+#[derive(Clone, Debug)]
+pub struct DiscoveryOptions {
+    /// How long to listen for mDNS announcements. `None` disables mDNS.
+    pub mdns_timeout: Option<Duration>,
+    /// Subnets to probe as (network, prefix length), ex: (192.168.1.0, 24).
+    pub subnets: Vec<(Ipv4Addr, u8)>,
+    /// The REST port to probe.
+    pub port: u16,
+    /// How long to wait on each probed host.
+    pub probe_timeout: Duration,
+    /// How many hosts to probe at once.
+    pub concurrency: usize,
+    /// The credentials to check candidates with, for gateways that require them.
+    pub authentication: Authentication,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            mdns_timeout: Some(Duration::from_secs(3)),
+            subnets: Vec::new(),
+            port: DEFAULT_PORT,
+            probe_timeout: Duration::from_millis(750),
+            concurrency: 64,
+            authentication: Authentication::None,
+        }
+    }
+}
+
+impl Candidate {
+    /// Create a client for this candidate, with the credentials it was checked with.
+    pub fn client(&self) -> Client {
+        self.client_with(reqwest::Client::new())
+    }
+
+    fn client_with(&self, http: reqwest::Client) -> Client {
+        Client::with_http(self.address.clone(), http)
+            .with_authentication(self.authentication.clone())
+    }
+}
+
+/// Finds LCI gateways on the local network.
+/// mDNS candidates are returned first, then probed ones. Every candidate passed the fingerprint check.
+/// When nothing is found the stock address is tried as a last resort.
+/// An mDNS failure is logged and the subnets are still probed.
+pub async fn discover(options: &DiscoveryOptions) -> Result<Vec<Candidate>, DiscoveryError> {
+    let http = http_with_timeout(options.probe_timeout);
+    let mut candidates = Vec::new();
+
+    if let Some(timeout) = options.mdns_timeout {
+        match discover_mdns(timeout).await {
+            Ok(announced) => {
+                for mut candidate in announced {
+                    candidate.authentication = options.authentication.clone();
+                    if is_gateway(&candidate.client_with(http.clone())).await {
+                        push_unique(&mut candidates, candidate);
+                    }
+                }
+            }
+            Err(err) => log::warn!("Skipping mDNS discovery: {}", err),
+        }
+    }
+
+    for (network, prefix) in &options.subnets {
+        let probed = probe(
+            *network,
+            *prefix,
+            options.port,
+            &http,
+            options.concurrency,
+            &options.authentication,
+        )
+        .await?;
+        for candidate in probed {
+            push_unique(&mut candidates, candidate);
+        }
+    }
+
+    if candidates.is_empty() {
+        let candidate = Candidate {
+            address: DEFAULT_ADDRESS.to_string(),
+            hostname: None,
+            source: DiscoverySource::Probe,
+            authentication: options.authentication.clone(),
+        };
+        if is_gateway(&candidate.client_with(http)).await {
+            candidates.push(candidate);
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Listens for gateways announcing themselves over mDNS / DNS-SD for the given duration.
+/// Candidates are not fingerprinted, see `is_gateway`, and carry no credentials.
+pub async fn discover_mdns(timeout: Duration) -> Result<Vec<Candidate>, DiscoveryError> {
+    let daemon = mdns_sd::ServiceDaemon::new().map_err(DiscoveryError::Mdns)?;
+    let receiver = daemon.browse(SERVICE_TYPE).map_err(DiscoveryError::Mdns)?;
+    let mut candidates = Vec::new();
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    while let Ok(Ok(event)) = tokio::time::timeout_at(deadline, receiver.recv_async()).await {
+        if let mdns_sd::ServiceEvent::ServiceResolved(info) = event {
+            log::trace!("Resolved {} over mDNS", info.get_fullname());
+            for ip in info.get_addresses_v4() {
+                let candidate = Candidate {
+                    address: format!("http://{}:{}", ip, info.get_port()),
+                    hostname: Some(info.get_hostname().trim_end_matches('.').to_string()),
+                    source: DiscoverySource::Mdns,
+                    authentication: Authentication::None,
+                };
+                push_unique(&mut candidates, candidate);
+            }
+        }
+    }
+
+    if let Err(err) = daemon.shutdown() {
+        log::warn!("Failed to shut down the mDNS daemon {:?}", err);
+    }
+    Ok(candidates)
+}
+
+/// Probes every host in the subnet on the given port with the credentials and keeps the ones that look like a gateway.
+pub async fn probe_subnet(
+    network: Ipv4Addr,
+    prefix: u8,
+    port: u16,
+    timeout: Duration,
+    concurrency: usize,
+    authentication: &Authentication,
+) -> Result<Vec<Candidate>, DiscoveryError> {
+    let http = http_with_timeout(timeout);
+    probe(network, prefix, port, &http, concurrency, authentication).await
+}
+
+/// Probes the subnet with one HTTP client shared by every host.
+async fn probe(
+    network: Ipv4Addr,
+    prefix: u8,
+    port: u16,
+    http: &reqwest::Client,
+    concurrency: usize,
+    authentication: &Authentication,
+) -> Result<Vec<Candidate>, DiscoveryError> {
+    let hosts = subnet_hosts(network, prefix)?;
+    log::trace!("Probing {} hosts in {}/{}", hosts.len(), network, prefix);
+
+    let candidates = futures::stream::iter(hosts)
+        .map(|host| async move {
+            let candidate = Candidate {
+                address: format!("http://{}:{}", host, port),
+                hostname: None,
+                source: DiscoverySource::Probe,
+                authentication: authentication.clone(),
+            };
+            is_gateway(&candidate.client_with(http.clone()))
+                .await
+                .then_some(candidate)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .filter_map(|candidate| async move { candidate })
+        .collect::<Vec<_>>()
+        .await;
+    Ok(candidates)
+}
+
+/// Fingerprints the address behind the client: the things endpoint has to answer with LCI devices.
+/// A gateway that requires credentials only passes if the client has them.
+pub async fn is_gateway(client: &Client) -> bool {
+    match client.get_things().await {
+        Ok(things) => things
+            .iter()
+            .any(|thing| !matches!(thing.get_type(), None | Some(DeviceType::Gateway))),
+        Err(err) => {
+            log::trace!("{} is not a gateway: {}", client.address(), err);
+            false
+        }
+    }
+}
+
+fn subnet_hosts(network: Ipv4Addr, prefix: u8) -> Result<Vec<Ipv4Addr>, DiscoveryError> {
+    if !(16..=30).contains(&prefix) {
+        return Err(DiscoveryError::InvalidSubnet(network, prefix));
+    }
+    let mask = u32::MAX << (32 - prefix);
+    let first = u32::from(network) & mask;
+    let last = first | !mask;
+    Ok((first + 1..last).map(Ipv4Addr::from).collect())
+}
+
+fn push_unique(candidates: &mut Vec<Candidate>, candidate: Candidate) {
+    if !candidates.iter().any(|c| c.address == candidate.address) {
+        candidates.push(candidate);
+    }
+}
+
+/// Returned when discovery could not run.
+#[derive(Debug, Error)]
+pub enum DiscoveryError {
+    /// The mDNS daemon could not be started or queried.
+    #[error("The mDNS browse failed. {0}")]
+    Mdns(mdns_sd::Error),
+    /// Only /16 through /30 subnets can be probed.
+    #[error("The subnet {0}/{1} can not be probed, the prefix must be between 16 and 30.")]
+    InvalidSubnet(Ipv4Addr, u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_the_hosts_of_a_subnet() {
+        let hosts = subnet_hosts(Ipv4Addr::new(192, 168, 1, 77), 24).unwrap();
+        assert_eq!(hosts.len(), 254);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 1, 254));
+    }
+
+    #[test]
+    fn probes_from_16_to_30_bit_prefixes() {
+        let hosts = subnet_hosts(Ipv4Addr::new(10, 1, 0, 0), 16).unwrap();
+        assert_eq!(hosts.len(), 65534);
+        assert_eq!(hosts[0], Ipv4Addr::new(10, 1, 0, 1));
+        assert_eq!(hosts[65533], Ipv4Addr::new(10, 1, 255, 254));
+
+        assert_eq!(
+            subnet_hosts(Ipv4Addr::new(10, 1, 2, 6), 30).unwrap(),
+            [Ipv4Addr::new(10, 1, 2, 5), Ipv4Addr::new(10, 1, 2, 6)]
+        );
+
+        for prefix in [0, 15, 31, 32, 33] {
+            assert!(matches!(
+                subnet_hosts(Ipv4Addr::new(10, 1, 2, 0), prefix),
+                Err(DiscoveryError::InvalidSubnet(_, invalid)) if invalid == prefix
+            ));
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use super::{common, Tank, TankLevelError};
+use derive_getters::Getters;
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+const SECONDS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// A tank's smoothed level, usage rate and how long until it is empty or full.
+#[derive(Clone, Debug, Getters, PartialEq)]
+pub struct TankForecast {
+    /// The smoothed 0-100 level at the latest reading.
+    level: f64,
+    /// Percentage points per day. Negative while emptying, ex: fresh water; positive while filling, ex: grey or black.
+    rate_per_day: f64,
+    /// How long from the latest reading until the tank is empty. None unless it is emptying.
+    time_to_empty: Option<Duration>,
+    /// How long from the latest reading until the tank is full. None unless it is filling.
+    time_to_full: Option<Duration>,
+}
+
+/// Builds a `TankForecast` from a series of readings of one tank.
+///
+/// Coarse sensors step, ex: 66% to 33%, so the rate is a least-squares fit over every reading in the window
+/// rather than the difference between the last two. A jump against the trend, such as a dump or a fill,
+/// starts the series over, as does any jump from a lone reading or a flat run. Set the reset threshold above
+/// a coarse sensor's step, ex: 40 for a sensor reading 0, 33, 66 and 100%.
+#[derive(Clone, Debug)]
+pub struct TankEstimator {
+    readings: VecDeque<(SystemTime, f64)>,
+    window: Duration,
+    reset_threshold: u8,
+}
+
+impl Default for TankEstimator {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(3 * 24 * 60 * 60))
+    }
+}
+
+impl TankEstimator {
+    /// Create an estimator that fits readings from the given window, ex: the last 3 days.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            readings: VecDeque::new(),
+            window,
+            reset_threshold: 10,
+        }
+    }
+
+    /// Sets how many percentage points against the trend, or either way without one, count as a dump or fill,
+    /// 10 by default.
+    pub fn with_reset_threshold(mut self, reset_threshold: u8) -> Self {
+        self.reset_threshold = reset_threshold;
+        self
+    }
+
+    /// Adds a reading. Readings older than the latest one are ignored.
+    pub fn push(&mut self, time: SystemTime, level: common::Percentage) {
+        let level = level.value() as f64;
+        if let Some((last_time, last_level)) = self.readings.back() {
+            if time < *last_time {
+                return;
+            }
+            let change = level - last_level;
+            let threshold = self.reset_threshold as f64;
+            let reset = match self.rate() {
+                Some(rate) if rate < 0.0 => change > threshold,
+                Some(rate) if rate > 0.0 => change < -threshold,
+                _ => change.abs() > threshold,
+            };
+            if reset {
+                self.readings.clear();
+            }
+        }
+        self.readings.push_back((time, level));
+        while let Some((first, _)) = self.readings.front() {
+            match time.duration_since(*first) {
+                Ok(age) if age > self.window => {
+                    self.readings.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Reads the tank's level now and adds it.
+    pub async fn read(&mut self, tank: &Tank) -> Result<(), TankLevelError> {
+        let level = tank.level().await?;
+        self.push(SystemTime::now(), level);
+        Ok(())
+    }
+
+    /// Drops every reading, ex: after the tank was emptied by hand.
+    pub fn clear(&mut self) {
+        self.readings.clear();
+    }
+
+    /// Returns the forecast, or None until there are readings at two different times.
+    pub fn forecast(&self) -> Option<TankForecast> {
+        let (last_time, _) = self.readings.back()?;
+        let (intercept, slope) = self.fit()?;
+        let latest = seconds_since(self.readings.front()?.0, *last_time);
+        let level = (intercept + slope * latest).clamp(0.0, 100.0);
+        let rate_per_day = slope * SECONDS_PER_DAY;
+        let time_to = |points: f64| Duration::try_from_secs_f64(points / slope.abs()).ok();
+        Some(TankForecast {
+            level,
+            rate_per_day,
+            time_to_empty: if slope < 0.0 { time_to(level) } else { None },
+            time_to_full: if slope > 0.0 {
+                time_to(100.0 - level)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn rate(&self) -> Option<f64> {
+        self.fit().map(|(_, slope)| slope)
+    }
+
+    /// Returns the intercept and slope, in points per second, of the least-squares line through the readings.
+    fn fit(&self) -> Option<(f64, f64)> {
+        let start = self.readings.front()?.0;
+        let count = self.readings.len() as f64;
+        let points: Vec<(f64, f64)> = self
+            .readings
+            .iter()
+            .map(|(time, level)| (seconds_since(start, *time), *level))
+            .collect();
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+        let spread: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if spread == 0.0 {
+            return None;
+        }
+        let slope = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f64>()
+            / spread;
+        Some((mean_y - slope * mean_x, slope))
+    }
+}
+
+fn seconds_since(start: SystemTime, time: SystemTime) -> f64 {
+    time.duration_since(start).unwrap_or_default().as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    const HOUR: u64 = 60 * 60;
+
+    fn estimator(readings: &[(u64, u8)]) -> TankEstimator {
+        push(TankEstimator::default(), readings)
+    }
+
+    fn push(mut estimator: TankEstimator, readings: &[(u64, u8)]) -> TankEstimator {
+        for (hours, level) in readings {
+            estimator.push(at(*hours), common::Percentage::try_from(*level).unwrap());
+        }
+        estimator
+    }
+
+    fn at(hours: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000 + hours * HOUR)
+    }
+
+    #[test]
+    fn needs_two_times() {
+        assert_eq!(estimator(&[]).forecast(), None);
+        assert_eq!(estimator(&[(0, 80)]).forecast(), None);
+    }
+
+    #[test]
+    fn fits_coarse_steps() {
+        // Steps of a 3-level sensor that average 1 point an hour.
+        let readings = [(0, 100), (1, 100), (33, 66), (34, 66), (66, 33), (67, 33)];
+        let coarse = TankEstimator::default().with_reset_threshold(40);
+        let forecast = push(coarse, &readings).forecast().unwrap();
+        assert!((forecast.rate_per_day + 24.0).abs() < 1.5, "{:?}", forecast);
+        assert!(forecast.time_to_full.is_none());
+        let empty = forecast.time_to_empty.unwrap().as_secs_f64() / HOUR as f64;
+        assert!((empty - forecast.level / 1.0).abs() < 5.0, "{:?}", forecast);
+    }
+
+    #[test]
+    fn times_to_empty_and_full() {
+        let emptying = estimator(&[(0, 60), (10, 50)]).forecast().unwrap();
+        assert_eq!(emptying.level, 50.0);
+        assert_eq!(emptying.rate_per_day, -24.0);
+        assert_eq!(emptying.time_to_empty, Some(Duration::from_secs(50 * HOUR)));
+        assert_eq!(emptying.time_to_full, None);
+
+        let filling = estimator(&[(0, 20), (10, 30)]).forecast().unwrap();
+        assert_eq!(filling.time_to_full, Some(Duration::from_secs(70 * HOUR)));
+        assert_eq!(filling.time_to_empty, None);
+
+        let steady = estimator(&[(0, 40), (10, 40)]).forecast().unwrap();
+        assert_eq!((steady.time_to_empty, steady.time_to_full), (None, None));
+    }
+
+    #[test]
+    fn resets_on_a_jump_against_the_trend() {
+        // A grey tank filling, then dumped.
+        let forecast = estimator(&[(0, 20), (10, 30), (20, 40), (21, 0), (31, 5)])
+            .forecast()
+            .unwrap();
+        assert_eq!(forecast.level, 5.0);
+        assert_eq!(forecast.rate_per_day, 12.0);
+        // A jump with the trend is kept.
+        let forecast = estimator(&[(0, 20), (10, 30), (11, 45)])
+            .forecast()
+            .unwrap();
+        assert!(forecast.rate_per_day > 24.0);
+    }
+
+    #[test]
+    fn resets_on_a_jump_from_a_lone_reading() {
+        // Dumped right after the first reading.
+        let forecast = estimator(&[(0, 80), (1, 0), (11, 10)]).forecast().unwrap();
+        assert_eq!(forecast.level, 10.0);
+        assert_eq!(forecast.rate_per_day, 24.0);
+        // A small change still starts the trend.
+        let forecast = estimator(&[(0, 80), (10, 75)]).forecast().unwrap();
+        assert_eq!(forecast.rate_per_day, -12.0);
+    }
+
+    #[test]
+    fn resets_on_a_jump_after_a_flat_run() {
+        // A grey tank holding, then dumped.
+        let forecast = estimator(&[(0, 60), (10, 60), (20, 60), (21, 0), (31, 5)])
+            .forecast()
+            .unwrap();
+        assert_eq!(forecast.level, 5.0);
+        assert_eq!(forecast.rate_per_day, 12.0);
+        // A small step off a flat run starts the trend.
+        let forecast = estimator(&[(0, 60), (10, 60), (20, 55)])
+            .forecast()
+            .unwrap();
+        assert!(forecast.rate_per_day < 0.0);
+    }
+
+    #[test]
+    fn drops_readings_outside_the_window() {
+        let forecast = estimator(&[(0, 100), (24, 90), (72, 70), (96, 60)])
+            .forecast()
+            .unwrap();
+        assert_eq!(forecast.rate_per_day, -10.0);
+        assert_eq!(
+            estimator(&[(0, 100), (24, 90), (72, 70), (96, 60)])
+                .readings
+                .len(),
+            3
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use super::{common, DeviceType, Thing};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// A connected generator.
@@ -7,7 +8,7 @@ pub struct Generator {
 }
 
 /// The generator's detected discrete state.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum GeneratorState {
     /// The generator is not.
     Off,
@@ -49,6 +50,11 @@ impl Generator {
         self.thing.label().clone()
     }
 
+    /// Returns the unique ID of the device in the LCI gateway.
+    pub fn uid(&self) -> String {
+        self.thing.UID().clone()
+    }
+
     /// Returns the device's online state.
     pub async fn online(&self) -> Result<common::OnlineState, common::OnlineStateConversionError> {
         common::get_online_state(&self.thing).await
@@ -0,0 +1,412 @@
+use super::device::match_devices;
+use super::{Device, DeviceCommand, DeviceCommandError, DeviceState, DeviceStateError};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A named set of devices, ex: a room or "all exterior lights".
+#[derive(Clone, Debug, Getters, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    /// The group's name.
+    name: String,
+    /// The labels or UIDs of the devices in the group.
+    members: Vec<String>,
+}
+
+/// How one member of a group fared.
+#[derive(Debug, Getters)]
+pub struct GroupResult<T> {
+    /// The member's label or UID, as written in the group.
+    device: String,
+    /// The member's value, or why there is none.
+    result: Result<T, GroupError>,
+}
+
+/// The state of every member of a group.
+#[derive(Debug, Getters)]
+pub struct GroupState {
+    /// Each member's state.
+    members: Vec<GroupResult<DeviceState>>,
+}
+
+impl Group {
+    /// Create a group of the devices with the labels or UIDs.
+    pub fn new(name: impl Into<String>, members: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            members,
+        }
+    }
+
+    /// Create a group of the devices whose label contains the room's name, ignoring case,
+    /// ex: "Kitchen" holds "Kitchen Lights" and "Kitchen Fan".
+    pub fn room(name: impl Into<String>, devices: &[Device]) -> Self {
+        let name = name.into();
+        let room = name.to_lowercase();
+        let members = devices
+            .iter()
+            .filter(|device| device.label().to_lowercase().contains(&room))
+            .map(|device| device.uid())
+            .collect();
+        Self { name, members }
+    }
+
+    /// Adds a device by label or UID.
+    pub fn with_member(mut self, member: impl Into<String>) -> Self {
+        self.members.push(member.into());
+        self
+    }
+
+    /// Sends the command to every member at once.
+    pub async fn execute(
+        &self,
+        devices: &mut [Device],
+        command: &DeviceCommand,
+    ) -> Vec<GroupResult<()>> {
+        self.execute_where(devices, command, |_| true).await
+    }
+
+    /// Turns every switch and dimmer in the group on. Other members are skipped.
+    pub async fn on(&self, devices: &mut [Device]) -> Vec<GroupResult<()>> {
+        self.execute_where(devices, &DeviceCommand::On, |device| {
+            matches!(device, Device::Switch(_) | Device::Dimmer(_))
+        })
+        .await
+    }
+
+    /// Turns every switch and dimmer in the group off. Other members are skipped.
+    pub async fn off(&self, devices: &mut [Device]) -> Vec<GroupResult<()>> {
+        self.execute_where(devices, &DeviceCommand::Off, |device| {
+            matches!(device, Device::Switch(_) | Device::Dimmer(_))
+        })
+        .await
+    }
+
+    /// Sets the brightness of every dimmer in the group. Other members are skipped.
+    pub async fn set_brightness(
+        &self,
+        devices: &mut [Device],
+        brightness: u8,
+    ) -> Vec<GroupResult<()>> {
+        self.execute_where(
+            devices,
+            &DeviceCommand::SetBrightness(brightness),
+            |device| matches!(device, Device::Dimmer(_)),
+        )
+        .await
+    }
+
+    /// Reads every member's state at once.
+    pub async fn state(&self, devices: &[Device]) -> GroupState {
+        let names: Vec<&str> = self.members.iter().map(String::as_str).collect();
+        let targets = match_devices(devices, &names);
+        let reads = self
+            .members
+            .iter()
+            .zip(targets)
+            .map(|(member, device)| async move {
+                let result = match device {
+                    Some(device) => device.state().await.map_err(GroupError::State),
+                    None => Err(GroupError::NotFound(member.clone())),
+                };
+                GroupResult {
+                    device: member.clone(),
+                    result,
+                }
+            });
+        GroupState {
+            members: futures::future::join_all(reads).await,
+        }
+    }
+
+    /// Sends the command to the members the filter accepts. Members that were not found are still reported.
+    async fn execute_where(
+        &self,
+        devices: &mut [Device],
+        command: &DeviceCommand,
+        filter: impl Fn(&Device) -> bool,
+    ) -> Vec<GroupResult<()>> {
+        let executes = self
+            .members
+            .iter()
+            .zip(self.targets(devices))
+            .filter(|(_, device)| device.as_deref().is_none_or(&filter))
+            .map(|(member, device)| async move {
+                let result = match device {
+                    Some(device) => device.execute(command).await.map_err(GroupError::Command),
+                    None => Err(GroupError::NotFound(member.clone())),
+                };
+                GroupResult {
+                    device: member.clone(),
+                    result,
+                }
+            });
+        futures::future::join_all(executes).await
+    }
+
+    fn targets<'a>(&self, devices: &'a mut [Device]) -> Vec<Option<&'a mut Device>> {
+        let names: Vec<&str> = self.members.iter().map(String::as_str).collect();
+        match_devices(devices, &names)
+    }
+}
+
+impl<T> GroupResult<T> {
+    /// Returns true when the member succeeded.
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl GroupState {
+    /// Returns how many members are on. Tanks, HVACs and unread members are not counted.
+    pub fn on(&self) -> usize {
+        self.states()
+            .filter(|state| state.is_on() == Some(true))
+            .count()
+    }
+
+    /// Returns how many members are off. Tanks, HVACs and unread members are not counted.
+    pub fn off(&self) -> usize {
+        self.states()
+            .filter(|state| state.is_on() == Some(false))
+            .count()
+    }
+
+    /// Returns true when any switch, dimmer or generator in the group is on.
+    pub fn any_on(&self) -> bool {
+        self.on() > 0
+    }
+
+    /// Returns the average brightness of the group's dimmers, or None when it has none.
+    pub fn average_brightness(&self) -> Option<u8> {
+        let brightnesses: Vec<u32> = self
+            .states()
+            .filter_map(|state| match state {
+                DeviceState::Dimmer { brightness } => Some(brightness.value() as u32),
+                _ => None,
+            })
+            .collect();
+        if brightnesses.is_empty() {
+            return None;
+        }
+        Some((brightnesses.iter().sum::<u32>() / brightnesses.len() as u32) as u8)
+    }
+
+    /// Returns the members whose state could not be read.
+    pub fn failed(&self) -> impl Iterator<Item = &GroupResult<DeviceState>> {
+        self.members.iter().filter(|member| !member.is_ok())
+    }
+
+    fn states(&self) -> impl Iterator<Item = &DeviceState> {
+        self.members
+            .iter()
+            .filter_map(|member| member.result.as_ref().ok())
+    }
+}
+
+/// A member of a group could not be read or set.
+#[derive(Debug, Error)]
+pub enum GroupError {
+    /// No device matches the label or UID.
+    #[error("No device matches \"{0}\".")]
+    NotFound(String),
+    /// The member's state could not be read.
+    #[error("{0}")]
+    State(DeviceStateError),
+    /// The command failed on the member.
+    #[error("{0}")]
+    Command(DeviceCommandError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, Thing};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Serves dimmer 0002 at 20% and every other item at 80, and records each command as "path body".
+    async fn gateway(commands: Arc<Mutex<Vec<String>>>) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(socket);
+                let mut request = String::new();
+                reader.read_line(&mut request).await.unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let path = request.split_whitespace().nth(1).unwrap();
+                let state = if path.ends_with("_online") {
+                    "ON"
+                } else if path.contains("0002") {
+                    "20"
+                } else {
+                    "80"
+                };
+                if request.starts_with("POST") {
+                    commands.lock().unwrap().push(format!(
+                        "{} {}",
+                        path,
+                        String::from_utf8_lossy(&body)
+                    ));
+                }
+                let body = format!("{{\"state\": \"{}\"}}", state);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                reader
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    fn device(client: &Client, label: &str, uid: &str, device_type: u8) -> Device {
+        let mut thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": label,
+            "UID": uid,
+            "configuration": {"deviceType": device_type},
+        }))
+        .unwrap();
+        thing.set_client(client.clone());
+        Device::from_thing(thing).unwrap()
+    }
+
+    /// Two dimmers with the same label and a tank.
+    fn devices(client: &Client) -> Vec<Device> {
+        vec![
+            device(client, "Porch Light", "idsmyrv:dimmer:0001", 20),
+            device(client, "Porch Light", "idsmyrv:dimmer:0002", 20),
+            device(client, "Porch Tank", "idsmyrv:tank-sensor:0003", 10),
+        ]
+    }
+
+    fn member(state: serde_json::Value) -> GroupResult<DeviceState> {
+        GroupResult {
+            device: "member".to_string(),
+            result: Ok(serde_json::from_value(state).unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_each_member_once() {
+        let client = gateway(Arc::new(Mutex::new(Vec::new()))).await;
+        let group = Group::new(
+            "Porch",
+            vec![
+                "Porch Light".to_string(),
+                "Porch Light".to_string(),
+                "Awning".to_string(),
+            ],
+        );
+        let state = group.state(&devices(&client)).await;
+
+        let brightnesses: Vec<u8> = state
+            .states()
+            .map(|state| match state {
+                DeviceState::Dimmer { brightness } => brightness.value(),
+                state => panic!("{:?} is not a dimmer", state),
+            })
+            .collect();
+        assert_eq!(brightnesses, [80, 20]);
+        assert_eq!(state.average_brightness(), Some(50));
+        assert_eq!(state.on(), 2);
+        let failed: Vec<&GroupResult<DeviceState>> = state.failed().collect();
+        assert_eq!(failed.len(), 1);
+        assert!(matches!(
+            failed[0].result(),
+            Err(GroupError::NotFound(name)) if name == "Awning"
+        ));
+    }
+
+    #[tokio::test]
+    async fn sends_commands_to_the_members_that_take_them() {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let client = gateway(commands.clone()).await;
+        let mut devices = devices(&client);
+        let group = Group::room("porch", &devices).with_member("Awning");
+        assert_eq!(group.members().len(), 4);
+
+        let results = group.off(&mut devices).await;
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert!(matches!(results[2].result(), Err(GroupError::NotFound(_))));
+        let mut sent = commands.lock().unwrap().clone();
+        sent.sort();
+        assert_eq!(
+            sent,
+            [
+                "/rest/items/idsmyrv_dimmer_0001_dimmer OFF",
+                "/rest/items/idsmyrv_dimmer_0002_dimmer OFF",
+            ]
+        );
+
+        let results = group
+            .execute(&mut devices, &DeviceCommand::SetBrightness(40))
+            .await;
+        assert!(matches!(
+            results[2].result(),
+            Err(GroupError::Command(DeviceCommandError::Unsupported(..)))
+        ));
+    }
+
+    #[test]
+    fn sums_up_member_states() {
+        let state = GroupState {
+            members: vec![
+                member(serde_json::json!({"type": "dimmer", "brightness": 0})),
+                member(serde_json::json!({"type": "dimmer", "brightness": 45})),
+                member(serde_json::json!({"type": "dimmer", "brightness": 100})),
+                member(
+                    serde_json::json!({"type": "switch", "state": "On", "fault": "Off", "relay_current": "1.0"}),
+                ),
+                member(serde_json::json!({"type": "tank", "level": 50})),
+                GroupResult {
+                    device: "Awning".to_string(),
+                    result: Err(GroupError::NotFound("Awning".to_string())),
+                },
+            ],
+        };
+        assert_eq!(state.on(), 3);
+        assert_eq!(state.off(), 1);
+        assert!(state.any_on());
+        assert_eq!(state.average_brightness(), Some(48));
+        assert_eq!(state.failed().count(), 1);
+
+        let state = GroupState {
+            members: vec![
+                member(serde_json::json!({"type": "dimmer", "brightness": 0})),
+                member(serde_json::json!({"type": "tank", "level": 50})),
+            ],
+        };
+        assert_eq!(state.on(), 0);
+        assert_eq!(state.off(), 1);
+        assert!(!state.any_on());
+        assert_eq!(state.average_brightness(), Some(0));
+
+        let state = GroupState {
+            members: vec![member(serde_json::json!({"type": "tank", "level": 50}))],
+        };
+        assert_eq!(state.average_brightness(), None);
+    }
+}
@@ -0,0 +1,352 @@
+use super::{DeviceChange, DeviceSnapshot, DeviceState, OnlineState};
+use derive_getters::Getters;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// A device's snapshot and when it was recorded.
+#[derive(Clone, Debug, Getters, PartialEq)]
+pub struct Entry {
+    /// When the change was observed.
+    time: SystemTime,
+    /// The device as it was from then on.
+    snapshot: DeviceSnapshot,
+}
+
+/// Every observed state change per device, kept in a SQLite database.
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    /// Opens the database at the path, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, HistoryError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Opens a database that only lives as long as this value.
+    pub fn in_memory() -> Result<Self, HistoryError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self, HistoryError> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS changes (
+                id INTEGER PRIMARY KEY,
+                uid TEXT NOT NULL,
+                time INTEGER NOT NULL,
+                snapshot TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS changes_uid_time ON changes (uid, time);",
+        )?;
+        Ok(Self { connection })
+    }
+
+    /// Records the changes from `Watcher::poll` as happening at the given time.
+    pub fn record(
+        &mut self,
+        changes: &[DeviceChange],
+        time: SystemTime,
+    ) -> Result<(), HistoryError> {
+        let transaction = self.connection.transaction()?;
+        for change in changes {
+            transaction.execute(
+                "INSERT INTO changes (uid, time, snapshot) VALUES (?1, ?2, ?3)",
+                params![
+                    change.current().uid(),
+                    millis(time),
+                    serde_json::to_string(change.current())?
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Returns the device's changes from `from` up to, but not including, `to`, oldest first.
+    pub fn range(
+        &self,
+        uid: &str,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<Vec<Entry>, HistoryError> {
+        let mut statement = self.connection.prepare(
+            "SELECT time, snapshot FROM changes WHERE uid = ?1 AND time >= ?2 AND time < ?3 ORDER BY time, id",
+        )?;
+        let rows = statement.query_map(params![uid, millis(from), millis(to)], row)?;
+        rows.map(|row| entry(row?)).collect()
+    }
+
+    /// Returns the device's most recent change.
+    pub fn last_change(&self, uid: &str) -> Result<Option<Entry>, HistoryError> {
+        self.before(uid, i64::MAX)
+    }
+
+    /// Returns the device's most recent change whose state matches, ex: when the black tank was last below 5%.
+    pub fn last_matching(
+        &self,
+        uid: &str,
+        matches: impl Fn(&DeviceState) -> bool,
+    ) -> Result<Option<Entry>, HistoryError> {
+        let mut statement = self.connection.prepare(
+            "SELECT time, snapshot FROM changes WHERE uid = ?1 ORDER BY time DESC, id DESC",
+        )?;
+        for row in statement.query_map(params![uid], row)? {
+            let entry = entry(row?)?;
+            if matches(entry.snapshot.state()) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns how long the device spent in each state between `from` and `to`, grouped by the key.
+    /// The state before `from` counts from `from`; time before the first recorded change is not counted, and
+    /// neither is time the device was offline, as an offline snapshot only repeats its last readings.
+    ///
+    /// Only changes are recorded, so the last state counts up to `to`, or up to now when `to` is later. A
+    /// recorder that stopped can not be told apart from a device that did not change: end the range where
+    /// recording stopped, ex: at `last_change` of a device that changes often.
+    ///
+    /// ex: how long an A/C ran yesterday, by HVAC status:
+    /// `history.durations(uid, start, end, |state| match state { DeviceState::Hvac { status, .. } => Some(*status), _ => None })`
+    pub fn durations<K: Eq + Hash>(
+        &self,
+        uid: &str,
+        from: SystemTime,
+        to: SystemTime,
+        key: impl Fn(&DeviceState) -> K,
+    ) -> Result<HashMap<K, Duration>, HistoryError> {
+        let mut entries: Vec<Entry> = self.before(uid, millis(from))?.into_iter().collect();
+        entries.extend(self.range(uid, from, to)?);
+
+        let to = to.min(SystemTime::now());
+        let mut durations = HashMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            if *entry.snapshot.online() != OnlineState::Online {
+                continue;
+            }
+            let start = entry.time.max(from);
+            let end = entries
+                .get(index + 1)
+                .map(|next| next.time)
+                .unwrap_or(to)
+                .min(to);
+            if let Ok(duration) = end.duration_since(start) {
+                *durations
+                    .entry(key(entry.snapshot.state()))
+                    .or_insert(Duration::ZERO) += duration;
+            }
+        }
+        Ok(durations)
+    }
+
+    /// Returns the last change strictly before the time in milliseconds.
+    fn before(&self, uid: &str, time: i64) -> Result<Option<Entry>, HistoryError> {
+        self.connection
+            .query_row(
+                "SELECT time, snapshot FROM changes WHERE uid = ?1 AND time < ?2 ORDER BY time DESC, id DESC LIMIT 1",
+                params![uid, time],
+                row,
+            )
+            .optional()?
+            .map(entry)
+            .transpose()
+    }
+}
+
+fn row(row: &rusqlite::Row) -> rusqlite::Result<(i64, String)> {
+    Ok((row.get(0)?, row.get(1)?))
+}
+
+fn entry((time, snapshot): (i64, String)) -> Result<Entry, HistoryError> {
+    Ok(Entry {
+        time: UNIX_EPOCH + Duration::from_millis(time.max(0) as u64),
+        snapshot: serde_json::from_str(&snapshot)?,
+    })
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// The history could not be read or written.
+#[derive(Debug, Error)]
+pub enum HistoryError {
+    /// The SQLite database failed.
+    #[error("The history database failed. {0}")]
+    Sqlite(rusqlite::Error),
+    /// A snapshot could not be stored or read back.
+    #[error("A snapshot could not be converted. {0}")]
+    SerdeJsonConversion(serde_json::Error),
+}
+
+impl From<rusqlite::Error> for HistoryError {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::Sqlite(error)
+    }
+}
+
+impl From<serde_json::Error> for HistoryError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::SerdeJsonConversion(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: u64 = 60;
+
+    fn tank(uid: &str, level: u8) -> DeviceChange {
+        let current = serde_json::from_value(serde_json::json!({
+            "label": "Black Tank",
+            "uid": uid,
+            "device_type": "Tank",
+            "online": "Online",
+            "state": {"type": "tank", "level": level},
+        }))
+        .unwrap();
+        DeviceChange::new(None, current)
+    }
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000 + minutes * MINUTE)
+    }
+
+    fn level(entry: &Entry) -> u8 {
+        match entry.snapshot().state() {
+            DeviceState::Tank { level } => level.value(),
+            state => panic!("{} is not a tank", state),
+        }
+    }
+
+    /// The black tank at 10% from minute 0, 40% from 10, 80% from 30 and 2% from 40, with a grey tank
+    /// recorded alongside.
+    fn history() -> History {
+        let mut history = History::in_memory().unwrap();
+        for (minutes, percent) in [(0, 10), (10, 40), (30, 80), (40, 2)] {
+            history
+                .record(&[tank("black", percent), tank("grey", 50)], at(minutes))
+                .unwrap();
+        }
+        history
+    }
+
+    #[test]
+    fn ranges_include_the_start_only() {
+        let history = history();
+        let levels: Vec<u8> = history
+            .range("black", at(10), at(40))
+            .unwrap()
+            .iter()
+            .map(level)
+            .collect();
+        assert_eq!(levels, [40, 80]);
+        assert_eq!(
+            *history.range("black", at(10), at(40)).unwrap()[0].time(),
+            at(10)
+        );
+        assert!(history.range("black", at(41), at(50)).unwrap().is_empty());
+        assert!(history.range("fresh", at(0), at(50)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn finds_the_last_change() {
+        let history = history();
+        let last = history.last_change("black").unwrap().unwrap();
+        assert_eq!((level(&last), *last.time()), (2, at(40)));
+        assert_eq!(history.last_change("fresh").unwrap(), None);
+    }
+
+    #[test]
+    fn finds_the_last_matching_change() {
+        let history = history();
+        let above = |limit: u8| move |state: &DeviceState| matches!(state, DeviceState::Tank { level } if level.value() > limit);
+        let full = history.last_matching("black", above(75)).unwrap().unwrap();
+        assert_eq!(*full.time(), at(30));
+        let half = history.last_matching("black", above(30)).unwrap().unwrap();
+        assert_eq!(*half.time(), at(30));
+        assert_eq!(history.last_matching("black", above(90)).unwrap(), None);
+    }
+
+    #[test]
+    fn sums_durations_clipped_to_the_range() {
+        let history = history();
+        let over_half = |state: &DeviceState| matches!(state, DeviceState::Tank { level } if level.value() > 50);
+        // From minute 5, inside the first state, to 35, inside the third.
+        let durations = history
+            .durations("black", at(5), at(35), over_half)
+            .unwrap();
+        assert_eq!(durations[&false], Duration::from_secs(25 * MINUTE));
+        assert_eq!(durations[&true], Duration::from_secs(5 * MINUTE));
+
+        // The latest state counts up to the end of the range.
+        let durations = history
+            .durations("black", at(35), at(60), over_half)
+            .unwrap();
+        assert_eq!(durations[&true], Duration::from_secs(5 * MINUTE));
+        assert_eq!(durations[&false], Duration::from_secs(20 * MINUTE));
+    }
+
+    #[test]
+    fn skips_time_offline() {
+        let mut history = History::in_memory().unwrap();
+        let offline = DeviceChange::new(None, tank("black", 40).current().offline());
+        history.record(&[tank("black", 40)], at(0)).unwrap();
+        history.record(&[offline], at(10)).unwrap();
+        history.record(&[tank("black", 40)], at(25)).unwrap();
+        let durations = history.durations("black", at(0), at(30), |_| ()).unwrap();
+        assert_eq!(durations[&()], Duration::from_secs(15 * MINUTE));
+    }
+
+    #[test]
+    fn counts_the_last_state_up_to_now() {
+        let mut history = History::in_memory().unwrap();
+        let now = SystemTime::now();
+        history
+            .record(&[tank("black", 40)], now - Duration::from_secs(10 * MINUTE))
+            .unwrap();
+        let durations = history
+            .durations(
+                "black",
+                now - Duration::from_secs(60 * MINUTE),
+                now + Duration::from_secs(24 * 60 * MINUTE),
+                |_| (),
+            )
+            .unwrap();
+        assert!(durations[&()] >= Duration::from_secs(10 * MINUTE));
+        assert!(durations[&()] < Duration::from_secs(11 * MINUTE));
+    }
+
+    #[test]
+    fn skips_time_before_the_first_change() {
+        let history = history();
+        let durations = history
+            .durations("black", UNIX_EPOCH, at(10), |_| ())
+            .unwrap();
+        assert_eq!(durations[&()], Duration::from_secs(10 * MINUTE));
+        assert!(history
+            .durations("fresh", at(0), at(10), |_| ())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn reopens_recorded_files() {
+        let path = std::env::temp_dir().join(format!("lci-history-{}.db", std::process::id()));
+        History::open(&path)
+            .unwrap()
+            .record(&[tank("black", 10)], at(0))
+            .unwrap();
+        let last = History::open(&path).unwrap().last_change("black").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(last.map(|entry| level(&entry)), Some(10));
+    }
+}
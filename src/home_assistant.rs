@@ -0,0 +1,471 @@
+use super::mqtt::{Topics, PAYLOAD_OFFLINE, PAYLOAD_ONLINE};
+use super::{Device, DeviceType};
+use derive_getters::Getters;
+use serde_json::{json, Value};
+
+/// Where Home Assistant listens for discovery messages and how values are presented.
+#[derive(Clone, Debug)]
+pub struct HomeAssistantConfig {
+    /// Home Assistant's discovery prefix, "homeassistant" unless changed in Home Assistant.
+    pub prefix: String,
+    /// The unit the gateway reports temperatures in, "F" or "C".
+    pub temperature_unit: String,
+}
+
+impl Default for HomeAssistantConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "homeassistant".to_string(),
+            temperature_unit: "F".to_string(),
+        }
+    }
+}
+
+/// A single discovery config, to be published retained to its topic.
+#[derive(Clone, Debug, Getters, PartialEq)]
+pub struct DiscoveryMessage {
+    /// The config topic, ex: "homeassistant/light/<id>/config".
+    topic: String,
+    /// The JSON config for the entity.
+    payload: Value,
+}
+
+/// Returns the discovery configs for every entity of the device.
+/// * Dimmer: a light with brightness.
+/// * Switch: a switch, a relay current sensor and a fault binary sensor.
+/// * Tank: a percentage sensor.
+/// * Generator: a switch and a state sensor.
+/// * HVAC: a climate entity with modes, fan modes and dual setpoints, and a status sensor.
+pub fn discovery_messages(
+    config: &HomeAssistantConfig,
+    topics: &Topics,
+    device: &Device,
+) -> Vec<DiscoveryMessage> {
+    let entity = Entity {
+        config,
+        topics,
+        device,
+    };
+    match device.device_type() {
+        DeviceType::Dimmer => vec![entity.message(
+            "light",
+            "",
+            json!({
+                "schema": "basic",
+                "state_topic": topics.state(device, "state"),
+                "command_topic": topics.command(device, "state"),
+                "payload_on": "On",
+                "payload_off": "Off",
+                "brightness_state_topic": topics.state(device, "brightness"),
+                "brightness_command_topic": topics.command(device, "brightness"),
+                "brightness_scale": 100,
+                "on_command_type": "brightness",
+            }),
+        )],
+        DeviceType::Switch => vec![
+            entity.message(
+                "switch",
+                "",
+                json!({
+                    "state_topic": topics.state(device, "state"),
+                    "command_topic": topics.command(device, "state"),
+                    "payload_on": "On",
+                    "payload_off": "Off",
+                }),
+            ),
+            entity.message(
+                "sensor",
+                "relay_current",
+                json!({
+                    "name": "Relay current",
+                    "state_topic": topics.state(device, "relay_current"),
+                    "device_class": "current",
+                    "state_class": "measurement",
+                    "unit_of_measurement": "A",
+                }),
+            ),
+            entity.message(
+                "binary_sensor",
+                "fault",
+                json!({
+                    "name": "Fault",
+                    "state_topic": topics.state(device, "fault"),
+                    "device_class": "problem",
+                    "payload_on": "On",
+                    "payload_off": "Off",
+                }),
+            ),
+        ],
+        DeviceType::Tank => vec![entity.message(
+            "sensor",
+            "level",
+            json!({
+                "state_topic": topics.state(device, "level"),
+                "state_class": "measurement",
+                "unit_of_measurement": "%",
+                "icon": "mdi:storage-tank",
+            }),
+        )],
+        DeviceType::Generator => vec![
+            entity.message(
+                "switch",
+                "",
+                json!({
+                    "state_topic": topics.state(device, "state"),
+                    "command_topic": topics.command(device, "state"),
+                    "value_template": "{{ 'Off' if value == 'Off' else 'On' }}",
+                    "payload_on": "On",
+                    "payload_off": "Off",
+                    "icon": "mdi:engine",
+                }),
+            ),
+            entity.message(
+                "sensor",
+                "state",
+                json!({
+                    "name": "State",
+                    "state_topic": topics.state(device, "state"),
+                    "device_class": "enum",
+                    "options": ["Off", "Priming", "Starting", "Running"],
+                }),
+            ),
+        ],
+        DeviceType::Hvac => vec![
+            entity.message(
+                "climate",
+                "",
+                json!({
+                    "modes": ["off", "heat", "cool", "heat_cool"],
+                    "mode_state_topic": topics.state(device, "mode"),
+                    "mode_state_template": "{{ {'Off': 'off', 'Heat': 'heat', 'Cool': 'cool', 'HeatCool': 'heat_cool'}[value] | default('off') }}",
+                    "mode_command_topic": topics.command(device, "mode"),
+                    "fan_modes": ["auto", "low", "high"],
+                    "fan_mode_state_topic": topics.state(device, "fan"),
+                    "fan_mode_state_template": "{{ value | lower }}",
+                    "fan_mode_command_topic": topics.command(device, "fan"),
+                    "current_temperature_topic": topics.state(device, "inside_temperature"),
+                    "temperature_high_state_topic": topics.state(device, "high_temperature"),
+                    "temperature_high_command_topic": topics.command(device, "high_temperature"),
+                    "temperature_low_state_topic": topics.state(device, "low_temperature"),
+                    "temperature_low_command_topic": topics.command(device, "low_temperature"),
+                    "action_topic": topics.state(device, "status"),
+                    "action_template": "{{ {'Off': 'off', 'Idle': 'idle', 'Cooling': 'cooling', 'Heat Pump': 'heating', 'Electric Furnace': 'heating', 'Gas Furnace': 'heating', 'Gas Override': 'heating'}[value] | default('off') }}",
+                    "temperature_unit": config.temperature_unit,
+                    "precision": 1.0,
+                }),
+            ),
+            entity.message(
+                "sensor",
+                "status",
+                json!({
+                    "name": "Status",
+                    "state_topic": topics.state(device, "status"),
+                    "icon": "mdi:hvac",
+                }),
+            ),
+            entity.message(
+                "sensor",
+                "outside_temperature",
+                json!({
+                    "name": "Outside temperature",
+                    "state_topic": topics.state(device, "outside_temperature"),
+                    "device_class": "temperature",
+                    "state_class": "measurement",
+                    "unit_of_measurement": format!("°{}", config.temperature_unit),
+                }),
+            ),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+struct Entity<'a> {
+    config: &'a HomeAssistantConfig,
+    topics: &'a Topics,
+    device: &'a Device,
+}
+
+impl Entity<'_> {
+    /// Builds the message for one entity, adding the fields every entity shares.
+    /// An empty suffix makes the entity the device's main one, named after the device.
+    fn message(&self, component: &str, suffix: &str, mut payload: Value) -> DiscoveryMessage {
+        let object_id = self.device.object_id();
+        let unique_id = if suffix.is_empty() {
+            object_id.clone()
+        } else {
+            format!("{}_{}", object_id, suffix)
+        };
+
+        if let Value::Object(fields) = &mut payload {
+            if suffix.is_empty() {
+                fields.insert("name".to_string(), Value::Null);
+            }
+            fields.insert("unique_id".to_string(), json!(unique_id));
+            fields.insert(
+                "availability".to_string(),
+                json!([
+                    {
+                        "topic": self.topics.availability(),
+                        "payload_available": PAYLOAD_ONLINE,
+                        "payload_not_available": PAYLOAD_OFFLINE,
+                    },
+                    {
+                        "topic": self.topics.state(self.device, "online"),
+                        "payload_available": "Online",
+                        "payload_not_available": "Offline",
+                    },
+                ]),
+            );
+            fields.insert("availability_mode".to_string(), json!("all"));
+            fields.insert(
+                "device".to_string(),
+                json!({
+                    "identifiers": [self.device.uid()],
+                    "name": self.device.label(),
+                    "manufacturer": "LCI",
+                    "model": self.device.device_type().to_string(),
+                }),
+            );
+        }
+
+        DiscoveryMessage {
+            topic: format!("{}/{}/{}/config", self.config.prefix, component, unique_id),
+            payload,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Thing;
+
+    fn device(label: &str, uid: &str, device_type: u8) -> Device {
+        let thing: Thing = serde_json::from_value(json!({
+            "label": label,
+            "UID": uid,
+            "configuration": {"deviceType": device_type},
+        }))
+        .unwrap();
+        Device::from_thing(thing).unwrap()
+    }
+
+    fn messages(device: &Device) -> Vec<DiscoveryMessage> {
+        discovery_messages(&HomeAssistantConfig::default(), &Topics::default(), device)
+    }
+
+    /// Returns each message's topic and unique ID, checking the fields every entity shares on the way.
+    fn entities(device: &Device) -> Vec<(String, String)> {
+        messages(device)
+            .into_iter()
+            .map(|message| {
+                let payload = message.payload();
+                assert_eq!(
+                    payload["availability"],
+                    json!([
+                        {"topic": "lci/status", "payload_available": "online", "payload_not_available": "offline"},
+                        {
+                            "topic": Topics::default().state(device, "online"),
+                            "payload_available": "Online",
+                            "payload_not_available": "Offline",
+                        },
+                    ])
+                );
+                assert_eq!(payload["availability_mode"], "all");
+                assert_eq!(payload["device"]["identifiers"], json!([device.uid()]));
+                assert_eq!(payload["device"]["name"], device.label());
+                (
+                    message.topic().clone(),
+                    payload["unique_id"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn owned(entities: &[(&str, &str)]) -> Vec<(String, String)> {
+        entities
+            .iter()
+            .map(|(topic, unique_id)| (topic.to_string(), unique_id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dimmer_is_a_light() {
+        let dimmer = device("Porch Light", "idsmyrv:dimmer:0002", 20);
+        assert_eq!(
+            entities(&dimmer),
+            owned(&[(
+                "homeassistant/light/idsmyrv_dimmer_0002/config",
+                "idsmyrv_dimmer_0002"
+            )])
+        );
+        let payload = messages(&dimmer)[0].payload().clone();
+        assert_eq!(payload["name"], Value::Null);
+        assert_eq!(
+            payload["state_topic"],
+            "lci/dimmer/idsmyrv_dimmer_0002/state"
+        );
+        assert_eq!(
+            payload["command_topic"],
+            "lci/dimmer/idsmyrv_dimmer_0002/state/set"
+        );
+        assert_eq!(
+            payload["brightness_state_topic"],
+            "lci/dimmer/idsmyrv_dimmer_0002/brightness"
+        );
+        assert_eq!(
+            payload["brightness_command_topic"],
+            "lci/dimmer/idsmyrv_dimmer_0002/brightness/set"
+        );
+    }
+
+    #[test]
+    fn switch_has_a_current_sensor_and_a_fault() {
+        let switch = device("Water Pump", "idsmyrv:relay:0003", 30);
+        assert_eq!(
+            entities(&switch),
+            owned(&[
+                (
+                    "homeassistant/switch/idsmyrv_relay_0003/config",
+                    "idsmyrv_relay_0003"
+                ),
+                (
+                    "homeassistant/sensor/idsmyrv_relay_0003_relay_current/config",
+                    "idsmyrv_relay_0003_relay_current"
+                ),
+                (
+                    "homeassistant/binary_sensor/idsmyrv_relay_0003_fault/config",
+                    "idsmyrv_relay_0003_fault"
+                ),
+            ])
+        );
+        let messages = messages(&switch);
+        assert_eq!(
+            messages[0].payload()["command_topic"],
+            "lci/switch/idsmyrv_relay_0003/state/set"
+        );
+        assert_eq!(
+            messages[1].payload()["state_topic"],
+            "lci/switch/idsmyrv_relay_0003/relay_current"
+        );
+        assert_eq!(
+            messages[2].payload()["state_topic"],
+            "lci/switch/idsmyrv_relay_0003/fault"
+        );
+        assert!(messages[1].payload().get("command_topic").is_none());
+    }
+
+    #[test]
+    fn tank_is_a_percentage_sensor() {
+        let tank = device("Fresh Water", "idsmyrv:tank-sensor:0001", 10);
+        assert_eq!(
+            entities(&tank),
+            owned(&[(
+                "homeassistant/sensor/idsmyrv_tank_sensor_0001_level/config",
+                "idsmyrv_tank_sensor_0001_level"
+            )])
+        );
+        let payload = messages(&tank)[0].payload().clone();
+        assert_eq!(
+            payload["state_topic"],
+            "lci/tank/idsmyrv_tank_sensor_0001/level"
+        );
+        assert_eq!(payload["unit_of_measurement"], "%");
+        assert!(payload.get("command_topic").is_none());
+    }
+
+    #[test]
+    fn generator_has_a_switch_and_a_state() {
+        let generator = device("Generator", "idsmyrv:generator:0004", 24);
+        assert_eq!(
+            entities(&generator),
+            owned(&[
+                (
+                    "homeassistant/switch/idsmyrv_generator_0004/config",
+                    "idsmyrv_generator_0004"
+                ),
+                (
+                    "homeassistant/sensor/idsmyrv_generator_0004_state/config",
+                    "idsmyrv_generator_0004_state"
+                ),
+            ])
+        );
+        let messages = messages(&generator);
+        assert_eq!(
+            messages[0].payload()["command_topic"],
+            "lci/generator/idsmyrv_generator_0004/state/set"
+        );
+        assert_eq!(
+            messages[1].payload()["state_topic"],
+            "lci/generator/idsmyrv_generator_0004/state"
+        );
+    }
+
+    #[test]
+    fn hvac_is_a_climate_entity() {
+        let hvac = device("Bedroom", "idsmyrv:hvac:0005", 16);
+        assert_eq!(
+            entities(&hvac),
+            owned(&[
+                (
+                    "homeassistant/climate/idsmyrv_hvac_0005/config",
+                    "idsmyrv_hvac_0005"
+                ),
+                (
+                    "homeassistant/sensor/idsmyrv_hvac_0005_status/config",
+                    "idsmyrv_hvac_0005_status"
+                ),
+                (
+                    "homeassistant/sensor/idsmyrv_hvac_0005_outside_temperature/config",
+                    "idsmyrv_hvac_0005_outside_temperature"
+                ),
+            ])
+        );
+        let payload = messages(&hvac)[0].payload().clone();
+        for (field, topic) in [
+            ("mode_state_topic", "mode"),
+            ("mode_command_topic", "mode/set"),
+            ("fan_mode_state_topic", "fan"),
+            ("fan_mode_command_topic", "fan/set"),
+            ("current_temperature_topic", "inside_temperature"),
+            ("temperature_high_state_topic", "high_temperature"),
+            ("temperature_high_command_topic", "high_temperature/set"),
+            ("temperature_low_state_topic", "low_temperature"),
+            ("temperature_low_command_topic", "low_temperature/set"),
+            ("action_topic", "status"),
+        ] {
+            assert_eq!(
+                payload[field],
+                format!("lci/hvac/idsmyrv_hvac_0005/{}", topic),
+                "{}",
+                field
+            );
+        }
+        assert_eq!(payload["temperature_unit"], "F");
+    }
+
+    #[test]
+    fn follows_the_prefix_and_topics() {
+        let config = HomeAssistantConfig {
+            prefix: "ha".to_string(),
+            temperature_unit: "C".to_string(),
+        };
+        let topics = Topics {
+            base: "coach".to_string(),
+            ..Topics::default()
+        };
+        let hvac = device("Bedroom", "idsmyrv:hvac:0005", 16);
+        let messages = discovery_messages(&config, &topics, &hvac);
+        assert_eq!(messages[0].topic(), "ha/climate/idsmyrv_hvac_0005/config");
+        assert_eq!(
+            messages[0].payload()["mode_command_topic"],
+            "coach/hvac/idsmyrv_hvac_0005/mode/set"
+        );
+        assert_eq!(
+            messages[0].payload()["availability"][0]["topic"],
+            "coach/status"
+        );
+        assert_eq!(messages[0].payload()["temperature_unit"], "C");
+        assert_eq!(messages[2].payload()["unit_of_measurement"], "°C");
+    }
+}
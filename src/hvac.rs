@@ -1,4 +1,5 @@
 use super::{common, DeviceType, Thing};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// A connected A/C
@@ -7,7 +8,7 @@ pub struct HVAC {
 }
 
 /// Possible HVAC fan settings.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum HvacFanMode {
     /// Let the LCI or A/C decide fan level.
     Auto,
@@ -18,7 +19,7 @@ pub enum HvacFanMode {
 }
 
 /// Posible states for the A/C system.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum HvacMode {
     /// The A/C is not requested.
     Off,
@@ -31,7 +32,7 @@ pub enum HvacMode {
 }
 
 /// The current state of the A/C HVAC. If it is actively cooling, failing, etc.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum HvacStatus {
     Off,
     Idle,
@@ -53,6 +54,17 @@ pub enum HvacStatus {
     FailShedding,
 }
 
+/// What an HVAC heats with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HeatSource {
+    /// The A/C running in reverse.
+    HeatPump,
+    /// Electric heating elements.
+    ElectricFurnace,
+    /// A propane furnace.
+    GasFurnace,
+}
+
 impl HvacFanMode {
     /// Returns HvacFan enum value from the string value.
     /// Accepted values: ["AUTO", "LOW", "HIGH"]
@@ -105,6 +117,38 @@ impl HvacStatus {
             _ => Err(HvacStatusConversionError::UnknownValue(string)),
         }
     }
+
+    /// Returns true for any of the Fail statuses.
+    pub fn is_failure(&self) -> bool {
+        self.underlying_status() != *self
+    }
+
+    /// Returns what the unit is doing, ignoring the failure, ex: Cooling for FailCooling.
+    /// Statuses that are not failures are returned as they are.
+    pub fn underlying_status(&self) -> HvacStatus {
+        match self {
+            HvacStatus::FailOff => HvacStatus::Off,
+            HvacStatus::FailIdle => HvacStatus::Idle,
+            HvacStatus::FailCooling => HvacStatus::Cooling,
+            HvacStatus::FailHeatPump => HvacStatus::HeatPump,
+            HvacStatus::FailElectricFurnace => HvacStatus::ElectricFurnace,
+            HvacStatus::FailGasFurnace => HvacStatus::GasFurnace,
+            HvacStatus::FailGasOverride => HvacStatus::GasOverride,
+            HvacStatus::FailDeadTime => HvacStatus::DeadTime,
+            HvacStatus::FailShedding => HvacStatus::LoadShedding,
+            status => *status,
+        }
+    }
+
+    /// Returns what the unit is heating with, or None when it is not heating.
+    pub fn heat_source(&self) -> Option<HeatSource> {
+        match self.underlying_status() {
+            HvacStatus::HeatPump => Some(HeatSource::HeatPump),
+            HvacStatus::ElectricFurnace => Some(HeatSource::ElectricFurnace),
+            HvacStatus::GasFurnace | HvacStatus::GasOverride => Some(HeatSource::GasFurnace),
+            _ => None,
+        }
+    }
 }
 
 impl HVAC {
@@ -123,6 +167,11 @@ impl HVAC {
         self.thing.label().clone()
     }
 
+    /// Returns the unique ID of the device in the LCI gateway.
+    pub fn uid(&self) -> String {
+        self.thing.UID().clone()
+    }
+
     /// Returns the device's online state.
     pub async fn online(&self) -> Result<common::OnlineState, common::OnlineStateConversionError> {
         common::get_online_state(&self.thing).await
@@ -235,6 +284,17 @@ impl std::fmt::Display for HvacMode {
     }
 }
 
+impl std::fmt::Display for HeatSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match self {
+            HeatSource::HeatPump => "Heat Pump",
+            HeatSource::ElectricFurnace => "Electric Furnace",
+            HeatSource::GasFurnace => "Gas Furnace",
+        };
+        write!(f, "{}", source)
+    }
+}
+
 impl std::fmt::Display for HvacStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mode = match self {
@@ -247,8 +307,8 @@ impl std::fmt::Display for HvacStatus {
             HvacStatus::GasOverride => "Gas Override".to_string(),
             HvacStatus::DeadTime => "Dead Time".to_string(),
             HvacStatus::LoadShedding => "Load Shedding".to_string(),
-            HvacStatus::FailOff => "Load Off".to_string(),
-            HvacStatus::FailIdle => "Load Idle".to_string(),
+            HvacStatus::FailOff => "Fail Off".to_string(),
+            HvacStatus::FailIdle => "Fail Idle".to_string(),
             HvacStatus::FailCooling => "Fail Cooling".to_string(),
             HvacStatus::FailHeatPump => "Fail Heat Pump".to_string(),
             HvacStatus::FailElectricFurnace => "Fail Electric Furnace".to_string(),
@@ -398,3 +458,96 @@ impl From<common::GetFailure> for HvacModeConversionError {
         Self::GetFailure(error)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [(&str, HvacStatus, &str); 18] = [
+        ("OFF", HvacStatus::Off, "Off"),
+        ("IDLE", HvacStatus::Idle, "Idle"),
+        ("COOLING", HvacStatus::Cooling, "Cooling"),
+        ("HEAT_PUMP", HvacStatus::HeatPump, "Heat Pump"),
+        (
+            "ELEC_FURNACE",
+            HvacStatus::ElectricFurnace,
+            "Electric Furnace",
+        ),
+        ("GAS_FURNACE", HvacStatus::GasFurnace, "Gas Furnace"),
+        ("GAS_OVERRIDE", HvacStatus::GasOverride, "Gas Override"),
+        ("DEAD_TIME", HvacStatus::DeadTime, "Dead Time"),
+        ("LOAD_SHEDDING", HvacStatus::LoadShedding, "Load Shedding"),
+        ("FAIL_OFF", HvacStatus::FailOff, "Fail Off"),
+        ("FAIL_IDLE", HvacStatus::FailIdle, "Fail Idle"),
+        ("FAIL_COOLING", HvacStatus::FailCooling, "Fail Cooling"),
+        ("FAIL_HEAT_PUMP", HvacStatus::FailHeatPump, "Fail Heat Pump"),
+        (
+            "FAIL_ELEC_FURNACE",
+            HvacStatus::FailElectricFurnace,
+            "Fail Electric Furnace",
+        ),
+        (
+            "FAIL_GAS_FURNACE",
+            HvacStatus::FailGasFurnace,
+            "Fail Gas Furnace",
+        ),
+        (
+            "FAIL_GAS_OVERRIDE",
+            HvacStatus::FailGasOverride,
+            "Fail Gas Override",
+        ),
+        ("FAIL_DEAD_TIME", HvacStatus::FailDeadTime, "Fail Dead Time"),
+        ("FAIL_SHEDDING", HvacStatus::FailShedding, "Fail Shedding"),
+    ];
+
+    #[test]
+    fn parses_and_displays_statuses() {
+        for (value, status, display) in STATUSES {
+            assert_eq!(
+                HvacStatus::from_string(value.to_lowercase()).unwrap(),
+                status
+            );
+            assert_eq!(status.to_string(), display);
+        }
+        assert!(HvacStatus::from_string("HEATING".to_string()).is_err());
+    }
+
+    #[test]
+    fn maps_failures_to_what_the_unit_is_doing() {
+        for (value, status, display) in STATUSES {
+            assert_eq!(status.is_failure(), value.starts_with("FAIL_"));
+            let underlying = status.underlying_status();
+            assert!(!underlying.is_failure());
+            if status.is_failure() {
+                assert_eq!(
+                    format!("Fail {}", underlying),
+                    display.replace("Shedding", "Load Shedding")
+                );
+            } else {
+                assert_eq!(underlying, status);
+            }
+        }
+    }
+
+    #[test]
+    fn finds_heat_sources() {
+        assert_eq!(
+            HvacStatus::HeatPump.heat_source(),
+            Some(HeatSource::HeatPump)
+        );
+        assert_eq!(
+            HvacStatus::FailElectricFurnace.heat_source(),
+            Some(HeatSource::ElectricFurnace)
+        );
+        assert_eq!(
+            HvacStatus::GasOverride.heat_source(),
+            Some(HeatSource::GasFurnace)
+        );
+        assert_eq!(
+            HvacStatus::FailGasFurnace.heat_source(),
+            Some(HeatSource::GasFurnace)
+        );
+        assert_eq!(HvacStatus::Cooling.heat_source(), None);
+        assert_eq!(HvacStatus::FailIdle.heat_source(), None);
+    }
+}
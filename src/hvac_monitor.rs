@@ -0,0 +1,319 @@
+use super::{DeviceSnapshot, DeviceState, HvacStatus};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+/// Something worth looking into on an HVAC, worked out from its readings.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HvacDiagnosticEvent {
+    /// The unit started reporting a fail status.
+    FailureStarted {
+        /// The fail status.
+        status: HvacStatus,
+        /// When it was first read.
+        time: SystemTime,
+    },
+    /// The unit has reported fail statuses for longer than `HvacMonitorThresholds::failure_duration`.
+    FailurePersisting {
+        /// The latest fail status.
+        status: HvacStatus,
+        /// When the unit started failing.
+        since: SystemTime,
+        /// How long it has been failing.
+        duration: Duration,
+    },
+    /// The unit stopped reporting a fail status.
+    FailureCleared {
+        /// The status it reports now.
+        status: HvacStatus,
+        /// How long it was failing.
+        duration: Duration,
+        /// When the cleared status was read.
+        time: SystemTime,
+    },
+    /// The unit has been cooling without the inside temperature dropping.
+    CoolingIneffective {
+        /// When the unit started cooling.
+        since: SystemTime,
+        /// The inside temperature when it started cooling.
+        start_temperature: f32,
+        /// The inside temperature now.
+        temperature: f32,
+    },
+}
+
+impl std::fmt::Display for HvacDiagnosticEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HvacDiagnosticEvent::FailureStarted { status, .. } => write!(f, "reports {}", status),
+            HvacDiagnosticEvent::FailurePersisting {
+                status, duration, ..
+            } => write!(
+                f,
+                "has been failing for {} minutes, now {}",
+                duration.as_secs() / 60,
+                status
+            ),
+            HvacDiagnosticEvent::FailureCleared {
+                status, duration, ..
+            } => write!(
+                f,
+                "reports {} after failing for {} minutes",
+                status,
+                duration.as_secs() / 60
+            ),
+            HvacDiagnosticEvent::CoolingIneffective {
+                start_temperature,
+                temperature,
+                ..
+            } => write!(
+                f,
+                "has been cooling without effect, from {:.1}° to {:.1}° inside",
+                start_temperature, temperature
+            ),
+        }
+    }
+}
+
+/// When the monitor raises events.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HvacMonitorThresholds {
+    /// How long a unit can fail before `FailurePersisting` is raised.
+    pub failure_duration: Duration,
+    /// How long a unit is given to cool before its effect is checked.
+    pub cooling_window: Duration,
+    /// How many degrees the inside temperature has to drop within the cooling window.
+    pub min_cooling_drop: f32,
+}
+
+impl Default for HvacMonitorThresholds {
+    fn default() -> Self {
+        Self {
+            failure_duration: Duration::from_secs(10 * 60),
+            cooling_window: Duration::from_secs(30 * 60),
+            min_cooling_drop: 1.0,
+        }
+    }
+}
+
+/// Tracks one HVAC's fail statuses and cooling runs from a stream of readings.
+#[derive(Clone, Debug, Default)]
+pub struct HvacMonitor {
+    thresholds: HvacMonitorThresholds,
+    failing_since: Option<SystemTime>,
+    failure_reported: bool,
+    /// When the current cooling run started, the inside temperature then, and if it was reported.
+    cooling: Option<(SystemTime, f32, bool)>,
+}
+
+impl HvacMonitor {
+    /// Create a monitor with the thresholds.
+    pub fn new(thresholds: HvacMonitorThresholds) -> Self {
+        Self {
+            thresholds,
+            ..Self::default()
+        }
+    }
+
+    /// Returns when the unit started failing, or None when it is not failing.
+    pub fn failing_since(&self) -> Option<SystemTime> {
+        self.failing_since
+    }
+
+    /// Returns how long the unit has been failing at the given time, or None when it is not failing.
+    pub fn fail_duration(&self, now: SystemTime) -> Option<Duration> {
+        self.failing_since
+            .map(|since| now.duration_since(since).unwrap_or_default())
+    }
+
+    /// Adds a reading and returns the events it raises.
+    pub fn push(
+        &mut self,
+        time: SystemTime,
+        status: HvacStatus,
+        inside_temperature: f32,
+    ) -> Vec<HvacDiagnosticEvent> {
+        let mut events = Vec::new();
+
+        match (status.is_failure(), self.failing_since) {
+            (true, None) => {
+                self.failing_since = Some(time);
+                self.failure_reported = false;
+                events.push(HvacDiagnosticEvent::FailureStarted { status, time });
+            }
+            (true, Some(since)) => {
+                let duration = time.duration_since(since).unwrap_or_default();
+                if !self.failure_reported && duration >= self.thresholds.failure_duration {
+                    self.failure_reported = true;
+                    events.push(HvacDiagnosticEvent::FailurePersisting {
+                        status,
+                        since,
+                        duration,
+                    });
+                }
+            }
+            (false, Some(since)) => {
+                self.failing_since = None;
+                events.push(HvacDiagnosticEvent::FailureCleared {
+                    status,
+                    duration: time.duration_since(since).unwrap_or_default(),
+                    time,
+                });
+            }
+            (false, None) => {}
+        }
+
+        // A unit failing to cool still counts, as the point is whether the room gets cooler.
+        if status.underlying_status() == HvacStatus::Cooling {
+            let (since, start_temperature, reported) =
+                self.cooling
+                    .get_or_insert((time, inside_temperature, false));
+            let cooled = *start_temperature - inside_temperature;
+            let running = time.duration_since(*since).unwrap_or_default();
+            if !*reported
+                && running >= self.thresholds.cooling_window
+                && cooled < self.thresholds.min_cooling_drop
+            {
+                *reported = true;
+                events.push(HvacDiagnosticEvent::CoolingIneffective {
+                    since: *since,
+                    start_temperature: *start_temperature,
+                    temperature: inside_temperature,
+                });
+            }
+        } else {
+            self.cooling = None;
+        }
+
+        events
+    }
+
+    /// Adds an HVAC snapshot's reading. Snapshots of other devices are ignored.
+    pub fn observe(
+        &mut self,
+        time: SystemTime,
+        snapshot: &DeviceSnapshot,
+    ) -> Vec<HvacDiagnosticEvent> {
+        match snapshot.state() {
+            DeviceState::Hvac {
+                status,
+                inside_temperature,
+                ..
+            } => self.push(time, *status, *inside_temperature),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000 + minutes * 60)
+    }
+
+    #[test]
+    fn reports_a_failure_once_each_way() {
+        let mut monitor = HvacMonitor::default();
+        assert_eq!(monitor.push(at(0), HvacStatus::Idle, 70.0), []);
+        assert_eq!(
+            monitor.push(at(1), HvacStatus::FailIdle, 70.0),
+            [HvacDiagnosticEvent::FailureStarted {
+                status: HvacStatus::FailIdle,
+                time: at(1),
+            }]
+        );
+        assert_eq!(monitor.failing_since(), Some(at(1)));
+        assert_eq!(monitor.push(at(5), HvacStatus::FailOff, 70.0), []);
+        assert_eq!(
+            monitor.push(at(11), HvacStatus::FailOff, 70.0),
+            [HvacDiagnosticEvent::FailurePersisting {
+                status: HvacStatus::FailOff,
+                since: at(1),
+                duration: Duration::from_secs(10 * 60),
+            }]
+        );
+        assert_eq!(monitor.push(at(20), HvacStatus::FailOff, 70.0), []);
+        assert_eq!(
+            monitor.fail_duration(at(21)),
+            Some(Duration::from_secs(20 * 60))
+        );
+        assert_eq!(
+            monitor.push(at(21), HvacStatus::Idle, 70.0),
+            [HvacDiagnosticEvent::FailureCleared {
+                status: HvacStatus::Idle,
+                duration: Duration::from_secs(20 * 60),
+                time: at(21),
+            }]
+        );
+        assert_eq!(monitor.failing_since(), None);
+    }
+
+    #[test]
+    fn reports_cooling_without_effect() {
+        let mut monitor = HvacMonitor::default();
+        assert_eq!(monitor.push(at(0), HvacStatus::Cooling, 80.0), []);
+        assert_eq!(monitor.push(at(29), HvacStatus::Cooling, 79.5), []);
+        // Failing to cool still counts as cooling.
+        let events = monitor.push(at(30), HvacStatus::FailCooling, 79.5);
+        assert_eq!(
+            events[1],
+            HvacDiagnosticEvent::CoolingIneffective {
+                since: at(0),
+                start_temperature: 80.0,
+                temperature: 79.5,
+            }
+        );
+        assert!(matches!(
+            events[0],
+            HvacDiagnosticEvent::FailureStarted { .. }
+        ));
+        assert_eq!(monitor.push(at(40), HvacStatus::Cooling, 79.5).len(), 1);
+        assert_eq!(monitor.push(at(50), HvacStatus::Cooling, 79.5), []);
+    }
+
+    #[test]
+    fn effective_cooling_is_quiet() {
+        let mut monitor = HvacMonitor::default();
+        monitor.push(at(0), HvacStatus::Cooling, 80.0);
+        assert_eq!(monitor.push(at(30), HvacStatus::Cooling, 78.5), []);
+        // A new run starts over.
+        monitor.push(at(31), HvacStatus::Idle, 78.0);
+        monitor.push(at(60), HvacStatus::Cooling, 78.0);
+        assert_eq!(monitor.push(at(80), HvacStatus::Cooling, 78.0), []);
+    }
+
+    #[test]
+    fn observes_hvac_snapshots() {
+        let snapshot = |state: serde_json::Value| -> DeviceSnapshot {
+            serde_json::from_value(serde_json::json!({
+                "label": "Bedroom",
+                "uid": "hvac-1",
+                "device_type": "Hvac",
+                "online": "Online",
+                "state": state,
+            }))
+            .unwrap()
+        };
+        let mut monitor = HvacMonitor::default();
+        let events = monitor.observe(
+            at(0),
+            &snapshot(serde_json::json!({
+                "type": "hvac",
+                "status": "FailHeatPump",
+                "mode": "Heat",
+                "fan": "Auto",
+                "inside_temperature": 60.0,
+                "outside_temperature": 30.0,
+                "high_temperature": 76.0,
+                "low_temperature": 66.0,
+            })),
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].to_string(), "reports Fail Heat Pump");
+        let tank = snapshot(serde_json::json!({"type": "tank", "level": 10}));
+        assert_eq!(monitor.observe(at(20), &tank), []);
+    }
+}
@@ -0,0 +1,428 @@
+use super::{DeviceSnapshot, DeviceState, HvacStatus, HvacStatusConversionError, HVAC};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How runtime is counted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HvacRuntimeConfig {
+    /// Compressor runs shorter than this count as short cycles.
+    pub min_run: Duration,
+    /// Time between two readings longer than this is not counted, ex: while the gateway was unreachable.
+    pub max_gap: Duration,
+    /// The time zone days start at midnight in, ex: "America/New_York".
+    pub timezone: Tz,
+}
+
+impl Default for HvacRuntimeConfig {
+    fn default() -> Self {
+        Self {
+            min_run: Duration::from_secs(5 * 60),
+            max_gap: Duration::from_secs(15 * 60),
+            timezone: Tz::UTC,
+        }
+    }
+}
+
+/// One run of the compressor, from switching on for cooling or the heat pump to switching off.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize)]
+pub struct CompressorCycle {
+    /// When the compressor was first read running.
+    start: SystemTime,
+    /// When it was first read stopped.
+    end: SystemTime,
+    /// True when the run was shorter than `HvacRuntimeConfig::min_run`.
+    short: bool,
+}
+
+impl CompressorCycle {
+    /// Returns how long the compressor ran.
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+}
+
+/// One day of an HVAC's runtime.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize)]
+pub struct RuntimeSummary {
+    /// When the day started.
+    day_start: SystemTime,
+    /// Time spent in each status.
+    time_in_status: HashMap<HvacStatus, Duration>,
+    /// Time covered by readings.
+    observed: Duration,
+    /// Compressor runs started during the day.
+    compressor_cycles: u32,
+    /// Compressor runs started during the day that were short.
+    short_cycles: u32,
+}
+
+impl RuntimeSummary {
+    fn new(day_start: SystemTime) -> Self {
+        Self {
+            day_start,
+            time_in_status: HashMap::new(),
+            observed: Duration::ZERO,
+            compressor_cycles: 0,
+            short_cycles: 0,
+        }
+    }
+
+    /// Returns the time spent in the status.
+    pub fn time_in(&self, status: HvacStatus) -> Duration {
+        self.time_in_status
+            .get(&status)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the time spent cooling, including while reporting FailCooling.
+    pub fn cooling(&self) -> Duration {
+        self.time_where(|status| status == HvacStatus::Cooling)
+    }
+
+    /// Returns the time spent heating with the heat pump.
+    pub fn heat_pump(&self) -> Duration {
+        self.time_where(|status| status == HvacStatus::HeatPump)
+    }
+
+    /// Returns the time spent heating with the electric or gas furnace.
+    pub fn furnace(&self) -> Duration {
+        self.time_where(|status| {
+            matches!(
+                status,
+                HvacStatus::ElectricFurnace | HvacStatus::GasFurnace | HvacStatus::GasOverride
+            )
+        })
+    }
+
+    /// Returns the time spent cooling or heating.
+    pub fn running(&self) -> Duration {
+        self.cooling() + self.heat_pump() + self.furnace()
+    }
+
+    /// Returns the share of the observed time spent cooling or heating, from 0 to 1.
+    pub fn duty_cycle(&self) -> Option<f64> {
+        if self.observed.is_zero() {
+            return None;
+        }
+        Some(self.running().as_secs_f64() / self.observed.as_secs_f64())
+    }
+
+    fn time_where(&self, matches: impl Fn(HvacStatus) -> bool) -> Duration {
+        self.time_in_status
+            .iter()
+            .filter(|(status, _)| matches(status.underlying_status()))
+            .map(|(_, duration)| *duration)
+            .sum()
+    }
+}
+
+/// Adds up one HVAC's time in each status per day from a stream of readings.
+///
+/// The time between two readings is counted against the status of the earlier one, so the latest
+/// status only counts once the next reading is pushed.
+#[derive(Clone, Debug, Default)]
+pub struct HvacRuntime {
+    config: HvacRuntimeConfig,
+    last: Option<(SystemTime, HvacStatus)>,
+    /// While the compressor runs, when it started, or None when it ran through a gap in the readings.
+    compressor: Option<Option<SystemTime>>,
+    days: BTreeMap<NaiveDate, RuntimeSummary>,
+}
+
+impl HvacRuntime {
+    /// Create an accumulator with the config.
+    pub fn new(config: HvacRuntimeConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a reading and returns the compressor cycle it ends, if any. Readings older than the latest one are ignored.
+    pub fn push(&mut self, time: SystemTime, status: HvacStatus) -> Option<CompressorCycle> {
+        if let Some((last_time, last_status)) = self.last {
+            let Ok(gap) = time.duration_since(last_time) else {
+                return None;
+            };
+            if gap <= self.config.max_gap {
+                self.add(last_time, time, last_status);
+            } else if let Some(start) = &mut self.compressor {
+                // A run that spans a gap can't be timed.
+                *start = None;
+            }
+        }
+        self.last = Some((time, status));
+
+        let running = matches!(
+            status.underlying_status(),
+            HvacStatus::Cooling | HvacStatus::HeatPump
+        );
+        match (running, self.compressor) {
+            (true, None) => {
+                self.compressor = Some(Some(time));
+                self.day(time).compressor_cycles += 1;
+                None
+            }
+            (false, Some(start)) => {
+                self.compressor = None;
+                // A run whose start is unknown was counted on its day and can't be judged short.
+                let start = start?;
+                let short = time.duration_since(start).unwrap_or_default() < self.config.min_run;
+                if short {
+                    self.day(start).short_cycles += 1;
+                }
+                Some(CompressorCycle {
+                    start,
+                    end: time,
+                    short,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Adds an HVAC snapshot's reading. Snapshots of other devices are ignored.
+    pub fn observe(
+        &mut self,
+        time: SystemTime,
+        snapshot: &DeviceSnapshot,
+    ) -> Option<CompressorCycle> {
+        match snapshot.state() {
+            DeviceState::Hvac { status, .. } => self.push(time, *status),
+            _ => None,
+        }
+    }
+
+    /// Reads the HVAC's status now and adds it.
+    pub async fn read(
+        &mut self,
+        hvac: &HVAC,
+    ) -> Result<Option<CompressorCycle>, HvacStatusConversionError> {
+        let status = hvac.status().await?;
+        Ok(self.push(SystemTime::now(), status))
+    }
+
+    /// Returns when the running compressor started, or None when it is not running or ran through a gap in
+    /// the readings.
+    pub fn compressor_since(&self) -> Option<SystemTime> {
+        self.compressor.flatten()
+    }
+
+    /// Returns the summary of the day containing the time.
+    pub fn summary(&self, time: SystemTime) -> Option<&RuntimeSummary> {
+        self.days.get(&self.date(time))
+    }
+
+    /// Returns every day's summary, oldest first.
+    pub fn summaries(&self) -> impl Iterator<Item = &RuntimeSummary> {
+        self.days.values()
+    }
+
+    /// Drops the summaries of days that ended before the time.
+    pub fn remove_before(&mut self, time: SystemTime) {
+        let day = self.date(time);
+        self.days.retain(|date, _| *date >= day);
+    }
+
+    /// Counts the time from `start` to `end` against the status, split at day boundaries.
+    fn add(&mut self, start: SystemTime, end: SystemTime, status: HvacStatus) {
+        let mut start = start;
+        while start < end {
+            let until = match self.date(start).succ_opt() {
+                Some(next_day) => end.min(self.day_start(next_day)),
+                None => end,
+            };
+            let duration = until.duration_since(start).unwrap_or_default();
+            let summary = self.day(start);
+            *summary.time_in_status.entry(status).or_default() += duration;
+            summary.observed += duration;
+            start = until;
+        }
+    }
+
+    fn day(&mut self, time: SystemTime) -> &mut RuntimeSummary {
+        let date = self.date(time);
+        let day_start = self.day_start(date);
+        self.days
+            .entry(date)
+            .or_insert_with(|| RuntimeSummary::new(day_start))
+    }
+
+    fn date(&self, time: SystemTime) -> NaiveDate {
+        DateTime::<Utc>::from(time)
+            .with_timezone(&self.config.timezone)
+            .date_naive()
+    }
+
+    /// Returns when the date starts, the first local time on it where midnight is skipped by a daylight
+    /// saving change.
+    fn day_start(&self, date: NaiveDate) -> SystemTime {
+        (0..=180)
+            .find_map(|minutes| {
+                let time = date.and_time(NaiveTime::MIN) + chrono::Duration::minutes(minutes);
+                self.config.timezone.from_local_datetime(&time).earliest()
+            })
+            .map_or(UNIX_EPOCH, SystemTime::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::Denver;
+
+    const MINUTE: u64 = 60;
+
+    fn runtime(timezone: Tz) -> HvacRuntime {
+        HvacRuntime::new(HvacRuntimeConfig {
+            timezone,
+            ..HvacRuntimeConfig::default()
+        })
+    }
+
+    fn denver(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> SystemTime {
+        Denver
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .earliest()
+            .unwrap()
+            .into()
+    }
+
+    fn minutes(time: SystemTime, minutes: u64) -> SystemTime {
+        time + Duration::from_secs(minutes * MINUTE)
+    }
+
+    #[test]
+    fn counts_duty_cycle() {
+        let mut runtime = runtime(Denver);
+        let start = denver(2026, 7, 1, 12, 0);
+        runtime.push(start, HvacStatus::Idle);
+        runtime.push(minutes(start, 10), HvacStatus::Cooling);
+        runtime.push(minutes(start, 20), HvacStatus::FailCooling);
+        runtime.push(minutes(start, 30), HvacStatus::Idle);
+        runtime.push(minutes(start, 40), HvacStatus::ElectricFurnace);
+        runtime.push(minutes(start, 50), HvacStatus::Idle);
+
+        let summary = runtime.summary(start).unwrap();
+        assert_eq!(summary.cooling(), Duration::from_secs(20 * MINUTE));
+        assert_eq!(summary.furnace(), Duration::from_secs(10 * MINUTE));
+        assert_eq!(
+            summary.time_in(HvacStatus::Idle),
+            Duration::from_secs(20 * MINUTE)
+        );
+        assert_eq!(summary.observed, Duration::from_secs(50 * MINUTE));
+        assert_eq!(summary.duty_cycle(), Some(0.6));
+        assert_eq!(*summary.day_start(), denver(2026, 7, 1, 0, 0));
+    }
+
+    #[test]
+    fn skips_gaps() {
+        let mut runtime = runtime(Tz::UTC);
+        let start = denver(2026, 7, 1, 12, 0);
+        runtime.push(start, HvacStatus::Cooling);
+        runtime.push(minutes(start, 60), HvacStatus::Cooling);
+        assert_eq!(runtime.compressor_since(), None);
+        // The run spanned the gap, so it stays one cycle and is not timed.
+        assert_eq!(runtime.push(minutes(start, 63), HvacStatus::Idle), None);
+        let summary = runtime.summary(start).unwrap();
+        assert_eq!(summary.observed, Duration::from_secs(3 * MINUTE));
+        assert_eq!(summary.compressor_cycles, 1);
+        assert_eq!(summary.short_cycles, 0);
+
+        runtime.push(minutes(start, 70), HvacStatus::Cooling);
+        let cycle = runtime.push(minutes(start, 72), HvacStatus::Idle).unwrap();
+        assert!(cycle.short);
+    }
+
+    #[test]
+    fn flags_short_cycles() {
+        let mut runtime = runtime(Denver);
+        let start = denver(2026, 7, 1, 12, 0);
+        assert_eq!(runtime.push(start, HvacStatus::Cooling), None);
+        assert_eq!(runtime.compressor_since(), Some(start));
+        let cycle = runtime.push(minutes(start, 3), HvacStatus::Idle).unwrap();
+        assert!(cycle.short);
+        assert_eq!(cycle.duration(), Duration::from_secs(3 * MINUTE));
+
+        runtime.push(minutes(start, 10), HvacStatus::HeatPump);
+        // Failing to heat still runs the compressor.
+        runtime.push(minutes(start, 13), HvacStatus::FailHeatPump);
+        let cycle = runtime
+            .push(minutes(start, 20), HvacStatus::GasFurnace)
+            .unwrap();
+        assert!(!cycle.short);
+        assert_eq!(runtime.compressor_since(), None);
+
+        let summary = runtime.summary(start).unwrap();
+        assert_eq!(summary.compressor_cycles, 2);
+        assert_eq!(summary.short_cycles, 1);
+    }
+
+    #[test]
+    fn splits_at_local_midnight() {
+        let mut runtime = runtime(Denver);
+        let start = denver(2026, 7, 1, 23, 50);
+        runtime.push(start, HvacStatus::Cooling);
+        runtime.push(minutes(start, 15), HvacStatus::Idle);
+
+        let first = runtime.summary(start).unwrap();
+        let second = runtime.summary(minutes(start, 15)).unwrap();
+        assert_eq!(first.cooling(), Duration::from_secs(10 * MINUTE));
+        assert_eq!(second.cooling(), Duration::from_secs(5 * MINUTE));
+        assert_eq!(*second.day_start(), denver(2026, 7, 2, 0, 0));
+        // The cycle counts on the day it started.
+        assert_eq!((first.compressor_cycles, second.compressor_cycles), (1, 0));
+        assert_eq!(runtime.summaries().count(), 2);
+
+        runtime.remove_before(minutes(start, 15));
+        assert_eq!(runtime.summaries().count(), 1);
+    }
+
+    #[test]
+    fn days_follow_daylight_saving() {
+        let mut runtime = runtime(Denver);
+        // 2026-03-08 is 23 hours long in Denver.
+        let start = denver(2026, 3, 8, 0, 0);
+        let mut time = start;
+        while time <= denver(2026, 3, 9, 0, 10) {
+            runtime.push(time, HvacStatus::Idle);
+            time = minutes(time, 10);
+        }
+        let day = runtime.summary(start).unwrap();
+        assert_eq!(day.observed, Duration::from_secs(23 * 60 * MINUTE));
+        assert_eq!(
+            runtime.summary(denver(2026, 3, 9, 0, 0)).unwrap().observed,
+            Duration::from_secs(10 * MINUTE)
+        );
+    }
+
+    #[test]
+    fn observes_hvac_snapshots() {
+        let mut runtime = runtime(Denver);
+        let snapshot: DeviceSnapshot = serde_json::from_value(serde_json::json!({
+            "label": "Bedroom",
+            "uid": "hvac-1",
+            "device_type": "Hvac",
+            "online": "Online",
+            "state": {
+                "type": "hvac",
+                "status": "Cooling",
+                "mode": "Cool",
+                "fan": "Auto",
+                "inside_temperature": 78.0,
+                "outside_temperature": 95.0,
+                "high_temperature": 76.0,
+                "low_temperature": 66.0,
+            },
+        }))
+        .unwrap();
+        let start = denver(2026, 7, 1, 12, 0);
+        runtime.observe(start, &snapshot);
+        assert_eq!(runtime.compressor_since(), Some(start));
+    }
+}
@@ -0,0 +1,260 @@
+#![allow(non_snake_case)]
+
+use super::{client, Client};
+use derive_getters::Getters;
+use serde::Deserialize;
+use thiserror::Error;
+
+/// An item in the LCI gateway. Items hold the state of a thing's channel.
+/// Use `Thing::item_name` to find the item for a channel.
+#[derive(Getters, Deserialize, Debug)]
+pub struct Item {
+    /// The unique name of the item, ex: "idsmyrv_tank_sensor_0000_01_tank_level".
+    name: String,
+    /// The name shown to people, if one was given.
+    label: Option<String>,
+    /// The kind of item, ex: "Number", "Switch" or "Dimmer".
+    #[serde(rename = "type")]
+    itemType: String,
+    /// The current state as the gateway reports it, ex: "ON" or "42".
+    state: String,
+    /// The REST address of the item.
+    link: Option<String>,
+    /// If the item can be changed through the REST API.
+    editable: Option<bool>,
+    /// The category used for the item's icon.
+    category: Option<String>,
+    /// Tags assigned to the item.
+    #[serde(default)]
+    tags: Vec<String>,
+    /// The groups the item belongs to.
+    #[serde(default)]
+    groupNames: Vec<String>,
+}
+
+impl Client {
+    /// Returns every item in the LCI gateway.
+    pub async fn items(&self) -> Result<Vec<Item>, ItemError> {
+        let txt = self.item_request(self.get("/rest/items"), "").await?;
+        serde_json::from_str(&txt).map_err(ItemError::SerdeJsonConversion)
+    }
+
+    /// Returns the item with the given name, including its state.
+    pub async fn item(&self, name: &str) -> Result<Item, ItemError> {
+        let path = format!("/rest/items/{}", path_segment(name));
+        let txt = self.item_request(self.get(&path), name).await?;
+        serde_json::from_str(&txt).map_err(ItemError::SerdeJsonConversion)
+    }
+
+    /// Returns only the state of the item with the given name, ex: "ON".
+    pub async fn item_state(&self, name: &str) -> Result<String, ItemError> {
+        let path = format!("/rest/items/{}/state", path_segment(name));
+        self.item_request(self.get(&path), name).await
+    }
+
+    /// Sends a command to the item, ex: "ON" or "50". The gateway forwards it to the device.
+    pub async fn send_command(&self, name: &str, command: &str) -> Result<(), ItemError> {
+        let path = format!("/rest/items/{}", path_segment(name));
+        let request = self
+            .post(&path)
+            .header("Content-Type", "text/plain")
+            .body(command.to_string());
+        self.item_request(request, name).await?;
+        Ok(())
+    }
+
+    /// Updates the state the gateway holds for the item without commanding the device.
+    pub async fn update_state(&self, name: &str, state: &str) -> Result<(), ItemError> {
+        let path = format!("/rest/items/{}/state", path_segment(name));
+        let request = self
+            .put(&path)
+            .header("Content-Type", "text/plain")
+            .body(state.to_string());
+        self.item_request(request, name).await?;
+        Ok(())
+    }
+
+    async fn item_request(
+        &self,
+        request: reqwest::RequestBuilder,
+        name: &str,
+    ) -> Result<String, ItemError> {
+        let response = request.send().await.map_err(ItemError::Request)?;
+        let status = response.status();
+        if client::is_unauthorized(status) {
+            return Err(ItemError::Unauthorized);
+        }
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(ItemError::NotFound(name.to_string()));
+        }
+        if !status.is_success() {
+            return Err(ItemError::Status(status.as_u16()));
+        }
+        response.text().await.map_err(ItemError::Text)
+    }
+}
+
+/// Percent-encodes the name for use as one path segment, ex: "Porch/Lights" becomes "Porch%2FLights".
+fn path_segment(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Returned when an item request to the LCI gateway fails.
+#[derive(Debug, Error)]
+pub enum ItemError {
+    /// The LCI gateway could not be reached, see the inner request error.
+    #[error("The LCI gateway could not be reached. {0}")]
+    Request(reqwest::Error),
+    /// Could not get the text from the HTTP response.
+    #[error("The text could not be retrieved. {0}")]
+    Text(reqwest::Error),
+    /// The LCI gateway returned unexpected or invalid JSON.
+    #[error("The JSON response could not be parsed. {0}")]
+    SerdeJsonConversion(serde_json::Error),
+    /// The LCI gateway rejected the credentials, or credentials are required.
+    #[error("The LCI gateway rejected the request as unauthorized.")]
+    Unauthorized,
+    /// There is no item with the given name.
+    #[error("The item '{0}' does not exist.")]
+    NotFound(String),
+    /// The response from the gateway did not indicate success.
+    #[error("The http response code from the gateway, {0}, did not indicate success.")]
+    Status(u16),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Answers every request with the status and body, and records each as "METHOD path body".
+    async fn gateway(status: u16, body: &'static str, requests: Arc<Mutex<Vec<String>>>) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(socket);
+                let mut request = String::new();
+                reader.read_line(&mut request).await.unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut content = vec![0; length];
+                reader.read_exact(&mut content).await.unwrap();
+                let mut parts = request.split_whitespace();
+                requests.lock().unwrap().push(
+                    format!(
+                        "{} {} {}",
+                        parts.next().unwrap(),
+                        parts.next().unwrap(),
+                        String::from_utf8_lossy(&content)
+                    )
+                    .trim()
+                    .to_string(),
+                );
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                reader
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    #[tokio::test]
+    async fn reads_items() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let client = gateway(
+            200,
+            r#"{"name": "FreshWaterLevel", "type": "Number", "state": "42", "tags": ["Tank"]}"#,
+            requests.clone(),
+        )
+        .await;
+        let item = client.item("FreshWaterLevel").await.unwrap();
+        assert_eq!(item.name(), "FreshWaterLevel");
+        assert_eq!(item.itemType(), "Number");
+        assert_eq!(item.state(), "42");
+        assert_eq!(item.tags(), &["Tank"]);
+        assert_eq!(*item.label(), None);
+        assert!(item.groupNames().is_empty());
+
+        let client = gateway(200, "ON", requests.clone()).await;
+        assert_eq!(client.item_state("PorchLight").await.unwrap(), "ON");
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                "GET /rest/items/FreshWaterLevel",
+                "GET /rest/items/PorchLight/state",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_commands_and_updates() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let client = gateway(202, "", requests.clone()).await;
+        client.send_command("Porch Light", "ON").await.unwrap();
+        client.update_state("Porch/Light", "50").await.unwrap();
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                "POST /rest/items/Porch%20Light ON",
+                "PUT /rest/items/Porch%2FLight/state 50",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_error_statuses() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let missing = gateway(404, "", requests.clone()).await;
+        assert!(matches!(
+            missing.item("Awning?").await,
+            Err(ItemError::NotFound(name)) if name == "Awning?"
+        ));
+        assert_eq!(requests.lock().unwrap()[0], "GET /rest/items/Awning%3F");
+
+        let unauthorized = gateway(401, "", requests.clone()).await;
+        assert!(matches!(
+            unauthorized.send_command("Pump", "OFF").await,
+            Err(ItemError::Unauthorized)
+        ));
+
+        let failing = gateway(500, "", requests.clone()).await;
+        assert!(matches!(
+            failing.item_state("Pump").await,
+            Err(ItemError::Status(500))
+        ));
+
+        let invalid = gateway(200, "not json", requests).await;
+        assert!(matches!(
+            invalid.items().await,
+            Err(ItemError::SerdeJsonConversion(_))
+        ));
+    }
+}
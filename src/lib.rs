@@ -1,25 +1,78 @@
+#[cfg(feature = "alerts")]
+pub mod alert;
+#[cfg(feature = "api")]
+pub mod api;
+mod client;
 mod common;
+mod device;
 mod dimmer;
+#[cfg(feature = "discovery")]
+pub mod discovery;
+mod forecast;
 mod generator;
+mod group;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "mqtt")]
+pub mod home_assistant;
 mod hvac;
+mod hvac_monitor;
+mod hvac_runtime;
+mod item;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+mod predicate;
+#[cfg(feature = "rules")]
+pub mod rules;
+mod scene;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
 mod sse;
 mod switch;
 mod tank;
+mod tank_event;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+mod watch;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
-pub use common::{OnlineState, OnlineStateConversionError, Percentage, SetError};
+pub use client::{Authentication, Client, DEFAULT_ADDRESS};
+pub use common::{
+    GetFailure, OnlineState, OnlineStateConversionError, Percentage, PercentageConversionError,
+    SetError,
+};
+pub use device::{
+    get_devices, Device, DeviceCommand, DeviceCommandError, DeviceSnapshot, DeviceState,
+    DeviceStateError,
+};
 pub use dimmer::{Dimmer, DimmerBrightnessError, DimmerError, SetBrightnessError};
+pub use forecast::{TankEstimator, TankForecast};
 pub use generator::{Generator, GeneratorError, GeneratorState, GeneratorStateConversionError};
+pub use group::{Group, GroupError, GroupResult, GroupState};
 pub use hvac::{
-    HvacError, HvacFanMode, HvacFanModeConversionError, HvacHighTemperatureFailure,
+    HeatSource, HvacError, HvacFanMode, HvacFanModeConversionError, HvacHighTemperatureFailure,
     HvacInsideTemperatureFailure, HvacLowTemperatureFailure, HvacMode, HvacModeConversionError,
     HvacOutsideTemperatureFailure, HvacStatus, HvacStatusConversionError, HvacStatusFailure, HVAC,
 };
-pub use sse::{Configuration, DeviceType, Thing};
+pub use hvac_monitor::{HvacDiagnosticEvent, HvacMonitor, HvacMonitorThresholds};
+pub use hvac_runtime::{CompressorCycle, HvacRuntime, HvacRuntimeConfig, RuntimeSummary};
+pub use item::{Item, ItemError};
+pub use predicate::Predicate;
+pub use scene::{Scene, SceneEntry, SceneError, SceneResult};
+pub use sse::{Channel, Configuration, DeviceType, StatusInfo, Thing, ThingStatus};
 pub use switch::{
     Switch, SwitchError, SwitchRelayCurrentError, SwitchState, SwitchStateConversionError,
 };
-pub use tank::{Tank, TankError, TankLevelError};
+pub use tank::{
+    Calibration, CalibrationError, Tank, TankConfig, TankConfigError, TankConfigs, TankError,
+    TankKind, TankKindConversionError, TankLevelError, TankVolumeError, Volume, VolumeUnit,
+};
+pub use tank_event::{TankEvent, TankEventDetector, TankEventThresholds};
 use thiserror::Error;
+pub use watch::{DeviceChange, Watcher};
 
 /// Used when the list of things can not be fetched.
 #[derive(Debug, Error)]
@@ -33,25 +86,16 @@ pub enum ThingError {
     /// The LCI gateway returned unexpected or invalid JSON.
     #[error("The JSON response could not be parsed. {0}")]
     ConvertingJson(serde_json::Error),
+    /// The LCI gateway rejected the credentials, or credentials are required.
+    #[error("The LCI gateway rejected the request as unauthorized.")]
+    Unauthorized,
+    /// The response from the gateway did not indicate success.
+    #[error("The http response code from the gateway, {0}, did not indicate success.")]
+    Status(u16),
 }
 
-/// Returns the "things" availabe in the LCI Gateway.
+/// Returns the "things" availabe in the LCI Gateway at the default address.
+/// Use a `Client` to connect to a different address or to send credentials.
 pub async fn get_things() -> Result<Vec<Thing>, ThingError> {
-    log::trace!("Fetching things");
-    let body = reqwest::get("http://192.168.1.4:8080/rest/things/")
-        .await
-        .map_err(|err| {
-            log::error!("Failed to request {:?}", err);
-            ThingError::Getting(err)
-        })?
-        .text()
-        .await
-        .map_err(|err| {
-            log::error!("Failed to pull text {:?}", err);
-            ThingError::Text(err)
-        })?;
-    log::trace!("Converting with serde.");
-    let things: Vec<Thing> = serde_json::from_str(&body).map_err(ThingError::ConvertingJson)?;
-    log::trace!("returning things");
-    Ok(things)
+    Client::default().get_things().await
 }
@@ -0,0 +1,361 @@
+use super::{Client, DeviceState, DeviceType, GeneratorState, OnlineState, SwitchState};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+
+/// Reads every device on each scrape and renders the readings in the Prometheus text format.
+pub struct Exporter {
+    client: Client,
+    scrapes: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl Exporter {
+    /// Create an exporter for the gateway behind the client.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            scrapes: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// Reads every device and returns the metrics page.
+    /// `lci_device_online` is read apart from the other readings, so a device whose state can't be read is still
+    /// listed. Failed reads are counted in `lci_gateway_scrape_errors_total`.
+    pub async fn render(&self) -> String {
+        let start = Instant::now();
+        self.scrapes.fetch_add(1, Ordering::Relaxed);
+
+        let devices = match self.client.devices().await {
+            Ok(devices) => devices,
+            Err(err) => {
+                log::error!("Could not fetch devices: {}", err);
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                Vec::new()
+            }
+        };
+        let reads = futures::future::join_all(
+            devices
+                .iter()
+                .map(|device| futures::future::join(device.online(), device.state())),
+        )
+        .await;
+
+        let mut page = Page::default();
+        for (device, (online, state)) in devices.iter().zip(reads) {
+            let labels = labels(&device.label(), &device.uid());
+            let online = match online {
+                Ok(online) => online == OnlineState::Online,
+                Err(err) => {
+                    log::warn!("Could not read if {} is online: {}", device.label(), err);
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                    false
+                }
+            };
+            page.online(&labels, device.device_type(), online);
+            match state {
+                Ok(state) => page.state(&labels, &state),
+                Err(err) => {
+                    log::warn!("Could not read {}: {}", device.label(), err);
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        page.gauge(
+            "lci_gateway_scrape_duration_seconds",
+            "How long reading every device from the gateway took.",
+            "",
+            start.elapsed().as_secs_f64(),
+        );
+        page.counter(
+            "lci_gateway_scrapes_total",
+            "Scrapes of the gateway.",
+            self.scrapes.load(Ordering::Relaxed),
+        );
+        page.counter(
+            "lci_gateway_scrape_errors_total",
+            "Failed requests to the gateway while scraping.",
+            self.errors.load(Ordering::Relaxed),
+        );
+        page.render()
+    }
+}
+
+/// Serves the exporter's metrics on `/metrics` at the given address, ex: "0.0.0.0:9898".
+pub async fn serve(exporter: Exporter, address: &str) -> Result<(), MetricsError> {
+    let app = axum::Router::new()
+        .route("/metrics", axum::routing::get(metrics))
+        .with_state(Arc::new(exporter));
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(MetricsError::Bind)?;
+    log::info!("Serving metrics on http://{}/metrics", address);
+    axum::serve(listener, app)
+        .await
+        .map_err(MetricsError::Serve)
+}
+
+async fn metrics(
+    axum::extract::State(exporter): axum::extract::State<Arc<Exporter>>,
+) -> impl axum::response::IntoResponse {
+    (
+        [("Content-Type", "text/plain; version=0.0.4")],
+        exporter.render().await,
+    )
+}
+
+/// Collects metrics grouped by name, as the text format requires every series of a metric to be together.
+#[derive(Default)]
+struct Page {
+    families: Vec<Family>,
+}
+
+struct Family {
+    name: &'static str,
+    help: String,
+    kind: &'static str,
+    lines: Vec<String>,
+}
+
+impl Page {
+    /// Adds the device's online gauge, 0 when it is offline or could not be read.
+    fn online(&mut self, labels: &str, device_type: DeviceType, online: bool) {
+        self.gauge(
+            "lci_device_online",
+            "1 when the device is online.",
+            &format!("{},type=\"{}\"", labels, device_type),
+            online as u8 as f64,
+        );
+    }
+
+    /// Adds the device's readings.
+    fn state(&mut self, labels: &str, state: &DeviceState) {
+        match state {
+            DeviceState::Tank { level } => self.gauge(
+                "lci_tank_level_percent",
+                "The tank level.",
+                labels,
+                level.value() as f64,
+            ),
+            DeviceState::Dimmer { brightness } => self.gauge(
+                "lci_dimmer_brightness_percent",
+                "The dimmer brightness, 0 when off.",
+                labels,
+                brightness.value() as f64,
+            ),
+            DeviceState::Switch {
+                state,
+                fault,
+                relay_current,
+            } => {
+                self.gauge(
+                    "lci_switch_on",
+                    "1 when the switch is on.",
+                    labels,
+                    (*state == SwitchState::On) as u8 as f64,
+                );
+                self.gauge(
+                    "lci_switch_fault",
+                    "1 when the switch reports a fault.",
+                    labels,
+                    (*fault == SwitchState::On) as u8 as f64,
+                );
+                if let Ok(current) = relay_current.parse::<f64>() {
+                    self.gauge(
+                        "lci_switch_relay_current_amperes",
+                        "The current used by the relay.",
+                        labels,
+                        current,
+                    );
+                }
+            }
+            DeviceState::Generator { state } => {
+                for candidate in [
+                    GeneratorState::Off,
+                    GeneratorState::Priming,
+                    GeneratorState::Starting,
+                    GeneratorState::Running,
+                ] {
+                    self.gauge(
+                        "lci_generator_state",
+                        "1 for the generator's current state.",
+                        &format!("{},state=\"{}\"", labels, candidate),
+                        (*state == candidate) as u8 as f64,
+                    );
+                }
+            }
+            DeviceState::Hvac {
+                status,
+                inside_temperature,
+                outside_temperature,
+                high_temperature,
+                low_temperature,
+                ..
+            } => {
+                self.gauge(
+                    "lci_hvac_inside_temperature",
+                    "The temperature inside the room.",
+                    labels,
+                    *inside_temperature as f64,
+                );
+                self.gauge(
+                    "lci_hvac_outside_temperature",
+                    "The outside temperature reported by the HVAC.",
+                    labels,
+                    *outside_temperature as f64,
+                );
+                self.gauge(
+                    "lci_hvac_high_temperature",
+                    "The setpoint the HVAC starts cooling at.",
+                    labels,
+                    *high_temperature as f64,
+                );
+                self.gauge(
+                    "lci_hvac_low_temperature",
+                    "The setpoint the HVAC starts heating at.",
+                    labels,
+                    *low_temperature as f64,
+                );
+                self.gauge(
+                    "lci_hvac_status",
+                    "1 for the HVAC's current status.",
+                    &format!("{},status=\"{}\"", labels, status),
+                    1.0,
+                );
+                self.gauge(
+                    "lci_hvac_failure",
+                    "1 when the HVAC reports a fail status.",
+                    labels,
+                    status.is_failure() as u8 as f64,
+                );
+            }
+        }
+    }
+
+    fn gauge(&mut self, name: &'static str, help: &str, labels: &str, value: f64) {
+        let line = if labels.is_empty() {
+            format!("{} {}", name, value)
+        } else {
+            format!("{}{{{}}} {}", name, labels, value)
+        };
+        self.family(name, help, "gauge").lines.push(line);
+    }
+
+    fn counter(&mut self, name: &'static str, help: &str, value: u64) {
+        let line = format!("{} {}", name, value);
+        self.family(name, help, "counter").lines.push(line);
+    }
+
+    fn family(&mut self, name: &'static str, help: &str, kind: &'static str) -> &mut Family {
+        let index = match self.families.iter().position(|family| family.name == name) {
+            Some(index) => index,
+            None => {
+                self.families.push(Family {
+                    name,
+                    help: help.to_string(),
+                    kind,
+                    lines: Vec::new(),
+                });
+                self.families.len() - 1
+            }
+        };
+        &mut self.families[index]
+    }
+
+    fn render(&self) -> String {
+        let mut text = String::new();
+        for family in &self.families {
+            let _ = writeln!(text, "# HELP {} {}", family.name, family.help);
+            let _ = writeln!(text, "# TYPE {} {}", family.name, family.kind);
+            for line in &family.lines {
+                let _ = writeln!(text, "{}", line);
+            }
+        }
+        text
+    }
+}
+
+fn labels(label: &str, uid: &str) -> String {
+    format!("label=\"{}\",uid=\"{}\"", escape(label), escape(uid))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Returned when the metrics server stops.
+#[derive(Debug, Error)]
+pub enum MetricsError {
+    /// The address could not be listened on.
+    #[error("The metrics address could not be bound. {0}")]
+    Bind(std::io::Error),
+    /// The server failed while running.
+    #[error("The metrics server failed. {0}")]
+    Serve(std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_families_together() {
+        let mut page = Page::default();
+        let fresh = labels("Fresh \"Water\"", "tank-1");
+        page.online(&fresh, DeviceType::Tank, true);
+        page.state(
+            &fresh,
+            &serde_json::from_value(serde_json::json!({"type": "tank", "level": 40})).unwrap(),
+        );
+        let black = labels("Black Tank", "tank-2");
+        page.online(&black, DeviceType::Tank, false);
+        page.counter("lci_gateway_scrapes_total", "Scrapes of the gateway.", 3);
+
+        assert_eq!(
+            page.render(),
+            "# HELP lci_device_online 1 when the device is online.\n\
+             # TYPE lci_device_online gauge\n\
+             lci_device_online{label=\"Fresh \\\"Water\\\"\",uid=\"tank-1\",type=\"Tank\"} 1\n\
+             lci_device_online{label=\"Black Tank\",uid=\"tank-2\",type=\"Tank\"} 0\n\
+             # HELP lci_tank_level_percent The tank level.\n\
+             # TYPE lci_tank_level_percent gauge\n\
+             lci_tank_level_percent{label=\"Fresh \\\"Water\\\"\",uid=\"tank-1\"} 40\n\
+             # HELP lci_gateway_scrapes_total Scrapes of the gateway.\n\
+             # TYPE lci_gateway_scrapes_total counter\n\
+             lci_gateway_scrapes_total 3\n"
+        );
+    }
+
+    #[test]
+    fn renders_one_series_per_generator_state() {
+        let mut page = Page::default();
+        page.state(
+            &labels("Generator", "gen-1"),
+            &serde_json::from_value(serde_json::json!({"type": "generator", "state": "Running"}))
+                .unwrap(),
+        );
+        let text = page.render();
+        assert!(text.contains(
+            "lci_generator_state{label=\"Generator\",uid=\"gen-1\",state=\"Running\"} 1\n"
+        ));
+        assert!(text
+            .contains("lci_generator_state{label=\"Generator\",uid=\"gen-1\",state=\"Off\"} 0\n"));
+    }
+
+    #[tokio::test]
+    async fn counts_an_unreachable_gateway() {
+        let exporter = Exporter::new(
+            Client::new("http://127.0.0.1:1").with_timeout(std::time::Duration::from_secs(1)),
+        );
+        let text = exporter.render().await;
+        assert!(!text.contains("lci_device_online"));
+        assert!(text.contains("lci_gateway_scrapes_total 1\n"));
+        assert!(text.contains("lci_gateway_scrape_errors_total 1\n"));
+    }
+}
@@ -0,0 +1,645 @@
+use super::home_assistant::{self, HomeAssistantConfig};
+use super::{
+    Client, Device, DeviceCommand, DeviceSnapshot, DeviceState, DeviceType, HvacFanMode, HvacMode,
+    ThingError,
+};
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+
+/// The payload published to the availability topic while the bridge is connected.
+pub const PAYLOAD_ONLINE: &str = "online";
+/// The payload the broker publishes to the availability topic when the bridge drops off.
+pub const PAYLOAD_OFFLINE: &str = "offline";
+
+/// How to reach the broker and where to publish.
+#[derive(Clone, Debug)]
+pub struct BridgeConfig {
+    /// The MQTT broker's host name or IP.
+    pub host: String,
+    /// The MQTT broker's port.
+    pub port: u16,
+    /// The client ID to connect to the broker as.
+    pub client_id: String,
+    /// The user to connect to the broker as, if the broker requires one.
+    pub username: Option<String>,
+    /// The password to connect to the broker with.
+    pub password: Option<String>,
+    /// Where topics are published and subscribed, see `Topics`.
+    pub topics: Topics,
+    /// If state messages are retained by the broker.
+    pub retain: bool,
+    /// How often every device is read and published.
+    pub poll_interval: Duration,
+    /// Publishes Home Assistant discovery configs on connect when set.
+    pub home_assistant: Option<HomeAssistantConfig>,
+}
+
+/// The topic layout. Templates can use `{base}`, `{type}`, `{id}` and `{field}`.
+/// `{type}` is ex: "switch" or "hvac", `{id}` is the device's `object_id`.
+#[derive(Clone, Debug)]
+pub struct Topics {
+    /// The prefix for every topic, ex: "lci".
+    pub base: String,
+    /// The topic a single field of a device is published to.
+    pub state: String,
+    /// The topic a single field of a device accepts commands on.
+    pub command: String,
+    /// The topic the whole device snapshot is published to as JSON.
+    pub snapshot: String,
+    /// The topic the bridge's "online"/"offline" status is published to.
+    pub availability: String,
+}
+
+impl Default for Topics {
+    fn default() -> Self {
+        Self {
+            base: "lci".to_string(),
+            state: "{base}/{type}/{id}/{field}".to_string(),
+            command: "{base}/{type}/{id}/{field}/set".to_string(),
+            snapshot: "{base}/{type}/{id}".to_string(),
+            availability: "{base}/status".to_string(),
+        }
+    }
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "lci-gateway".to_string(),
+            username: None,
+            password: None,
+            topics: Topics::default(),
+            retain: true,
+            poll_interval: Duration::from_secs(30),
+            home_assistant: None,
+        }
+    }
+}
+
+impl Topics {
+    /// Returns the topic the given field of the device is published to.
+    pub fn state(&self, device: &Device, field: &str) -> String {
+        self.fill(&self.state, device, field)
+    }
+
+    /// Returns the topic the given field of the device accepts commands on.
+    pub fn command(&self, device: &Device, field: &str) -> String {
+        self.fill(&self.command, device, field)
+    }
+
+    /// Returns the topic the device's JSON snapshot is published to.
+    pub fn snapshot(&self, device: &Device) -> String {
+        self.fill(&self.snapshot, device, "")
+    }
+
+    /// Returns the topic the bridge's status is published to.
+    pub fn availability(&self) -> String {
+        self.availability.replace("{base}", &self.base)
+    }
+
+    fn fill(&self, template: &str, device: &Device, field: &str) -> String {
+        template
+            .replace("{base}", &self.base)
+            .replace("{type}", type_segment(device.device_type()))
+            .replace("{id}", &device.object_id())
+            .replace("{field}", field)
+    }
+}
+
+/// Publishes device state to MQTT and carries out commands received from it.
+pub struct Bridge {
+    config: BridgeConfig,
+    client: Client,
+}
+
+impl Bridge {
+    /// Create a bridge between the gateway behind the client and the broker in the config.
+    pub fn new(config: BridgeConfig, client: Client) -> Self {
+        Self { config, client }
+    }
+
+    /// Connects to the broker and runs until the connection can not be kept up.
+    /// Reconnects are handled by the MQTT client; subscriptions and retained state are renewed on each.
+    pub async fn run(self) -> Result<(), BridgeError> {
+        let mut devices = self.client.devices().await?;
+        log::info!("Bridging {} devices", devices.len());
+
+        let topics = &self.config.topics;
+        let availability = topics.availability();
+        let mut options = MqttOptions::new(
+            self.config.client_id.clone(),
+            self.config.host.clone(),
+            self.config.port,
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            availability.clone(),
+            PAYLOAD_OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &self.config.username {
+            options.set_credentials(
+                username.clone(),
+                self.config.password.clone().unwrap_or_default(),
+            );
+        }
+
+        let (mqtt, mut eventloop) = AsyncClient::new(options, 64);
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let events = tokio::spawn(async move {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        if sender.send(Incoming::Connected).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                        if sender
+                            .send(Incoming::Message(publish.topic, payload))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        log::warn!("MQTT connection error {:?}", err);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        let commands = command_topics(topics, &devices);
+        let mut published: HashMap<String, String> = HashMap::new();
+        let mut interval = tokio::time::interval(self.config.poll_interval);
+        let result = loop {
+            tokio::select! {
+                incoming = receiver.recv() => match incoming {
+                    Some(Incoming::Connected) => {
+                        log::info!("Connected to {}:{}", self.config.host, self.config.port);
+                        published.clear();
+                        mqtt.publish(availability.clone(), QoS::AtLeastOnce, true, PAYLOAD_ONLINE)
+                            .await
+                            .map_err(BridgeError::Client)?;
+                        self.publish_discovery(&mqtt, &devices).await?;
+                        for topic in commands.keys() {
+                            mqtt.subscribe(topic.clone(), QoS::AtLeastOnce)
+                                .await
+                                .map_err(BridgeError::Client)?;
+                        }
+                        interval.reset_immediately();
+                    }
+                    Some(Incoming::Message(topic, payload)) => {
+                        let Some((index, field)) = commands.get(&topic) else {
+                            continue;
+                        };
+                        let device = &mut devices[*index];
+                        match parse_command(device.device_type(), field, &payload) {
+                            Some(command) => {
+                                log::info!("{}: {}", device.label(), command);
+                                if let Err(err) = device.execute(&command).await {
+                                    log::error!("{} failed to {}: {}", device.label(), command, err);
+                                }
+                                self.publish_device(&mqtt, device, &mut published).await?;
+                            }
+                            None => log::warn!("Ignoring '{}' on {}", payload, topic),
+                        }
+                    }
+                    None => break Err(BridgeError::Disconnected),
+                },
+                _ = interval.tick() => {
+                    for device in devices.iter() {
+                        self.publish_device(&mqtt, device, &mut published).await?;
+                    }
+                }
+            }
+        };
+
+        events.abort();
+        result
+    }
+
+    async fn publish_discovery(
+        &self,
+        mqtt: &AsyncClient,
+        devices: &[Device],
+    ) -> Result<(), BridgeError> {
+        let Some(home_assistant) = &self.config.home_assistant else {
+            return Ok(());
+        };
+        for device in devices {
+            for message in
+                home_assistant::discovery_messages(home_assistant, &self.config.topics, device)
+            {
+                mqtt.publish(
+                    message.topic().clone(),
+                    QoS::AtLeastOnce,
+                    true,
+                    message.payload().to_string(),
+                )
+                .await
+                .map_err(BridgeError::Client)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn publish_device(
+        &self,
+        mqtt: &AsyncClient,
+        device: &Device,
+        published: &mut HashMap<String, String>,
+    ) -> Result<(), BridgeError> {
+        let topics = &self.config.topics;
+        let snapshot = match device.snapshot().await {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                log::warn!("Could not read {}: {}", device.label(), err);
+                return Ok(());
+            }
+        };
+
+        let mut messages = vec![(
+            topics.snapshot(device),
+            serde_json::to_string(&snapshot).map_err(BridgeError::Json)?,
+        )];
+        for (field, value) in state_fields(&snapshot) {
+            messages.push((topics.state(device, field), value));
+        }
+
+        for (topic, payload) in messages {
+            if published.get(&topic) == Some(&payload) {
+                continue;
+            }
+            mqtt.publish(
+                topic.clone(),
+                QoS::AtLeastOnce,
+                self.config.retain,
+                payload.clone(),
+            )
+            .await
+            .map_err(BridgeError::Client)?;
+            published.insert(topic, payload);
+        }
+        Ok(())
+    }
+}
+
+enum Incoming {
+    Connected,
+    Message(String, String),
+}
+
+/// Returns the fields published for a snapshot as (field, payload) pairs.
+/// Enum values use their `Display` form, ex: "On" or "Heat Pump", temperatures and percentages are plain numbers.
+pub fn state_fields(snapshot: &DeviceSnapshot) -> Vec<(&'static str, String)> {
+    let mut fields = vec![("online", snapshot.online().to_string())];
+    match snapshot.state() {
+        DeviceState::Tank { level } => fields.push(("level", level.value().to_string())),
+        DeviceState::Dimmer { brightness } => {
+            let state = if brightness.value() > 0 { "On" } else { "Off" };
+            fields.push(("state", state.to_string()));
+            fields.push(("brightness", brightness.value().to_string()));
+        }
+        DeviceState::Switch {
+            state,
+            fault,
+            relay_current,
+        } => {
+            fields.push(("state", state.to_string()));
+            fields.push(("fault", fault.to_string()));
+            fields.push(("relay_current", relay_current.clone()));
+        }
+        DeviceState::Generator { state } => fields.push(("state", state.to_string())),
+        DeviceState::Hvac {
+            status,
+            mode,
+            fan,
+            inside_temperature,
+            outside_temperature,
+            high_temperature,
+            low_temperature,
+        } => {
+            fields.push(("status", status.to_string()));
+            fields.push(("mode", mode.to_string()));
+            fields.push(("fan", fan.to_string()));
+            fields.push(("inside_temperature", inside_temperature.to_string()));
+            fields.push(("outside_temperature", outside_temperature.to_string()));
+            fields.push(("high_temperature", high_temperature.to_string()));
+            fields.push(("low_temperature", low_temperature.to_string()));
+        }
+    }
+    fields
+}
+
+/// Returns the fields of a device type that accept commands.
+pub fn command_fields(device_type: DeviceType) -> &'static [&'static str] {
+    match device_type {
+        DeviceType::Dimmer => &["state", "brightness"],
+        DeviceType::Switch | DeviceType::Generator => &["state"],
+        DeviceType::Hvac => &["mode", "fan", "high_temperature", "low_temperature"],
+        _ => &[],
+    }
+}
+
+/// Maps a payload received on a command topic to a device command.
+/// Accepts "ON"/"OFF" for states, 0-100 for brightness, mode and fan names in any case
+/// ("heat_cool" included) and whole degrees for temperatures.
+pub fn parse_command(device_type: DeviceType, field: &str, payload: &str) -> Option<DeviceCommand> {
+    let payload = payload.trim();
+    match (device_type, field) {
+        (DeviceType::Dimmer | DeviceType::Switch | DeviceType::Generator, "state") => {
+            match payload.to_uppercase().as_str() {
+                "ON" => Some(DeviceCommand::On),
+                "OFF" => Some(DeviceCommand::Off),
+                _ => None,
+            }
+        }
+        (DeviceType::Dimmer, "brightness") => payload
+            .parse::<f32>()
+            .ok()
+            .filter(|brightness| (0.0..=100.0).contains(brightness))
+            .map(|brightness| DeviceCommand::SetBrightness(brightness.round() as u8)),
+        (DeviceType::Hvac, "mode") => HvacMode::from_string(payload.replace('_', ""))
+            .ok()
+            .map(DeviceCommand::SetMode),
+        (DeviceType::Hvac, "fan") => HvacFanMode::from_string(payload.to_string())
+            .ok()
+            .map(DeviceCommand::SetFan),
+        (DeviceType::Hvac, "high_temperature") => payload
+            .parse::<f32>()
+            .ok()
+            .map(|temp| DeviceCommand::SetHighTemperature(temp.round() as isize)),
+        (DeviceType::Hvac, "low_temperature") => payload
+            .parse::<f32>()
+            .ok()
+            .map(|temp| DeviceCommand::SetLowTemperature(temp.round() as isize)),
+        _ => None,
+    }
+}
+
+fn command_topics(topics: &Topics, devices: &[Device]) -> HashMap<String, (usize, &'static str)> {
+    let mut commands = HashMap::new();
+    for (index, device) in devices.iter().enumerate() {
+        for field in command_fields(device.device_type()) {
+            commands.insert(topics.command(device, field), (index, *field));
+        }
+    }
+    commands
+}
+
+fn type_segment(device_type: DeviceType) -> &'static str {
+    match device_type {
+        DeviceType::Gateway => "gateway",
+        DeviceType::Tank => "tank",
+        DeviceType::RgbLights => "rgb_lights",
+        DeviceType::Hvac => "hvac",
+        DeviceType::Dimmer => "dimmer",
+        DeviceType::Generator => "generator",
+        DeviceType::Switch => "switch",
+    }
+}
+
+/// Returned when the bridge stops.
+#[derive(Debug, Error)]
+pub enum BridgeError {
+    /// The devices could not be fetched from the LCI gateway.
+    #[error("The devices could not be fetched. {0}")]
+    Things(ThingError),
+    /// A request could not be handed to the MQTT client.
+    #[error("The MQTT client failed. {0}")]
+    Client(rumqttc::ClientError),
+    /// A snapshot could not be converted to JSON.
+    #[error("The snapshot could not be converted to JSON. {0}")]
+    Json(serde_json::Error),
+    /// The connection to the broker was dropped for good.
+    #[error("The connection to the MQTT broker was lost.")]
+    Disconnected,
+}
+
+impl From<ThingError> for BridgeError {
+    fn from(error: ThingError) -> Self {
+        Self::Things(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, Thing};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Accepts every command and records it as "path body".
+    async fn gateway(commands: Arc<Mutex<Vec<String>>>) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(socket);
+                let mut request = String::new();
+                reader.read_line(&mut request).await.unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                commands.lock().unwrap().push(format!(
+                    "{} {}",
+                    request.split_whitespace().nth(1).unwrap(),
+                    String::from_utf8_lossy(&body)
+                ));
+                reader
+                    .get_mut()
+                    .write_all(
+                        b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    fn switch() -> Device {
+        let thing = serde_json::from_value(serde_json::json!({
+            "label": "Water Pump",
+            "UID": "idsmyrv:switch:0000-01",
+            "configuration": {"deviceType": 30},
+        }))
+        .unwrap();
+        Device::from_thing(thing).unwrap()
+    }
+
+    fn snapshot(state: serde_json::Value) -> DeviceSnapshot {
+        serde_json::from_value(serde_json::json!({
+            "label": "Device",
+            "uid": "device-1",
+            "device_type": "Hvac",
+            "online": "Online",
+            "state": state,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fills_default_topics() {
+        let topics = Topics::default();
+        let pump = switch();
+        assert_eq!(
+            topics.state(&pump, "state"),
+            "lci/switch/idsmyrv_switch_0000_01/state"
+        );
+        assert_eq!(
+            topics.command(&pump, "state"),
+            "lci/switch/idsmyrv_switch_0000_01/state/set"
+        );
+        assert_eq!(topics.snapshot(&pump), "lci/switch/idsmyrv_switch_0000_01");
+        assert_eq!(topics.availability(), "lci/status");
+    }
+
+    #[test]
+    fn fills_custom_topics() {
+        let topics = Topics {
+            base: "coach".to_string(),
+            state: "{base}/{id}/{field}/state".to_string(),
+            command: "{base}/{id}/{field}/command".to_string(),
+            snapshot: "{base}/{type}/{id}/json".to_string(),
+            availability: "{base}/bridge".to_string(),
+        };
+        let pump = switch();
+        assert_eq!(
+            topics.state(&pump, "fault"),
+            "coach/idsmyrv_switch_0000_01/fault/state"
+        );
+        assert_eq!(
+            command_topics(&topics, &[pump]),
+            HashMap::from([(
+                "coach/idsmyrv_switch_0000_01/state/command".to_string(),
+                (0, "state")
+            )])
+        );
+        assert_eq!(topics.availability(), "coach/bridge");
+    }
+
+    #[test]
+    fn parses_commands() {
+        let parse = parse_command;
+        assert_eq!(
+            parse(DeviceType::Switch, "state", "on"),
+            Some(DeviceCommand::On)
+        );
+        assert_eq!(
+            parse(DeviceType::Generator, "state", " OFF "),
+            Some(DeviceCommand::Off)
+        );
+        assert_eq!(parse(DeviceType::Switch, "state", "toggle"), None);
+        assert_eq!(
+            parse(DeviceType::Dimmer, "brightness", "42.6"),
+            Some(DeviceCommand::SetBrightness(43))
+        );
+        assert_eq!(parse(DeviceType::Dimmer, "brightness", "101"), None);
+        assert_eq!(parse(DeviceType::Switch, "brightness", "50"), None);
+        assert_eq!(
+            parse(DeviceType::Hvac, "mode", "heat_cool"),
+            Some(DeviceCommand::SetMode(HvacMode::HeatCool))
+        );
+        assert_eq!(
+            parse(DeviceType::Hvac, "fan", "high"),
+            Some(DeviceCommand::SetFan(HvacFanMode::High))
+        );
+        assert_eq!(
+            parse(DeviceType::Hvac, "high_temperature", "74.4"),
+            Some(DeviceCommand::SetHighTemperature(74))
+        );
+        assert_eq!(parse(DeviceType::Hvac, "low_temperature", "warm"), None);
+        assert_eq!(parse(DeviceType::Tank, "level", "50"), None);
+    }
+
+    #[test]
+    fn lists_state_fields() {
+        assert_eq!(
+            state_fields(&snapshot(
+                serde_json::json!({"type": "dimmer", "brightness": 0})
+            )),
+            [
+                ("online", "Online".to_string()),
+                ("state", "Off".to_string()),
+                ("brightness", "0".to_string())
+            ]
+        );
+        let hvac = state_fields(&snapshot(serde_json::json!({
+            "type": "hvac",
+            "status": "HeatPump",
+            "mode": "Heat",
+            "fan": "Auto",
+            "inside_temperature": 68.5,
+            "outside_temperature": 40.0,
+            "high_temperature": 76.0,
+            "low_temperature": 66.0,
+        })));
+        assert_eq!(hvac[1], ("status", "Heat Pump".to_string()));
+        assert_eq!(hvac[4], ("inside_temperature", "68.5".to_string()));
+        assert_eq!(hvac.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn carries_out_parsed_commands() {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let client = gateway(commands.clone()).await;
+        let device = |label: &str, uid: &str, device_type: u8| {
+            let mut thing: Thing = serde_json::from_value(serde_json::json!({
+                "label": label,
+                "UID": uid,
+                "configuration": {"deviceType": device_type},
+            }))
+            .unwrap();
+            thing.set_client(client.clone());
+            Device::from_thing(thing).unwrap()
+        };
+        let mut devices = [
+            device("Porch Light", "idsmyrv:dimmer:0002", 20),
+            device("Bedroom", "idsmyrv:hvac:0003", 16),
+        ];
+
+        for (index, field, payload) in [
+            (0, "brightness", "74.6"),
+            (0, "state", "off"),
+            (1, "mode", "heat_cool"),
+            (1, "fan", "high"),
+            (1, "low_temperature", "64"),
+        ] {
+            let device = &mut devices[index];
+            let command = parse_command(device.device_type(), field, payload).unwrap();
+            device.execute(&command).await.unwrap();
+        }
+        assert_eq!(
+            *commands.lock().unwrap(),
+            [
+                "/rest/items/idsmyrv_dimmer_0002_dimmer 75",
+                "/rest/items/idsmyrv_dimmer_0002_dimmer OFF",
+                "/rest/items/idsmyrv_hvac_0003_hvac_mode HEATCOOL",
+                "/rest/items/idsmyrv_hvac_0003_fan_mode HIGH",
+                "/rest/items/idsmyrv_hvac_0003_low_temperature 64",
+            ]
+        );
+    }
+}
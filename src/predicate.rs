@@ -0,0 +1,105 @@
+use super::{
+    DeviceSnapshot, DeviceState, GeneratorState, HvacMode, HvacStatus, OnlineState, SwitchState,
+};
+use serde::{Deserialize, Serialize};
+
+/// A check of one device's state, shared by alert and automation rules, ex: `{"tank_below": 15}` or `"on"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// A switch, dimmer or generator is on.
+    On,
+    /// A switch, dimmer or generator is off.
+    Off,
+    /// The device is online.
+    Online,
+    /// The device is not online.
+    Offline,
+    /// A tank is below the percentage.
+    TankBelow(u8),
+    /// A tank is above the percentage.
+    TankAbove(u8),
+    /// A switch reports a fault.
+    SwitchFault,
+    /// A generator is in the state, ex: Running.
+    GeneratorState(GeneratorState),
+    /// An HVAC reports any fail status.
+    HvacFailure,
+    /// An HVAC reports the status.
+    HvacStatus(HvacStatus),
+    /// An HVAC is set to the mode.
+    HvacMode(HvacMode),
+    /// An HVAC's inside temperature is above the value.
+    InsideTemperatureAbove(f32),
+    /// An HVAC's inside temperature is below the value.
+    InsideTemperatureBelow(f32),
+    /// An HVAC's outside temperature is above the value.
+    OutsideTemperatureAbove(f32),
+    /// An HVAC's outside temperature is below the value.
+    OutsideTemperatureBelow(f32),
+}
+
+impl Predicate {
+    /// Returns if the snapshot passes. Predicates of the wrong device type never pass.
+    pub fn test(&self, snapshot: &DeviceSnapshot) -> bool {
+        self.test_with_margin(snapshot, 0.0)
+    }
+
+    /// Returns if the snapshot passes, with the limits of value predicates moved back by the margin, ex: a
+    /// temperature above 85 with a margin of 2 passes down to 83. Used to hold a passing predicate until
+    /// the value has clearly moved back.
+    pub fn test_with_margin(&self, snapshot: &DeviceSnapshot, margin: f32) -> bool {
+        let above = |value: f32, limit: f32| value > limit - margin;
+        let below = |value: f32, limit: f32| value < limit + margin;
+        match (self, snapshot.state()) {
+            (Predicate::On, state) => state.is_on() == Some(true),
+            (Predicate::Off, state) => state.is_on() == Some(false),
+            (Predicate::Online, _) => *snapshot.online() == OnlineState::Online,
+            (Predicate::Offline, _) => *snapshot.online() != OnlineState::Online,
+            (Predicate::TankBelow(percent), DeviceState::Tank { level }) => {
+                below(level.value() as f32, *percent as f32)
+            }
+            (Predicate::TankAbove(percent), DeviceState::Tank { level }) => {
+                above(level.value() as f32, *percent as f32)
+            }
+            (Predicate::SwitchFault, DeviceState::Switch { fault, .. }) => {
+                *fault == SwitchState::On
+            }
+            (Predicate::GeneratorState(expected), DeviceState::Generator { state }) => {
+                state == expected
+            }
+            (Predicate::HvacFailure, DeviceState::Hvac { status, .. }) => status.is_failure(),
+            (Predicate::HvacStatus(expected), DeviceState::Hvac { status, .. }) => {
+                status == expected
+            }
+            (Predicate::HvacMode(expected), DeviceState::Hvac { mode, .. }) => mode == expected,
+            (
+                Predicate::InsideTemperatureAbove(limit),
+                DeviceState::Hvac {
+                    inside_temperature, ..
+                },
+            ) => above(*inside_temperature, *limit),
+            (
+                Predicate::InsideTemperatureBelow(limit),
+                DeviceState::Hvac {
+                    inside_temperature, ..
+                },
+            ) => below(*inside_temperature, *limit),
+            (
+                Predicate::OutsideTemperatureAbove(limit),
+                DeviceState::Hvac {
+                    outside_temperature,
+                    ..
+                },
+            ) => above(*outside_temperature, *limit),
+            (
+                Predicate::OutsideTemperatureBelow(limit),
+                DeviceState::Hvac {
+                    outside_temperature,
+                    ..
+                },
+            ) => below(*outside_temperature, *limit),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use super::{Device, DeviceChange, DeviceSnapshot, Predicate, Scene, SceneEntry, SceneResult};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// A test of one device, ex: `{"device": "Bedroom", "test": {"inside_temperature_above": 85}, "hysteresis": 2}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    /// The device's label or UID.
+    pub device: String,
+    /// What the device's state is checked for.
+    pub test: Predicate,
+    /// For tests against a value, how far back past the value a reading has to go before a true
+    /// condition becomes false again, ex: 85 with a hysteresis of 2 stays true until the temperature is 83 or less.
+    #[serde(default)]
+    pub hysteresis: f32,
+}
+
+/// Commands sent when every condition becomes true, and optionally when they stop being true. Usually
+/// loaded from a config file, ex:
+/// `{"name": "Heat water on generator", "when": [{"device": "Generator", "test": {"generator_state": "Running"}}],
+/// "then": [{"device": "Water Heater", "commands": [{"command": "on"}]}]}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    /// A name for the rule, used in logs and results.
+    pub name: String,
+    /// The conditions, all of which have to be true.
+    pub when: Vec<Condition>,
+    /// How long the conditions have to stay true, or false, before the rule acts on it.
+    #[serde(default)]
+    pub for_seconds: u64,
+    /// The commands sent when the conditions become true.
+    pub then: Vec<SceneEntry>,
+    /// The commands sent when the conditions stop being true.
+    #[serde(default)]
+    pub otherwise: Vec<SceneEntry>,
+}
+
+/// A rule whose conditions became true or false, with the commands to send.
+#[derive(Clone, Debug, Getters, PartialEq)]
+pub struct Trigger {
+    /// The name of the rule.
+    rule: String,
+    /// True when the conditions became true, false when they stopped being true.
+    matched: bool,
+    /// The rule's `then` or `otherwise` commands.
+    actions: Vec<SceneEntry>,
+}
+
+/// How sending a trigger's commands went.
+#[derive(Debug, Getters)]
+pub struct RuleRun {
+    /// The name of the rule.
+    rule: String,
+    /// True when the conditions became true, false when they stopped being true.
+    matched: bool,
+    /// How each device fared.
+    results: Vec<SceneResult>,
+}
+
+/// Where one rule stands.
+#[derive(Clone, Debug, Default)]
+struct RuleState {
+    /// If the rule last acted on its conditions being true.
+    active: bool,
+    /// The opposite of `active` and when it was first seen, while it is debounced.
+    pending: Option<(bool, SystemTime)>,
+}
+
+/// Evaluates rules against device state changes and sends their commands.
+///
+/// Every rule starts inactive, so a rule whose conditions are already true fires on the first update.
+/// Call `update` or `process` on every poll, even without changes, so debounced rules fire on time.
+pub struct RulesEngine {
+    rules: Vec<Rule>,
+    states: Vec<RuleState>,
+    snapshots: HashMap<String, DeviceSnapshot>,
+}
+
+impl RulesEngine {
+    /// Create an engine for the rules.
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self {
+            states: vec![RuleState::default(); rules.len()],
+            rules,
+            snapshots: HashMap::new(),
+        }
+    }
+
+    /// Returns the rules.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Adds the changes, ex: from `Watcher::poll`, and returns the rules that became true or false.
+    /// A rule is skipped until every device it tests has been seen.
+    pub fn update(&mut self, changes: &[DeviceChange], time: SystemTime) -> Vec<Trigger> {
+        for change in changes {
+            let current = change.current();
+            self.snapshots
+                .insert(current.uid().clone(), current.clone());
+        }
+
+        let mut triggers = Vec::new();
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            let Some(matched) = rule
+                .when
+                .iter()
+                .map(|condition| {
+                    find(&self.snapshots, &condition.device)
+                        .map(|snapshot| test(condition, snapshot, state.active))
+                })
+                .try_fold(true, |all, matched| matched.map(|matched| all && matched))
+            else {
+                continue;
+            };
+
+            if matched == state.active {
+                state.pending = None;
+                continue;
+            }
+            let since = match state.pending {
+                Some((pending, since)) if pending == matched => since,
+                _ => {
+                    state.pending = Some((matched, time));
+                    time
+                }
+            };
+            if time.duration_since(since).unwrap_or_default()
+                < Duration::from_secs(rule.for_seconds)
+            {
+                continue;
+            }
+            state.active = matched;
+            state.pending = None;
+            triggers.push(Trigger {
+                rule: rule.name.clone(),
+                matched,
+                actions: if matched {
+                    rule.then.clone()
+                } else {
+                    rule.otherwise.clone()
+                },
+            });
+        }
+        triggers
+    }
+
+    /// Adds the changes like `update` and sends the commands of the rules that became true or false.
+    /// Rules run one after another, in the order they are defined.
+    pub async fn process(
+        &mut self,
+        changes: &[DeviceChange],
+        devices: &mut [Device],
+        time: SystemTime,
+    ) -> Vec<RuleRun> {
+        let mut runs = Vec::new();
+        for trigger in self.update(changes, time) {
+            runs.push(trigger.run(devices).await);
+        }
+        runs
+    }
+}
+
+impl Trigger {
+    /// Sends the commands to the devices: each device's commands in order, and every device at once.
+    pub async fn run(self, devices: &mut [Device]) -> RuleRun {
+        let scene = self
+            .actions
+            .into_iter()
+            .fold(Scene::new(self.rule.clone()), Scene::with_entry);
+        RuleRun {
+            rule: self.rule,
+            matched: self.matched,
+            results: scene.apply(devices).await,
+        }
+    }
+}
+
+impl RuleRun {
+    /// Returns true when every command was carried out.
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(SceneResult::is_ok)
+    }
+}
+
+fn find<'a>(
+    snapshots: &'a HashMap<String, DeviceSnapshot>,
+    name: &str,
+) -> Option<&'a DeviceSnapshot> {
+    snapshots
+        .get(name)
+        .or_else(|| snapshots.values().find(|snapshot| snapshot.matches(name)))
+}
+
+/// Returns if the condition holds. While the rule is active, thresholds are moved back by the hysteresis.
+fn test(condition: &Condition, snapshot: &DeviceSnapshot, active: bool) -> bool {
+    let margin = if active { condition.hysteresis } else { 0.0 };
+    condition.test.test_with_margin(snapshot, margin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DeviceCommand;
+    use std::time::UNIX_EPOCH;
+
+    fn bedroom(inside_temperature: f32) -> DeviceChange {
+        let current = serde_json::from_value(serde_json::json!({
+            "label": "Bedroom",
+            "uid": "idsmyrv:hvac-0001/01",
+            "device_type": "Hvac",
+            "online": "Online",
+            "state": {
+                "type": "hvac",
+                "status": "Cooling",
+                "mode": "Cool",
+                "fan": "Auto",
+                "inside_temperature": inside_temperature,
+                "outside_temperature": 90.0,
+                "high_temperature": 76.0,
+                "low_temperature": 66.0,
+            },
+        }))
+        .unwrap();
+        DeviceChange::new(None, current)
+    }
+
+    fn rule(device: &str, hysteresis: f32, for_seconds: u64) -> Rule {
+        Rule {
+            name: "Hot bedroom".to_string(),
+            when: vec![Condition {
+                device: device.to_string(),
+                test: Predicate::InsideTemperatureAbove(85.0),
+                hysteresis,
+            }],
+            for_seconds,
+            then: vec![SceneEntry::new("Fan", vec![DeviceCommand::On])],
+            otherwise: vec![SceneEntry::new("Fan", vec![DeviceCommand::Off])],
+        }
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000 + seconds)
+    }
+
+    fn matched(triggers: &[Trigger]) -> Vec<bool> {
+        triggers.iter().map(|trigger| trigger.matched).collect()
+    }
+
+    #[test]
+    fn fires_then_otherwise() {
+        let mut engine = RulesEngine::new(vec![rule("Bedroom", 0.0, 0)]);
+        let triggers = engine.update(&[bedroom(86.0)], at(0));
+        assert_eq!(matched(&triggers), [true]);
+        assert_eq!(triggers[0].actions, engine.rules()[0].then);
+        assert!(engine.update(&[bedroom(87.0)], at(10)).is_empty());
+
+        let triggers = engine.update(&[bedroom(80.0)], at(20));
+        assert_eq!(matched(&triggers), [false]);
+        assert_eq!(triggers[0].actions, engine.rules()[0].otherwise);
+    }
+
+    #[test]
+    fn waits_for_unseen_devices() {
+        let mut engine = RulesEngine::new(vec![rule("Living Room", 0.0, 0)]);
+        assert!(engine.update(&[bedroom(90.0)], at(0)).is_empty());
+    }
+
+    #[test]
+    fn finds_devices_by_object_id() {
+        let mut engine = RulesEngine::new(vec![rule("idsmyrv_hvac_0001_01", 0.0, 0)]);
+        assert_eq!(matched(&engine.update(&[bedroom(86.0)], at(0))), [true]);
+    }
+
+    #[test]
+    fn debounces_for_seconds() {
+        let mut engine = RulesEngine::new(vec![rule("Bedroom", 0.0, 60)]);
+        assert!(engine.update(&[bedroom(86.0)], at(0)).is_empty());
+        assert!(engine.update(&[], at(59)).is_empty());
+        assert_eq!(matched(&engine.update(&[], at(60))), [true]);
+
+        // A blip shorter than the debounce restarts it.
+        assert!(engine.update(&[bedroom(80.0)], at(100)).is_empty());
+        assert!(engine.update(&[bedroom(86.0)], at(130)).is_empty());
+        assert!(engine.update(&[bedroom(80.0)], at(140)).is_empty());
+        assert!(engine.update(&[], at(199)).is_empty());
+        assert_eq!(matched(&engine.update(&[], at(200))), [false]);
+    }
+
+    #[test]
+    fn holds_within_hysteresis() {
+        let mut engine = RulesEngine::new(vec![rule("Bedroom", 2.0, 0)]);
+        assert!(engine.update(&[bedroom(84.0)], at(0)).is_empty());
+        assert_eq!(matched(&engine.update(&[bedroom(85.5)], at(10))), [true]);
+        assert!(engine.update(&[bedroom(84.0)], at(20)).is_empty());
+        assert!(engine.update(&[bedroom(83.5)], at(30)).is_empty());
+        assert_eq!(matched(&engine.update(&[bedroom(83.0)], at(40))), [false]);
+        // Once inactive, the condition needs the real limit again.
+        assert!(engine.update(&[bedroom(84.5)], at(50)).is_empty());
+    }
+
+    #[test]
+    fn conditions_deserialize() {
+        let condition: Condition = serde_json::from_value(serde_json::json!({
+            "device": "Generator",
+            "test": {"generator_state": "Running"},
+        }))
+        .unwrap();
+        assert_eq!(
+            condition.test,
+            Predicate::GeneratorState(crate::GeneratorState::Running)
+        );
+        assert_eq!(condition.hysteresis, 0.0);
+    }
+}
@@ -0,0 +1,381 @@
+use super::device::match_devices;
+use super::{Device, DeviceCommand, DeviceCommandError, DeviceState, DeviceStateError};
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The commands that put one device in its scene state, in order.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize, Deserialize)]
+pub struct SceneEntry {
+    /// The device's label or UID.
+    device: String,
+    /// The commands to send, one after another.
+    commands: Vec<DeviceCommand>,
+}
+
+/// A named state of many devices, ex: "Movie" dims the living room lights, turns the pump off and sets the fan low.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    /// The scene's name.
+    name: String,
+    /// What each device is set to.
+    entries: Vec<SceneEntry>,
+}
+
+/// How capturing or applying a scene went for one device.
+#[derive(Debug, Getters)]
+pub struct SceneResult {
+    /// The device's label or UID, as written in the scene. The UID when capturing.
+    device: String,
+    /// Ok when every command was carried out, or the device was read.
+    result: Result<(), SceneError>,
+}
+
+impl SceneEntry {
+    /// Create an entry sending the commands to the device with the label or UID.
+    pub fn new(device: impl Into<String>, commands: Vec<DeviceCommand>) -> Self {
+        Self {
+            device: device.into(),
+            commands,
+        }
+    }
+}
+
+impl Scene {
+    /// Create an empty scene.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds the commands for a device.
+    pub fn with_entry(mut self, entry: SceneEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    /// Reads the dimmers, switches and HVACs among the devices into a scene that puts them back as they are now,
+    /// and returns how reading each of them went. Devices that can not be read are left out of the scene, and
+    /// other types of devices are skipped.
+    pub async fn capture(name: impl Into<String>, devices: &[Device]) -> (Self, Vec<SceneResult>) {
+        let devices: Vec<&Device> = devices
+            .iter()
+            .filter(|device| {
+                matches!(
+                    device,
+                    Device::Dimmer(_) | Device::Switch(_) | Device::Hvac(_)
+                )
+            })
+            .collect();
+        let states = futures::future::join_all(devices.iter().map(|device| device.state())).await;
+
+        let mut scene = Self::new(name);
+        let mut results = Vec::new();
+        for (device, state) in devices.into_iter().zip(states) {
+            let result = match state {
+                Ok(state) => {
+                    scene
+                        .entries
+                        .push(SceneEntry::new(device.uid(), commands_for(&state)));
+                    Ok(())
+                }
+                Err(err) => Err(SceneError::State(device.label(), err)),
+            };
+            results.push(SceneResult {
+                device: device.uid(),
+                result,
+            });
+        }
+        (scene, results)
+    }
+
+    /// Sends each entry's commands in order, to every device at once, and returns how each entry went.
+    /// Entries are matched to the devices by label or UID.
+    pub async fn apply(&self, devices: &mut [Device]) -> Vec<SceneResult> {
+        let names: Vec<&str> = self
+            .entries
+            .iter()
+            .map(|entry| entry.device.as_str())
+            .collect();
+        let targets = match_devices(devices, &names);
+
+        let applies = self
+            .entries
+            .iter()
+            .zip(targets)
+            .map(|(entry, device)| async move {
+                let result = match device {
+                    Some(device) => apply_entry(entry, device).await,
+                    None => Err(SceneError::NotFound(entry.device.clone())),
+                };
+                SceneResult {
+                    device: entry.device.clone(),
+                    result,
+                }
+            });
+        futures::future::join_all(applies).await
+    }
+}
+
+impl SceneResult {
+    /// Returns true when every command was carried out.
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+async fn apply_entry(entry: &SceneEntry, device: &mut Device) -> Result<(), SceneError> {
+    for command in &entry.commands {
+        device
+            .execute(command)
+            .await
+            .map_err(|err| SceneError::Command(command.clone(), err))?;
+    }
+    Ok(())
+}
+
+/// Returns the commands that restore a dimmer, switch or HVAC to the state.
+fn commands_for(state: &DeviceState) -> Vec<DeviceCommand> {
+    match state {
+        DeviceState::Dimmer { brightness } if brightness.value() == 0 => vec![DeviceCommand::Off],
+        DeviceState::Dimmer { brightness } => {
+            vec![DeviceCommand::SetBrightness(brightness.value())]
+        }
+        DeviceState::Switch { .. } if state.is_on() == Some(true) => vec![DeviceCommand::On],
+        DeviceState::Switch { .. } => vec![DeviceCommand::Off],
+        DeviceState::Hvac {
+            mode,
+            fan,
+            high_temperature,
+            low_temperature,
+            ..
+        } => vec![
+            DeviceCommand::SetHighTemperature(high_temperature.round() as isize),
+            DeviceCommand::SetLowTemperature(low_temperature.round() as isize),
+            DeviceCommand::SetFan(*fan),
+            DeviceCommand::SetMode(*mode),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// A scene could not be captured, or a device could not be put in its scene state.
+#[derive(Debug, Error)]
+pub enum SceneError {
+    /// No device matches the label or UID.
+    #[error("No device matches \"{0}\".")]
+    NotFound(String),
+    /// A device's state could not be read while capturing.
+    #[error("The state of {0} could not be read. {1}")]
+    State(String, DeviceStateError),
+    /// A command failed; the commands after it were not sent.
+    #[error("'{0}' failed. {1}")]
+    Command(DeviceCommand, DeviceCommandError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, HvacFanMode, HvacMode, Thing};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Serves a dimmer at 30% and fails every HVAC read. Records each request as "METHOD path body".
+    async fn gateway(requests: Arc<Mutex<Vec<String>>>) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(socket);
+                let mut request = String::new();
+                reader.read_line(&mut request).await.unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let mut parts = request.split_whitespace();
+                let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+                requests.lock().unwrap().push(
+                    format!("{} {} {}", method, path, String::from_utf8_lossy(&body))
+                        .trim()
+                        .to_string(),
+                );
+                let state = if path.contains("hvac") {
+                    None
+                } else if path.ends_with("_online") {
+                    Some("ON")
+                } else {
+                    Some("30")
+                };
+                let response = match state {
+                    Some(state) => {
+                        let body = format!("{{\"state\": \"{}\"}}", state);
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    None => "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        .to_string(),
+                };
+                reader
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    fn device(client: &Client, label: &str, uid: &str, device_type: u8) -> Device {
+        let mut thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": label,
+            "UID": uid,
+            "configuration": {"deviceType": device_type},
+        }))
+        .unwrap();
+        thing.set_client(client.clone());
+        Device::from_thing(thing).unwrap()
+    }
+
+    fn devices(client: &Client) -> Vec<Device> {
+        vec![
+            device(client, "Fresh Water", "idsmyrv:tank-sensor:0001", 10),
+            device(client, "Porch Light", "idsmyrv:dimmer:0002", 20),
+            device(client, "Bedroom", "idsmyrv:hvac:0003", 16),
+        ]
+    }
+
+    fn state(state: serde_json::Value) -> DeviceState {
+        serde_json::from_value(state).unwrap()
+    }
+
+    #[tokio::test]
+    async fn captures_the_devices_it_can_read() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let client = gateway(requests.clone()).await;
+        let (scene, results) = Scene::capture("Evening", &devices(&client)).await;
+
+        assert_eq!(
+            scene,
+            Scene::new("Evening").with_entry(SceneEntry::new(
+                "idsmyrv:dimmer:0002",
+                vec![DeviceCommand::SetBrightness(30)]
+            ))
+        );
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].device(), "idsmyrv:dimmer:0002");
+        assert!(results[0].is_ok());
+        assert_eq!(results[1].device(), "idsmyrv:hvac:0003");
+        assert!(matches!(
+            results[1].result(),
+            Err(SceneError::State(label, _)) if label == "Bedroom"
+        ));
+        assert!(!requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|request| request.contains("tank")));
+    }
+
+    #[tokio::test]
+    async fn applies_each_entry_in_order() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let client = gateway(requests.clone()).await;
+        let mut devices = devices(&client);
+        let scene = Scene::new("Movie")
+            .with_entry(SceneEntry::new(
+                "Porch Light",
+                vec![DeviceCommand::SetBrightness(50), DeviceCommand::Off],
+            ))
+            .with_entry(SceneEntry::new(
+                "idsmyrv:tank-sensor:0001",
+                vec![DeviceCommand::On, DeviceCommand::Off],
+            ))
+            .with_entry(SceneEntry::new("Nowhere", vec![DeviceCommand::On]));
+        let results = scene.apply(&mut devices).await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1].result(),
+            Err(SceneError::Command(DeviceCommand::On, _))
+        ));
+        assert!(matches!(
+            results[2].result(),
+            Err(SceneError::NotFound(name)) if name == "Nowhere"
+        ));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                "POST /rest/items/idsmyrv_dimmer_0002_dimmer 50",
+                "POST /rest/items/idsmyrv_dimmer_0002_dimmer OFF",
+            ]
+        );
+    }
+
+    #[test]
+    fn restores_each_state() {
+        assert_eq!(
+            commands_for(&state(
+                serde_json::json!({"type": "dimmer", "brightness": 0})
+            )),
+            [DeviceCommand::Off]
+        );
+        assert_eq!(
+            commands_for(&state(
+                serde_json::json!({"type": "dimmer", "brightness": 45})
+            )),
+            [DeviceCommand::SetBrightness(45)]
+        );
+        assert_eq!(
+            commands_for(&state(
+                serde_json::json!({"type": "switch", "state": "On", "fault": "Off", "relay_current": "1.2"})
+            )),
+            [DeviceCommand::On]
+        );
+        assert_eq!(
+            commands_for(&state(
+                serde_json::json!({"type": "switch", "state": "Off", "fault": "Off", "relay_current": "0"})
+            )),
+            [DeviceCommand::Off]
+        );
+        assert_eq!(
+            commands_for(&state(serde_json::json!({
+                "type": "hvac",
+                "status": "Cooling",
+                "mode": "Cool",
+                "fan": "Low",
+                "inside_temperature": 78.0,
+                "outside_temperature": 90.0,
+                "high_temperature": 72.4,
+                "low_temperature": 64.6,
+            }))),
+            [
+                DeviceCommand::SetHighTemperature(72),
+                DeviceCommand::SetLowTemperature(65),
+                DeviceCommand::SetFan(HvacFanMode::Low),
+                DeviceCommand::SetMode(HvacMode::Cool),
+            ]
+        );
+        assert_eq!(
+            commands_for(&state(serde_json::json!({"type": "tank", "level": 50}))),
+            []
+        );
+    }
+}
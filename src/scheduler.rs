@@ -0,0 +1,875 @@
+use super::{Client, DeviceCommand, DeviceCommandError, ThingError};
+use chrono::{DateTime, Datelike, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// How far ahead a cron expression is searched for a match, long enough for "29 Feb on a Monday".
+const SEARCH_DAYS: u32 = 366 * 28;
+/// The longest the scheduler sleeps before checking the clock and the schedule file again.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// The cron fields, as (name, first value, last value, value names).
+const FIELDS: [(&str, u32, u32, &[&str]); 5] = [
+    ("minute", 0, 59, &[]),
+    ("hour", 0, 23, &[]),
+    ("day of month", 1, 31, &[]),
+    (
+        "month",
+        1,
+        12,
+        &[
+            "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+        ],
+    ),
+    (
+        "day of week",
+        0,
+        7,
+        &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"],
+    ),
+];
+
+/// A five field cron expression, "minute hour day-of-month month day-of-week", matched in the schedule's time zone.
+///
+/// Fields take `*`, values, names (`JAN`, `SUN`), ranges, lists and steps, ex: `*/15 6-8 * * MON-FRI`.
+/// The day of week also takes `SUN#1` for the first Sunday of the month. As in cron, when both day fields
+/// are restricted a day matching either one matches. `@hourly`, `@daily`, `@weekly`, `@monthly` and
+/// `@yearly` are also accepted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    expression: String,
+    /// One bitmask per field, bit N set when value N matches.
+    masks: [u64; 5],
+    /// (weekday, nth) pairs from `DAY#N`, with Sunday as 0.
+    nth_weekdays: Vec<(u32, u32)>,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+    /// If the hour field starts with `*`, ex: `*` or `*/2`.
+    any_hour: bool,
+}
+
+/// Sunrise or sunset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SunEvent {
+    /// When the top of the sun rises above the horizon.
+    Sunrise,
+    /// When the top of the sun sets below the horizon.
+    Sunset,
+}
+
+/// Where the coach is, for sunrise and sunset.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    /// Degrees north, negative for south.
+    pub latitude: f64,
+    /// Degrees east, negative for west.
+    pub longitude: f64,
+}
+
+/// When a job runs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum When {
+    /// Whenever the cron expression matches, ex: `{"type": "cron", "cron": "0 6 * * *"}`.
+    Cron {
+        /// The expression.
+        cron: Cron,
+    },
+    /// Every so many seconds, on multiples counted from the Unix epoch so the times don't drift across restarts.
+    /// Intervals that divide a day land on the same UTC times every day, ex: 3600 runs on the hour.
+    Every {
+        /// Seconds between runs.
+        seconds: u64,
+    },
+    /// At sunrise or sunset, ex: `{"type": "sun", "event": "sunset", "offset_minutes": -15}`.
+    Sun {
+        /// Sunrise or sunset.
+        event: SunEvent,
+        /// Minutes after the event, negative for before.
+        #[serde(default)]
+        offset_minutes: i32,
+    },
+}
+
+/// Commands to send to a device on a schedule.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    /// The job's name, unique within the schedule.
+    name: String,
+    /// When the job runs.
+    when: When,
+    /// The device's label or UID.
+    device: String,
+    /// The commands to send, one after another.
+    commands: Vec<DeviceCommand>,
+}
+
+/// Every job, with the time zone and location they are worked out in. Saved as JSON.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// The time zone cron expressions are matched in, ex: "America/Denver".
+    timezone: Tz,
+    /// Where the coach is. Required for sunrise and sunset jobs.
+    location: Option<Location>,
+    /// The jobs.
+    jobs: Vec<Job>,
+}
+
+/// Runs a schedule's jobs against the gateway as they come due.
+pub struct Scheduler {
+    schedule: Schedule,
+    client: Client,
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+impl Cron {
+    /// Parses the expression.
+    pub fn new(expression: impl Into<String>) -> Result<Self, CronError> {
+        let expression = expression.into();
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != FIELDS.len() {
+            return Err(CronError::FieldCount(fields.len()));
+        }
+
+        let mut masks = [0; 5];
+        let mut nth_weekdays = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            for part in field.split(',') {
+                if index == 4 {
+                    if let Some((day, nth)) = part.split_once('#') {
+                        let day = parse_value(index, day)? % 7;
+                        match nth.parse() {
+                            Ok(nth) if (1..=5).contains(&nth) => nth_weekdays.push((day, nth)),
+                            _ => return Err(invalid(index, part)),
+                        }
+                        continue;
+                    }
+                }
+                masks[index] |= parse_part(index, part)?;
+            }
+        }
+        // 7 is also Sunday.
+        if masks[4] & (1 << 7) != 0 {
+            masks[4] = (masks[4] | 1) & !(1 << 7);
+        }
+        let any_day_of_month = fields[2] == "*";
+        let any_day_of_week = fields[4] == "*";
+        let any_hour = fields[1].starts_with('*');
+        Ok(Self {
+            expression,
+            masks,
+            nth_weekdays,
+            any_day_of_month,
+            any_day_of_week,
+            any_hour,
+        })
+    }
+
+    /// Returns the first time after `after` that matches, in the time zone.
+    ///
+    /// A local time skipped by a daylight saving change runs at the first minute after the gap. A local
+    /// time repeated by one runs the first time only when the hour is fixed, ex: `30 1 * * *`, and both
+    /// times when the hour field starts with `*`, so `*/15 * * * *` keeps running through the repeated hour.
+    pub fn next_after(&self, after: SystemTime, timezone: Tz) -> Option<SystemTime> {
+        let after = DateTime::<Utc>::from(after);
+        let mut date = after.with_timezone(&timezone).date_naive();
+        for _ in 0..SEARCH_DAYS {
+            if self.matches_date(date) {
+                let next = (0..24)
+                    .filter(|hour| self.masks[1] & (1 << hour) != 0)
+                    .flat_map(|hour| {
+                        (0..60)
+                            .filter(|minute| self.masks[0] & (1 << minute) != 0)
+                            .filter_map(move |minute| date.and_hms_opt(hour, minute, 0))
+                    })
+                    .flat_map(|time| resolve(timezone, time, self.any_hour))
+                    .filter(|time| *time > after)
+                    .min();
+                if let Some(time) = next {
+                    return Some(time.into());
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.masks[3] & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day_of_month = self.masks[2] & (1 << date.day()) != 0;
+        let weekday = date.weekday().num_days_from_sunday();
+        let nth = (date.day() - 1) / 7 + 1;
+        let day_of_week =
+            self.masks[4] & (1 << weekday) != 0 || self.nth_weekdays.contains(&(weekday, nth));
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (true, true) => true,
+            (true, false) => day_of_week,
+            (false, true) => day_of_month,
+            (false, false) => day_of_month || day_of_week,
+        }
+    }
+}
+
+impl Location {
+    /// Returns when the sun rises on the local date, or None when it doesn't, ex: polar night.
+    pub fn sunrise(&self, date: NaiveDate) -> Option<SystemTime> {
+        self.sun(date).map(|(sunrise, _)| sunrise)
+    }
+
+    /// Returns when the sun sets on the local date, or None when it doesn't, ex: midnight sun.
+    pub fn sunset(&self, date: NaiveDate) -> Option<SystemTime> {
+        self.sun(date).map(|(_, sunset)| sunset)
+    }
+
+    /// Returns the sunrise and sunset with the sunrise equation, accurate to a minute or two.
+    fn sun(&self, date: NaiveDate) -> Option<(SystemTime, SystemTime)> {
+        let j2000 = NaiveDate::from_ymd_opt(2000, 1, 1)?;
+        // Days from 2000-01-01 12:00 to the local solar noon.
+        let noon = (date - j2000).num_days() as f64 - self.longitude / 360.0;
+        let anomaly = (357.5291 + 0.98560028 * noon)
+            .rem_euclid(360.0)
+            .to_radians();
+        let center =
+            1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+        let ecliptic_longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+            .rem_euclid(360.0)
+            .to_radians();
+        let transit =
+            2451545.0 + noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+        let declination = (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+        let latitude = self.latitude.to_radians();
+        let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * declination.sin())
+            / (latitude.cos() * declination.cos());
+        if !(-1.0..=1.0).contains(&cos_hour_angle) {
+            return None;
+        }
+        let hour_angle = cos_hour_angle.acos().to_degrees() / 360.0;
+        Some((
+            julian_to_time(transit - hour_angle)?,
+            julian_to_time(transit + hour_angle)?,
+        ))
+    }
+}
+
+impl When {
+    /// Returns the first time after `after` the job runs.
+    pub fn next_after(
+        &self,
+        after: SystemTime,
+        timezone: Tz,
+        location: Option<&Location>,
+    ) -> Option<SystemTime> {
+        match self {
+            When::Cron { cron } => cron.next_after(after, timezone),
+            When::Every { seconds } => {
+                let since_epoch = after.duration_since(UNIX_EPOCH).ok()?.as_secs();
+                let runs = since_epoch.checked_div(*seconds)?;
+                Some(UNIX_EPOCH + Duration::from_secs((runs + 1) * seconds))
+            }
+            When::Sun {
+                event,
+                offset_minutes,
+            } => {
+                let location = location?;
+                let offset = Duration::from_secs(offset_minutes.unsigned_abs() as u64 * 60);
+                // Start a day early, as an offset can move yesterday's sunset past `after`.
+                let mut date = DateTime::<Utc>::from(after)
+                    .with_timezone(&timezone)
+                    .date_naive()
+                    .pred_opt()?;
+                for _ in 0..SEARCH_DAYS {
+                    let time = match event {
+                        SunEvent::Sunrise => location.sunrise(date),
+                        SunEvent::Sunset => location.sunset(date),
+                    }
+                    .and_then(|time| {
+                        if *offset_minutes < 0 {
+                            time.checked_sub(offset)
+                        } else {
+                            time.checked_add(offset)
+                        }
+                    });
+                    if let Some(time) = time.filter(|time| *time > after) {
+                        return Some(time);
+                    }
+                    date = date.succ_opt()?;
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Job {
+    /// Create a job sending the commands to the device with the label or UID.
+    pub fn new(
+        name: impl Into<String>,
+        when: When,
+        device: impl Into<String>,
+        commands: Vec<DeviceCommand>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            when,
+            device: device.into(),
+            commands,
+        }
+    }
+}
+
+impl Schedule {
+    /// Create an empty schedule in the time zone.
+    pub fn new(timezone: Tz) -> Self {
+        Self {
+            timezone,
+            location: None,
+            jobs: Vec::new(),
+        }
+    }
+
+    /// Sets where the coach is, for sunrise and sunset jobs.
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// Reads a schedule saved with `save`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScheduleError> {
+        let schedule: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        for job in &schedule.jobs {
+            schedule.check(job)?;
+        }
+        Ok(schedule)
+    }
+
+    /// Writes the schedule as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ScheduleError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Adds the job, replacing any job with the same name.
+    pub fn add_job(&mut self, job: Job) -> Result<(), ScheduleError> {
+        self.check(&job)?;
+        self.remove_job(&job.name);
+        self.jobs.push(job);
+        Ok(())
+    }
+
+    /// Removes the job with the name and returns it.
+    pub fn remove_job(&mut self, name: &str) -> Option<Job> {
+        let index = self.jobs.iter().position(|job| job.name == name)?;
+        Some(self.jobs.remove(index))
+    }
+
+    /// Returns the first time after `after` the job runs.
+    pub fn next_run(&self, job: &Job, after: SystemTime) -> Option<SystemTime> {
+        job.when
+            .next_after(after, self.timezone, self.location.as_ref())
+    }
+
+    fn check(&self, job: &Job) -> Result<(), ScheduleError> {
+        if matches!(job.when, When::Sun { .. }) && self.location.is_none() {
+            return Err(ScheduleError::NoLocation(job.name.clone()));
+        }
+        if matches!(job.when, When::Every { seconds: 0 }) {
+            return Err(ScheduleError::ZeroInterval(job.name.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new(Tz::UTC)
+    }
+}
+
+impl Scheduler {
+    /// Create a scheduler for the gateway behind the client.
+    pub fn new(schedule: Schedule, client: Client) -> Self {
+        Self {
+            schedule,
+            client,
+            path: None,
+            modified: None,
+        }
+    }
+
+    /// Create a scheduler that loads the schedule from the file, and loads it again whenever the file changes.
+    pub fn from_file(path: impl Into<PathBuf>, client: Client) -> Result<Self, ScheduleError> {
+        let path = path.into();
+        let modified = modified(&path);
+        Ok(Self {
+            schedule: Schedule::load(&path)?,
+            client,
+            path: Some(path),
+            modified,
+        })
+    }
+
+    /// Returns the schedule being run.
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Runs jobs as they come due, forever. A failed job is logged and runs again at its next time.
+    pub async fn run(mut self) {
+        let mut now = SystemTime::now();
+        let mut next = self.next_runs(now);
+        loop {
+            let wait = next
+                .iter()
+                .flatten()
+                .min()
+                .map(|time| time.duration_since(now).unwrap_or_default())
+                .unwrap_or(MAX_SLEEP)
+                .min(MAX_SLEEP);
+            tokio::time::sleep(wait).await;
+            let slept_from = now;
+            now = SystemTime::now();
+
+            if self.reload() {
+                next = self.next_runs(slept_from);
+            }
+            for (job, next) in self.schedule.jobs.iter().zip(next.iter_mut()) {
+                if next.is_some_and(|time| time <= now) {
+                    match self.run_job(job).await {
+                        Ok(()) => log::info!("Ran \"{}\".", job.name),
+                        Err(err) => log::error!("\"{}\" failed. {}", job.name, err),
+                    }
+                    *next = self.schedule.next_run(job, now);
+                }
+            }
+        }
+    }
+
+    /// Sends the job's commands to its device now.
+    pub async fn run_job(&self, job: &Job) -> Result<(), JobError> {
+        let mut device = self
+            .client
+            .devices()
+            .await?
+            .into_iter()
+            .find(|device| device.matches(&job.device))
+            .ok_or_else(|| JobError::NotFound(job.device.clone()))?;
+        for command in &job.commands {
+            device.execute(command).await?;
+        }
+        Ok(())
+    }
+
+    fn next_runs(&self, now: SystemTime) -> Vec<Option<SystemTime>> {
+        self.schedule
+            .jobs
+            .iter()
+            .map(|job| {
+                let next = self.schedule.next_run(job, now);
+                if next.is_none() {
+                    log::warn!("\"{}\" will never run.", job.name);
+                }
+                next
+            })
+            .collect()
+    }
+
+    /// Loads the schedule file again if it changed. Returns true when the schedule was replaced.
+    fn reload(&mut self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let modified = modified(path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        match Schedule::load(path) {
+            Ok(schedule) => {
+                log::info!(
+                    "Loaded {} jobs from {}.",
+                    schedule.jobs.len(),
+                    path.display()
+                );
+                self.schedule = schedule;
+                true
+            }
+            Err(err) => {
+                log::error!(
+                    "{} could not be loaded, keeping the current schedule. {}",
+                    path.display(),
+                    err
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Returns the instants of the local time, both of them when it is repeated and `repeats` is set.
+/// See `Cron::next_after` for daylight saving changes.
+fn resolve(timezone: Tz, time: NaiveDateTime, repeats: bool) -> Vec<DateTime<Utc>> {
+    let local = (0..=180)
+        .map(|minutes| timezone.from_local_datetime(&(time + chrono::Duration::minutes(minutes))))
+        .find(|local| !matches!(local, LocalResult::None));
+    let times = match local {
+        Some(LocalResult::Single(time)) => vec![time],
+        Some(LocalResult::Ambiguous(first, second)) if repeats => vec![first, second],
+        Some(LocalResult::Ambiguous(first, _)) => vec![first],
+        _ => Vec::new(),
+    };
+    times
+        .into_iter()
+        .map(|time| time.with_timezone(&Utc))
+        .collect()
+}
+
+fn julian_to_time(julian_day: f64) -> Option<SystemTime> {
+    let seconds = ((julian_day - 2440587.5) * 86400.0).round();
+    UNIX_EPOCH.checked_add(Duration::try_from_secs_f64(seconds).ok()?)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Parses a value, range or step of one field into a bitmask.
+fn parse_part(index: usize, part: &str) -> Result<u64, CronError> {
+    let (_, first, last, _) = FIELDS[index];
+    let (range, step) = match part.split_once('/') {
+        Some((range, step)) => match step.parse::<u32>() {
+            Ok(step) if step > 0 => (range, step),
+            _ => return Err(invalid(index, part)),
+        },
+        None => (part, 1),
+    };
+    let (start, end) = match range.split_once('-') {
+        _ if range == "*" => (first, last),
+        Some((start, end)) => (parse_value(index, start)?, parse_value(index, end)?),
+        // "5/15" runs from 5 to the end of the field.
+        None if step > 1 => (parse_value(index, range)?, last),
+        None => {
+            let value = parse_value(index, range)?;
+            (value, value)
+        }
+    };
+    if start > end {
+        return Err(invalid(index, part));
+    }
+    Ok((start..=end)
+        .step_by(step as usize)
+        .fold(0, |mask, value| mask | (1 << value)))
+}
+
+fn parse_value(index: usize, value: &str) -> Result<u32, CronError> {
+    let (_, first, last, names) = FIELDS[index];
+    let parsed = match names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+    {
+        // Months are named from 1, days of week from 0.
+        Some(position) => position as u32 + first,
+        None => value.parse().map_err(|_| invalid(index, value))?,
+    };
+    if parsed < first || parsed > last {
+        return Err(invalid(index, value));
+    }
+    Ok(parsed)
+}
+
+fn invalid(index: usize, value: &str) -> CronError {
+    CronError::Value(FIELDS[index].0, value.to_string())
+}
+
+impl TryFrom<String> for Cron {
+    type Error = CronError;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        Self::new(expression)
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> Self {
+        cron.expression
+    }
+}
+
+impl std::str::FromStr for Cron {
+    type Err = CronError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        Self::new(expression)
+    }
+}
+
+impl std::fmt::Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl std::fmt::Display for SunEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let event = match self {
+            SunEvent::Sunrise => "Sunrise",
+            SunEvent::Sunset => "Sunset",
+        };
+        write!(f, "{}", event)
+    }
+}
+
+/// A cron expression could not be parsed.
+#[derive(Debug, Error)]
+pub enum CronError {
+    /// The expression does not have five fields.
+    #[error("A cron expression has 5 fields, this one has {0}.")]
+    FieldCount(usize),
+    /// A field has a value out of range or that can't be read.
+    #[error("The {0} field can not be \"{1}\".")]
+    Value(&'static str, String),
+}
+
+/// The schedule could not be loaded, saved or changed.
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    /// The schedule file could not be read or written.
+    #[error("The schedule file could not be used. {0}")]
+    Io(std::io::Error),
+    /// The schedule is not valid JSON.
+    #[error("The schedule could not be converted. {0}")]
+    SerdeJsonConversion(serde_json::Error),
+    /// A sunrise or sunset job is in a schedule without a location.
+    #[error("\"{0}\" runs at sunrise or sunset, but the schedule has no location.")]
+    NoLocation(String),
+    /// An interval job runs every 0 seconds.
+    #[error("\"{0}\" runs every 0 seconds.")]
+    ZeroInterval(String),
+}
+
+/// A job's commands could not be sent.
+#[derive(Debug, Error)]
+pub enum JobError {
+    /// The devices could not be listed.
+    #[error("The devices could not be listed. {0}")]
+    Things(ThingError),
+    /// No device matches the job's label or UID.
+    #[error("No device matches \"{0}\".")]
+    NotFound(String),
+    /// A command failed.
+    #[error("{0}")]
+    Command(DeviceCommandError),
+}
+
+impl From<std::io::Error> for ScheduleError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ScheduleError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::SerdeJsonConversion(error)
+    }
+}
+
+impl From<ThingError> for JobError {
+    fn from(error: ThingError) -> Self {
+        Self::Things(error)
+    }
+}
+
+impl From<DeviceCommandError> for JobError {
+    fn from(error: DeviceCommandError) -> Self {
+        Self::Command(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::Denver;
+
+    fn denver(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> SystemTime {
+        Denver
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .earliest()
+            .unwrap()
+            .into()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> SystemTime {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+            .into()
+    }
+
+    fn next(cron: &str, after: SystemTime) -> SystemTime {
+        Cron::new(cron).unwrap().next_after(after, Denver).unwrap()
+    }
+
+    fn minutes_apart(a: SystemTime, b: SystemTime) -> u64 {
+        a.duration_since(b)
+            .or_else(|_| b.duration_since(a))
+            .unwrap()
+            .as_secs()
+            / 60
+    }
+
+    #[test]
+    fn matches_fields() {
+        assert_eq!(
+            next("*/15 6-8 * * MON-FRI", denver(2026, 3, 6, 8, 50)),
+            denver(2026, 3, 9, 6, 0)
+        );
+        assert_eq!(
+            next("@monthly", denver(2026, 3, 6, 8, 50)),
+            denver(2026, 4, 1, 0, 0)
+        );
+        // Either restricted day field matches.
+        assert_eq!(
+            next("0 12 13 * FRI", denver(2026, 3, 1, 0, 0)),
+            denver(2026, 3, 6, 12, 0)
+        );
+        assert!(Cron::new("0 24 * * *").is_err());
+        assert!(Cron::new("0 0 * * SUN#6").is_err());
+        assert!(Cron::new("0 0 * *").is_err());
+    }
+
+    #[test]
+    fn matches_the_first_sunday() {
+        // 2026-03-01 is a Sunday, 2026-04-05 is the next first Sunday.
+        assert_eq!(
+            next("0 9 * * SUN#1", denver(2026, 2, 20, 0, 0)),
+            denver(2026, 3, 1, 9, 0)
+        );
+        assert_eq!(
+            next("0 9 * * SUN#1", denver(2026, 3, 1, 9, 0)),
+            denver(2026, 4, 5, 9, 0)
+        );
+        assert_eq!(
+            next("0 9 * * 7#1", denver(2026, 3, 2, 0, 0)),
+            denver(2026, 4, 5, 9, 0)
+        );
+    }
+
+    #[test]
+    fn runs_skipped_times_after_the_gap() {
+        // Clocks in Denver jump from 02:00 to 03:00 on 2026-03-08.
+        let time = next("30 2 * * *", denver(2026, 3, 8, 0, 0));
+        assert_eq!(time, utc(2026, 3, 8, 9, 0));
+        assert_eq!(time, denver(2026, 3, 8, 3, 0));
+        assert_eq!(next("30 2 * * *", time), denver(2026, 3, 9, 2, 30));
+    }
+
+    #[test]
+    fn runs_repeated_times_once() {
+        // Clocks in Denver fall back from 02:00 to 01:00 on 2026-11-01.
+        let time = next("30 1 * * *", denver(2026, 10, 31, 12, 0));
+        assert_eq!(time, utc(2026, 11, 1, 7, 30));
+        assert_eq!(next("30 1 * * *", time), utc(2026, 11, 2, 8, 30));
+    }
+
+    #[test]
+    fn runs_wildcard_hours_through_repeated_times() {
+        // 01:00 to 02:00 happens twice in Denver on 2026-11-01, first at 07:00 UTC and again at 08:00 UTC.
+        let mut time = denver(2026, 11, 1, 0, 50);
+        let mut times = Vec::new();
+        for _ in 0..10 {
+            time = next("*/15 * * * *", time);
+            times.push(time);
+        }
+        let expected: Vec<SystemTime> = (0..10)
+            .map(|quarter| utc(2026, 11, 1, 7, 0) + Duration::from_secs(quarter * 15 * 60))
+            .collect();
+        assert_eq!(times, expected);
+    }
+
+    #[test]
+    fn rejects_zero_intervals() {
+        let mut schedule = Schedule::default();
+        let job = Job::new("Never", When::Every { seconds: 0 }, "Pump", Vec::new());
+        assert!(matches!(
+            schedule.add_job(job),
+            Err(ScheduleError::ZeroInterval(name)) if name == "Never"
+        ));
+        assert_eq!(
+            When::Every { seconds: 0 }.next_after(utc(2026, 1, 1, 0, 0), Denver, None),
+            None
+        );
+    }
+
+    #[test]
+    fn runs_every_interval_from_the_epoch() {
+        let every = When::Every { seconds: 3600 };
+        assert_eq!(
+            every.next_after(utc(2026, 3, 8, 10, 15), Denver, None),
+            Some(utc(2026, 3, 8, 11, 0))
+        );
+        assert_eq!(
+            every.next_after(utc(2026, 3, 8, 11, 0), Denver, None),
+            Some(utc(2026, 3, 8, 12, 0))
+        );
+    }
+
+    #[test]
+    fn finds_sunrise_and_sunset() {
+        // Greenwich on the 2026 summer solstice: sunrise 03:43 UTC, sunset 20:21 UTC.
+        let greenwich = Location {
+            latitude: 51.4769,
+            longitude: 0.0,
+        };
+        let date = NaiveDate::from_ymd_opt(2026, 6, 21).unwrap();
+        assert!(minutes_apart(greenwich.sunrise(date).unwrap(), utc(2026, 6, 21, 3, 43)) <= 3);
+        assert!(minutes_apart(greenwich.sunset(date).unwrap(), utc(2026, 6, 21, 20, 21)) <= 3);
+
+        // No sunrise in Tromsø in December.
+        let tromso = Location {
+            latitude: 69.65,
+            longitude: 18.96,
+        };
+        assert_eq!(
+            tromso.sunrise(NaiveDate::from_ymd_opt(2026, 12, 21).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn offsets_sun_events() {
+        // Denver on the 2026 summer solstice: sunset 20:31 MDT.
+        let location = Location {
+            latitude: 39.7392,
+            longitude: -104.9903,
+        };
+        let before_sunset = When::Sun {
+            event: SunEvent::Sunset,
+            offset_minutes: -15,
+        };
+        let time = before_sunset
+            .next_after(denver(2026, 6, 21, 12, 0), Denver, Some(&location))
+            .unwrap();
+        assert!(minutes_apart(time, denver(2026, 6, 21, 20, 16)) <= 3);
+        assert_eq!(
+            before_sunset.next_after(denver(2026, 6, 21, 12, 0), Denver, None),
+            None
+        );
+    }
+}
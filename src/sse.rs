@@ -1,11 +1,12 @@
 #![allow(non_snake_case)]
 
-use super::common;
+use super::{common, Client};
 use derive_getters::Getters;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// The type of device this "thing" represents.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DeviceType {
     /// This is the root gateway. Not supported.
     Gateway,
@@ -23,12 +24,75 @@ pub enum DeviceType {
     Switch,
 }
 
+/// The gateway's view of whether a thing is usable.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ThingStatus {
+    /// The thing has not been set up yet.
+    Uninitialized,
+    /// The thing is being set up.
+    Initializing,
+    /// The thing is working.
+    Online,
+    /// The thing can not be communicated with.
+    Offline,
+    /// The thing is being removed.
+    Removing,
+    /// The thing has been removed.
+    Removed,
+    /// The gateway can not tell the thing's state yet, or reported a status this crate does not know.
+    #[serde(other)]
+    Unknown,
+}
+
+/// The status of a thing along with the reason for it.
+#[derive(Getters, Deserialize, Debug)]
+pub struct StatusInfo {
+    /// The current status.
+    status: ThingStatus,
+    /// More detail on the status, ex: "NONE" or "COMMUNICATION_ERROR".
+    statusDetail: String,
+    /// A human readable description, mostly set for errors.
+    description: Option<String>,
+}
+
 /// How the device is configured to work in LCI.
 #[derive(Getters, Deserialize, Debug)]
+#[serde(from = "HashMap<String, serde_json::Value>")]
 pub struct Configuration {
     /// The device type as an integer.
     /// Not set for LCI Gateway thing.
     deviceType: Option<f32>,
+    /// Every configuration parameter, including the device type.
+    parameters: HashMap<String, serde_json::Value>,
+}
+
+/// A single value or control a thing exposes, ex: "tank_level" or "switch".
+#[derive(Getters, Deserialize, Debug)]
+pub struct Channel {
+    /// The channel's unique ID, the thing's UID followed by the channel ID.
+    uid: String,
+    /// The channel ID within the thing, ex: "tank_level".
+    id: String,
+    /// The type of channel, ex: "idsmyrv:tank_level".
+    channelTypeUID: Option<String>,
+    /// The type of item this channel can be linked to, ex: "Number" or "Switch".
+    itemType: Option<String>,
+    /// Either "STATE" or "TRIGGER".
+    kind: Option<String>,
+    /// The name of this channel.
+    label: Option<String>,
+    /// A description of this channel.
+    description: Option<String>,
+    /// The names of the items that hold this channel's state.
+    #[serde(default)]
+    linkedItems: Vec<String>,
+    /// Additional properties of this channel.
+    #[serde(default)]
+    properties: HashMap<String, String>,
+    /// How this channel is configured.
+    #[serde(default)]
+    configuration: HashMap<String, serde_json::Value>,
 }
 
 /// The "thing" in the LCI system.
@@ -38,14 +102,37 @@ pub struct Thing {
     label: String,
     /// The given UID- unique ID.
     UID: String,
+    /// The type of thing in the gateway, ex: "idsmyrv:tank-sensor".
+    thingTypeUID: Option<String>,
+    /// The UID of the bridge this thing is connected through. Not set for the gateway itself.
+    bridgeUID: Option<String>,
+    /// Whether the gateway can talk to the thing.
+    statusInfo: Option<StatusInfo>,
     /// What type of device this is configured as.
-    configuration: Configuration, // TODO: can't figure out how to make this optional
+    configuration: Option<Configuration>,
+    /// Additional properties such as firmware or product IDs.
+    #[serde(default)]
+    properties: HashMap<String, String>,
+    /// The values and controls this thing exposes.
+    #[serde(default)]
+    channels: Vec<Channel>,
+    /// The gateway connection this thing was fetched through.
+    #[serde(skip)]
+    client: Client,
 }
 
 impl Thing {
+    pub(crate) fn set_client(&mut self, client: Client) {
+        self.client = client;
+    }
+
     /// Returns the type of device this "thing" is configured as.
     pub fn get_type(&self) -> Option<DeviceType> {
-        match self.configuration().deviceType() {
+        let device_type = self
+            .configuration()
+            .as_ref()
+            .and_then(|configuration| *configuration.deviceType());
+        match &device_type {
             None => Some(DeviceType::Gateway),
             x if x == &Some(10.0) => Some(DeviceType::Tank),
             x if x == &Some(13.0) => Some(DeviceType::RgbLights),
@@ -57,8 +144,193 @@ impl Thing {
         }
     }
 
+    /// Returns the channel with the given ID, ex: "tank_level".
+    pub fn channel(&self, id: &str) -> Option<&Channel> {
+        self.channels().iter().find(|channel| channel.id() == id)
+    }
+
+    /// Returns the name of the item holding the given channel's state, ex: "tank_level".
+    /// Uses the item linked to the channel in the gateway, falling back to the gateway's naming
+    /// convention when the channel is not linked or the gateway did not list any channels.
+    /// Returns None when the gateway listed channels but not the requested one.
+    pub fn item_name(&self, channel_id: &str) -> Option<String> {
+        match self.channel(channel_id) {
+            Some(channel) => match channel.linkedItems().first() {
+                Some(item) => Some(item.clone()),
+                None => Some(self.default_item_name(channel_id)),
+            },
+            None if self.channels().is_empty() => Some(self.default_item_name(channel_id)),
+            None => None,
+        }
+    }
+
+    fn default_item_name(&self, channel_id: &str) -> String {
+        format!("{}_{}", self.UID().replace([':', '-'], "_"), channel_id)
+    }
+
     /// Returns the device's online state.
     pub async fn online(&self) -> Result<common::OnlineState, common::OnlineStateConversionError> {
         common::get_online_state(self).await
     }
 }
+
+impl From<HashMap<String, serde_json::Value>> for Configuration {
+    fn from(parameters: HashMap<String, serde_json::Value>) -> Self {
+        let deviceType = parameters
+            .get("deviceType")
+            .and_then(|value| value.as_f64())
+            .map(|value| value as f32);
+        Self {
+            deviceType,
+            parameters,
+        }
+    }
+}
+
+impl std::fmt::Display for ThingStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            ThingStatus::Uninitialized => "Uninitialized".to_string(),
+            ThingStatus::Initializing => "Initializing".to_string(),
+            ThingStatus::Online => "Online".to_string(),
+            ThingStatus::Offline => "Offline".to_string(),
+            ThingStatus::Removing => "Removing".to_string(),
+            ThingStatus::Removed => "Removed".to_string(),
+            ThingStatus::Unknown => "Unknown".to_string(),
+        };
+        write!(f, "{}", status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tank as the gateway lists it, with its level channel linked to an item that doesn't follow the
+    /// naming convention.
+    fn tank() -> Thing {
+        serde_json::from_value(serde_json::json!({
+            "label": "Fresh Water",
+            "UID": "idsmyrv:tank-sensor:0001",
+            "thingTypeUID": "idsmyrv:tank-sensor",
+            "bridgeUID": "idsmyrv:gateway:main",
+            "statusInfo": {"status": "ONLINE", "statusDetail": "NONE"},
+            "configuration": {"deviceType": 10, "deviceInstance": 3, "name": "Fresh"},
+            "properties": {"firmware": "1.2"},
+            "channels": [
+                {
+                    "uid": "idsmyrv:tank-sensor:0001:tank_level",
+                    "id": "tank_level",
+                    "channelTypeUID": "idsmyrv:tank_level",
+                    "itemType": "Number",
+                    "kind": "STATE",
+                    "label": "Level",
+                    "linkedItems": ["FreshWaterLevel"],
+                    "configuration": {"offset": 0},
+                },
+                {
+                    "uid": "idsmyrv:tank-sensor:0001:online",
+                    "id": "online",
+                },
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn deserializes_thing_metadata() {
+        let thing = tank();
+        assert_eq!(thing.get_type(), Some(DeviceType::Tank));
+        assert_eq!(thing.thingTypeUID().as_deref(), Some("idsmyrv:tank-sensor"));
+        assert_eq!(thing.bridgeUID().as_deref(), Some("idsmyrv:gateway:main"));
+        assert_eq!(thing.properties()["firmware"], "1.2");
+
+        let status = thing.statusInfo().as_ref().unwrap();
+        assert_eq!(*status.status(), ThingStatus::Online);
+        assert_eq!(status.statusDetail(), "NONE");
+        assert_eq!(*status.description(), None);
+
+        let configuration = thing.configuration().as_ref().unwrap();
+        assert_eq!(*configuration.deviceType(), Some(10.0));
+        assert_eq!(configuration.parameters()["deviceInstance"], 3);
+        assert_eq!(configuration.parameters()["name"], "Fresh");
+
+        assert_eq!(thing.channels().len(), 2);
+        let level = thing.channel("tank_level").unwrap();
+        assert_eq!(level.uid(), "idsmyrv:tank-sensor:0001:tank_level");
+        assert_eq!(level.itemType().as_deref(), Some("Number"));
+        assert_eq!(level.kind().as_deref(), Some("STATE"));
+        assert_eq!(level.linkedItems(), &["FreshWaterLevel"]);
+        assert_eq!(level.configuration()["offset"], 0);
+        let online = thing.channel("online").unwrap();
+        assert!(online.linkedItems().is_empty());
+        assert!(online.properties().is_empty());
+        assert_eq!(*online.label(), None);
+    }
+
+    #[test]
+    fn deserializes_the_gateway_and_unknown_statuses() {
+        let gateway: Thing = serde_json::from_value(serde_json::json!({
+            "label": "LCI Gateway",
+            "UID": "idsmyrv:gateway:main",
+            "statusInfo": {
+                "status": "SUSPENDED",
+                "statusDetail": "COMMUNICATION_ERROR",
+                "description": "No reply",
+            },
+        }))
+        .unwrap();
+        assert_eq!(gateway.get_type(), Some(DeviceType::Gateway));
+        assert!(gateway.configuration().is_none());
+        assert!(gateway.channels().is_empty());
+        let status = gateway.statusInfo().as_ref().unwrap();
+        assert_eq!(*status.status(), ThingStatus::Unknown);
+        assert_eq!(status.description().as_deref(), Some("No reply"));
+
+        let unsupported: Thing = serde_json::from_value(serde_json::json!({
+            "label": "Awning",
+            "UID": "idsmyrv:awning:0009",
+            "configuration": {"deviceType": 99},
+        }))
+        .unwrap();
+        assert_eq!(unsupported.get_type(), None);
+    }
+
+    #[test]
+    fn resolves_items_through_channel_links() {
+        let thing = tank();
+        assert_eq!(
+            thing.item_name("tank_level").as_deref(),
+            Some("FreshWaterLevel")
+        );
+        // A listed channel without a linked item falls back to the naming convention.
+        assert_eq!(
+            thing.item_name("online").as_deref(),
+            Some("idsmyrv_tank_sensor_0001_online")
+        );
+    }
+
+    #[test]
+    fn names_items_by_convention_without_channels() {
+        let thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": "Fresh Water",
+            "UID": "idsmyrv:tank-sensor:0001",
+            "configuration": {"deviceType": 10},
+        }))
+        .unwrap();
+        assert_eq!(
+            thing.item_name("tank_level").as_deref(),
+            Some("idsmyrv_tank_sensor_0001_tank_level")
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_channels_the_thing_does_not_have() {
+        let thing = tank();
+        assert_eq!(thing.item_name("dimmer"), None);
+        assert!(matches!(
+            common::get_field(&thing, "dimmer").await,
+            Err(common::GetFailure::MissingChannel(channel)) if channel == "dimmer"
+        ));
+    }
+}
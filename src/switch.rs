@@ -1,4 +1,5 @@
 use super::{common, DeviceType, Thing};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// For things that go on and off.
@@ -8,7 +9,7 @@ pub struct Switch {
 }
 
 /// The only two states of a switch.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SwitchState {
     Off,
     On,
@@ -42,6 +43,11 @@ impl Switch {
         self.thing.label().clone()
     }
 
+    /// Returns the unique ID of the device in the LCI gateway.
+    pub fn uid(&self) -> String {
+        self.thing.UID().clone()
+    }
+
     /// Returns the device's online state.
     pub async fn online(&self) -> Result<common::OnlineState, common::OnlineStateConversionError> {
         common::get_online_state(&self.thing).await
@@ -1,9 +1,80 @@
-use super::{common, DeviceType, Thing};
+use super::{common, Configuration, DeviceType, Thing};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 use thiserror::Error;
 
+const LITRES_PER_GALLON: f32 = 3.785_411_8;
+
 /// A connected tank.
 pub struct Tank {
     thing: Thing,
+    kind: Option<TankKind>,
+    capacity: Option<Volume>,
+    calibration: Option<Calibration>,
+}
+
+/// What a tank holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TankKind {
+    /// Drinking water.
+    Fresh,
+    /// Sink and shower waste water.
+    Grey,
+    /// Toilet waste water.
+    Black,
+    /// Liquid propane.
+    Lp,
+    /// Fuel for the generator or engine.
+    Fuel,
+}
+
+/// A unit of volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VolumeUnit {
+    /// US gallons.
+    Gallons,
+    /// Litres.
+    Litres,
+}
+
+/// An amount of liquid, ex: a tank's capacity.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Volume {
+    /// How much, in `unit`.
+    pub amount: f32,
+    /// The unit of `amount`.
+    pub unit: VolumeUnit,
+}
+
+/// Settings for a tank the gateway does not know, usually loaded from a config file keyed by label or UID.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TankConfig {
+    /// What the tank holds. Detected from the label when not set.
+    #[serde(default)]
+    pub kind: Option<TankKind>,
+    /// How much the tank holds when full.
+    #[serde(default)]
+    pub capacity: Option<Volume>,
+    /// Maps the sensor's raw percentage to the true fill.
+    #[serde(default)]
+    pub calibration: Option<Calibration>,
+}
+
+/// Tank settings keyed by label or UID, usually loaded from a JSON file, ex:
+/// `{"Fresh Water": {"capacity": {"amount": 40, "unit": "Gallons"}, "calibration": [[0, 0], [33, 20], [66, 55], [100, 100]]}}`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TankConfigs {
+    tanks: HashMap<String, TankConfig>,
+}
+
+/// A piecewise-linear map from a tank sensor's raw percentage to the true fill percentage,
+/// for coarse sensors and oddly shaped tanks. Serialized as `[[raw, true], ...]`, ex: `[[0, 0], [33, 20], [66, 55], [100, 100]]`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(u8, f32)>", into = "Vec<(u8, f32)>")]
+pub struct Calibration {
+    points: Vec<(u8, f32)>,
 }
 
 impl Tank {
@@ -11,7 +82,12 @@ impl Tank {
     pub fn new(thing: Thing) -> Result<Self, TankError> {
         let thing_type = thing.get_type();
         if thing_type == Some(DeviceType::Tank) {
-            Ok(Self { thing })
+            Ok(Self {
+                thing,
+                kind: None,
+                capacity: None,
+                calibration: None,
+            })
         } else {
             Err(TankError::InvalidDeviceType(thing_type))
         }
@@ -27,13 +103,324 @@ impl Tank {
         self.thing.label().clone()
     }
 
-    /// Gets the current percentage of the tank. Note accuracy depends on your sensors.
+    /// Returns the unique ID of the device in the LCI gateway.
+    pub fn uid(&self) -> String {
+        self.thing.UID().clone()
+    }
+
+    /// Applies the kind, capacity and calibration from the config. Settings missing from the config are left as they are.
+    pub fn configure(&mut self, config: &TankConfig) {
+        if config.kind.is_some() {
+            self.kind = config.kind;
+        }
+        if config.capacity.is_some() {
+            self.capacity = config.capacity;
+        }
+        if config.calibration.is_some() {
+            self.calibration = config.calibration.clone();
+        }
+    }
+
+    /// Returns the calibration applied to `level`, if set.
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.calibration.as_ref()
+    }
+
+    /// Sets the calibration applied to `level`.
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = Some(calibration);
+    }
+
+    /// Returns what the tank holds, as configured, else from the thing's configuration in the gateway,
+    /// else detected from the label.
+    pub fn kind(&self) -> Option<TankKind> {
+        self.kind
+            .or_else(|| {
+                self.thing
+                    .configuration()
+                    .as_ref()
+                    .and_then(TankKind::from_configuration)
+            })
+            .or_else(|| TankKind::from_label(self.thing.label()))
+    }
+
+    /// Sets what the tank holds, overriding the label.
+    pub fn set_kind(&mut self, kind: TankKind) {
+        self.kind = Some(kind);
+    }
+
+    /// Returns how much the tank holds when full, if configured.
+    pub fn capacity(&self) -> Option<Volume> {
+        self.capacity
+    }
+
+    /// Sets how much the tank holds when full.
+    pub fn set_capacity(&mut self, capacity: Volume) {
+        self.capacity = Some(capacity);
+    }
+
+    /// Returns how much is in the tank, in the unit of its capacity.
+    pub async fn volume(&self) -> Result<Volume, TankVolumeError> {
+        let capacity = self.capacity.ok_or(TankVolumeError::NoCapacity)?;
+        let raw = self.raw_level().await?;
+        let level = match &self.calibration {
+            Some(calibration) => calibration.apply(raw.value()),
+            None => raw.value() as f32,
+        };
+        Ok(capacity.scale(level / 100.0))
+    }
+
+    /// Gets the current percentage of the tank, calibrated when a calibration is set.
+    /// Note accuracy depends on your sensors.
     pub async fn level(&self) -> Result<common::Percentage, TankLevelError> {
+        let raw = self.raw_level().await?;
+        Ok(match &self.calibration {
+            Some(calibration) => {
+                common::Percentage::new(calibration.apply(raw.value()).round() as u8)
+            }
+            None => raw,
+        })
+    }
+
+    /// Gets the percentage of the tank as the sensor reports it, without calibration.
+    pub async fn raw_level(&self) -> Result<common::Percentage, TankLevelError> {
         let string = common::get_field(&self.thing, "tank_level").await?;
         let val = string
             .parse::<u8>()
             .map_err(|e| TankLevelError::Parse(string, e))?;
-        Ok(common::Percentage::new(val))
+        common::Percentage::try_from(val).map_err(TankLevelError::OutOfRange)
+    }
+}
+
+impl TankConfigs {
+    /// Create an empty set of settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the settings from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TankConfigError> {
+        let text = std::fs::read_to_string(path).map_err(TankConfigError::Io)?;
+        serde_json::from_str(&text).map_err(TankConfigError::SerdeJsonConversion)
+    }
+
+    /// Adds the settings for the tank with the label or UID.
+    pub fn with_tank(mut self, tank: impl Into<String>, config: TankConfig) -> Self {
+        self.tanks.insert(tank.into(), config);
+        self
+    }
+
+    /// Returns true when no tank has settings.
+    pub fn is_empty(&self) -> bool {
+        self.tanks.is_empty()
+    }
+
+    /// Returns the settings for the tank, matched by UID and else by label, ignoring case.
+    pub fn get(&self, tank: &Tank) -> Option<&TankConfig> {
+        self.tanks.get(tank.thing.UID()).or_else(|| {
+            self.tanks
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(tank.thing.label()))
+                .map(|(_, config)| config)
+        })
+    }
+
+    /// Applies the tank's settings, if it has any.
+    pub fn configure(&self, tank: &mut Tank) {
+        if let Some(config) = self.get(tank) {
+            tank.configure(config);
+        }
+    }
+}
+
+impl TankKind {
+    /// Returns the kind named in a label such as "Fresh Water", "Gray Tank" or "Generator Fuel".
+    pub fn from_label(label: &str) -> Option<Self> {
+        let label = label.to_lowercase();
+        let words: Vec<&str> = label
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let has = |names: &[&str]| words.iter().any(|word| names.contains(word));
+        if has(&["fresh", "water"]) && !has(&["grey", "gray", "black", "waste"]) {
+            Some(TankKind::Fresh)
+        } else if has(&["grey", "gray", "galley"]) {
+            Some(TankKind::Grey)
+        } else if has(&["black", "waste", "sewer"]) {
+            Some(TankKind::Black)
+        } else if has(&["lp", "lpg", "propane"]) {
+            Some(TankKind::Lp)
+        } else if has(&["fuel", "diesel", "gasoline"]) {
+            Some(TankKind::Fuel)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the kind named in the thing's configuration parameters, ex: `"tankType": "GREY"` or
+    /// `"fluidType": "Black Water"`. Only text parameters whose name mentions a tank, fluid, kind or type are read.
+    pub fn from_configuration(configuration: &Configuration) -> Option<Self> {
+        let mut parameters: Vec<(&String, &str)> = configuration
+            .parameters()
+            .iter()
+            .filter(|(name, _)| {
+                let name = name.to_lowercase();
+                ["tank", "fluid", "kind", "type"]
+                    .iter()
+                    .any(|word| name.contains(word))
+            })
+            .filter_map(|(name, value)| Some((name, value.as_str()?)))
+            .collect();
+        // Parameters are unordered, so the first match has to be picked the same way every time.
+        parameters.sort();
+        parameters.iter().find_map(|(_, value)| {
+            Self::from_string(value.to_string())
+                .ok()
+                .or_else(|| Self::from_label(value))
+        })
+    }
+
+    /// Returns TankKind enum value from the string value.
+    /// Accepted values: ["FRESH", "GREY", "GRAY", "BLACK", "LP", "FUEL"]
+    pub fn from_string(string: String) -> Result<Self, TankKindConversionError> {
+        match string.to_uppercase().as_str() {
+            "FRESH" => Ok(TankKind::Fresh),
+            "GREY" | "GRAY" => Ok(TankKind::Grey),
+            "BLACK" => Ok(TankKind::Black),
+            "LP" => Ok(TankKind::Lp),
+            "FUEL" => Ok(TankKind::Fuel),
+            _ => Err(TankKindConversionError::UnknownValue(string)),
+        }
+    }
+}
+
+impl std::fmt::Display for TankKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            TankKind::Fresh => "Fresh",
+            TankKind::Grey => "Grey",
+            TankKind::Black => "Black",
+            TankKind::Lp => "LP",
+            TankKind::Fuel => "Fuel",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+impl Volume {
+    /// A volume in US gallons.
+    pub fn gallons(amount: f32) -> Self {
+        Self {
+            amount,
+            unit: VolumeUnit::Gallons,
+        }
+    }
+
+    /// A volume in litres.
+    pub fn litres(amount: f32) -> Self {
+        Self {
+            amount,
+            unit: VolumeUnit::Litres,
+        }
+    }
+
+    /// Returns the volume in US gallons.
+    pub fn in_gallons(&self) -> f32 {
+        match self.unit {
+            VolumeUnit::Gallons => self.amount,
+            VolumeUnit::Litres => self.amount / LITRES_PER_GALLON,
+        }
+    }
+
+    /// Returns the volume in litres.
+    pub fn in_litres(&self) -> f32 {
+        match self.unit {
+            VolumeUnit::Gallons => self.amount * LITRES_PER_GALLON,
+            VolumeUnit::Litres => self.amount,
+        }
+    }
+
+    /// Returns the volume multiplied by the factor, in the same unit.
+    pub fn scale(&self, factor: f32) -> Self {
+        Self {
+            amount: self.amount * factor,
+            unit: self.unit,
+        }
+    }
+}
+
+impl Calibration {
+    /// Create a calibration from (raw, true) pairs. Needs at least two points with different raw values,
+    /// and true values between 0 and 100. A raw value given twice has to map to the same true value.
+    pub fn new(mut points: Vec<(u8, f32)>) -> Result<Self, CalibrationError> {
+        points.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        if let Some(pair) = points
+            .windows(2)
+            .find(|pair| pair[0].0 == pair[1].0 && pair[0].1 != pair[1].1)
+        {
+            return Err(CalibrationError::Conflicting(
+                pair[0].0, pair[0].1, pair[1].1,
+            ));
+        }
+        points.dedup();
+        if points.len() < 2 {
+            return Err(CalibrationError::TooFewPoints);
+        }
+        if let Some((raw, fill)) = points
+            .iter()
+            .find(|(raw, fill)| *raw > 100 || !(0.0..=100.0).contains(fill))
+        {
+            return Err(CalibrationError::OutOfRange(*raw, *fill));
+        }
+        Ok(Self { points })
+    }
+
+    /// Returns the (raw, true) pairs, ordered by raw value.
+    pub fn points(&self) -> &[(u8, f32)] {
+        &self.points
+    }
+
+    /// Returns the true fill for a raw percentage, interpolating between points and
+    /// holding the first and last true values outside them.
+    pub fn apply(&self, raw: u8) -> f32 {
+        let raw = raw as f32;
+        let mut previous = self.points[0];
+        if raw <= previous.0 as f32 {
+            return previous.1;
+        }
+        for point in &self.points[1..] {
+            let (point_raw, point_fill) = (point.0 as f32, point.1);
+            if raw <= point_raw {
+                let (previous_raw, previous_fill) = (previous.0 as f32, previous.1);
+                let position = (raw - previous_raw) / (point_raw - previous_raw);
+                return previous_fill + position * (point_fill - previous_fill);
+            }
+            previous = *point;
+        }
+        previous.1
+    }
+}
+
+impl TryFrom<Vec<(u8, f32)>> for Calibration {
+    type Error = CalibrationError;
+
+    fn try_from(points: Vec<(u8, f32)>) -> Result<Self, Self::Error> {
+        Self::new(points)
+    }
+}
+
+impl From<Calibration> for Vec<(u8, f32)> {
+    fn from(calibration: Calibration) -> Self {
+        calibration.points
+    }
+}
+
+impl std::fmt::Display for Volume {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.unit {
+            VolumeUnit::Gallons => write!(f, "{:.1} gal", self.amount),
+            VolumeUnit::Litres => write!(f, "{:.1} L", self.amount),
+        }
     }
 }
 
@@ -54,6 +441,9 @@ pub enum TankLevelError {
     /// The response from the LCI gateway could not be understood.
     #[error("The given value '{1}' could not be converted to a percentage.")]
     Parse(String, std::num::ParseIntError),
+    /// The LCI gateway reported a level above 100.
+    #[error("{0}")]
+    OutOfRange(common::PercentageConversionError),
 }
 
 impl From<common::GetFailure> for TankLevelError {
@@ -61,3 +451,282 @@ impl From<common::GetFailure> for TankLevelError {
         Self::GetFailure(error)
     }
 }
+
+/// The tank volume could not be worked out.
+#[derive(Debug, Error)]
+pub enum TankVolumeError {
+    /// The tank has no capacity configured.
+    #[error("The tank has no capacity configured.")]
+    NoCapacity,
+    /// See `TankLevelError`.
+    #[error("The tank level could not be read. {0}")]
+    Level(TankLevelError),
+}
+
+impl From<TankLevelError> for TankVolumeError {
+    fn from(error: TankLevelError) -> Self {
+        Self::Level(error)
+    }
+}
+
+/// Returned when a string is not a tank kind.
+#[derive(Debug, Error)]
+pub enum TankKindConversionError {
+    /// The provided value could not be mapped to the TankKind enum.
+    #[error("The given value '{0}' could not be converted to a TankKind.")]
+    UnknownValue(String),
+}
+
+/// The calibration points can not make a calibration.
+#[derive(Debug, Error)]
+pub enum CalibrationError {
+    /// Fewer than two points with different raw values were given.
+    #[error("A calibration needs at least two points with different raw values.")]
+    TooFewPoints,
+    /// A raw value is above 100 or a true value is not between 0 and 100.
+    #[error("The calibration point ({0}, {1}) is not between 0 and 100.")]
+    OutOfRange(u8, f32),
+    /// A raw value is given twice with different true values.
+    #[error("The raw value {0} is calibrated to both {1} and {2}.")]
+    Conflicting(u8, f32, f32),
+}
+
+/// The tank settings could not be loaded.
+#[derive(Debug, Error)]
+pub enum TankConfigError {
+    /// The file could not be read.
+    #[error("The tank settings could not be read. {0}")]
+    Io(std::io::Error),
+    /// The file is not valid tank settings.
+    #[error("The tank settings could not be converted. {0}")]
+    SerdeJsonConversion(serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Client;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers every item read with the state.
+    async fn gateway(state: &'static str) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let read = socket.read(&mut buffer).await.unwrap();
+                assert!(read > 0);
+                let body = format!("{{\"state\": \"{}\"}}", state);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    async fn tank_reading(state: &'static str) -> Tank {
+        let mut thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": "Fresh Water",
+            "UID": "idsmyrv:tank-sensor:0001",
+            "configuration": {"deviceType": 10},
+        }))
+        .unwrap();
+        thing.set_client(gateway(state).await);
+        Tank::new(thing).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reads_levels_up_to_100() {
+        assert_eq!(
+            tank_reading("100").await.raw_level().await.unwrap().value(),
+            100
+        );
+        assert!(matches!(
+            tank_reading("150").await.raw_level().await,
+            Err(TankLevelError::OutOfRange(
+                common::PercentageConversionError::OutOfRange(150)
+            ))
+        ));
+        assert!(matches!(
+            tank_reading("full").await.raw_level().await,
+            Err(TankLevelError::Parse(value, _)) if value == "full"
+        ));
+    }
+
+    #[test]
+    fn detects_kinds_from_labels() {
+        assert_eq!(TankKind::from_label("Fresh Water"), Some(TankKind::Fresh));
+        assert_eq!(TankKind::from_label("Waste Water"), Some(TankKind::Black));
+        assert_eq!(TankKind::from_label("Gray Tank"), Some(TankKind::Grey));
+        assert_eq!(TankKind::from_label("Galley Grey"), Some(TankKind::Grey));
+        assert_eq!(TankKind::from_label("Black Tank"), Some(TankKind::Black));
+        assert_eq!(TankKind::from_label("LP Tank"), Some(TankKind::Lp));
+        assert_eq!(TankKind::from_label("Generator Fuel"), Some(TankKind::Fuel));
+        assert_eq!(TankKind::from_label("Tank 2"), None);
+    }
+
+    #[test]
+    fn detects_kinds_from_configuration() {
+        let configuration = |parameters: serde_json::Value| -> Configuration {
+            serde_json::from_value(parameters).unwrap()
+        };
+        assert_eq!(
+            TankKind::from_configuration(&configuration(
+                serde_json::json!({"deviceType": 10, "tankType": "GRAY"})
+            )),
+            Some(TankKind::Grey)
+        );
+        assert_eq!(
+            TankKind::from_configuration(&configuration(
+                serde_json::json!({"deviceType": 10, "fluidType": "Black Water"})
+            )),
+            Some(TankKind::Black)
+        );
+        assert_eq!(
+            TankKind::from_configuration(&configuration(
+                serde_json::json!({"deviceType": 10, "deviceName": "Fresh Water"})
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn configured_kind_comes_first() {
+        let thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": "Tank 1",
+            "UID": "tank-1",
+            "configuration": {"deviceType": 10, "tankType": "FRESH"},
+        }))
+        .unwrap();
+        let mut tank = Tank::new(thing).unwrap();
+        assert_eq!(tank.kind(), Some(TankKind::Fresh));
+        tank.set_kind(TankKind::Grey);
+        assert_eq!(tank.kind(), Some(TankKind::Grey));
+    }
+
+    #[test]
+    fn converts_volumes() {
+        let gallons = Volume::gallons(40.0);
+        assert!((gallons.in_litres() - 151.416).abs() < 0.001);
+        assert_eq!(gallons.in_gallons(), 40.0);
+        let litres = Volume::litres(100.0);
+        assert!((litres.in_gallons() - 26.417).abs() < 0.001);
+        assert_eq!(litres.in_litres(), 100.0);
+        assert_eq!(gallons.scale(0.25), Volume::gallons(10.0));
+        assert_eq!(litres.scale(0.5).to_string(), "50.0 L");
+        assert_eq!(gallons.to_string(), "40.0 gal");
+    }
+
+    #[test]
+    fn calibration_interpolates_between_points() {
+        let calibration =
+            Calibration::new(vec![(100, 100.0), (0, 0.0), (33, 20.0), (66, 55.0)]).unwrap();
+        assert_eq!(calibration.points()[1], (33, 20.0));
+        assert_eq!(calibration.apply(0), 0.0);
+        assert_eq!(calibration.apply(33), 20.0);
+        assert!((calibration.apply(50) - 38.030_3).abs() < 0.001);
+        assert_eq!(calibration.apply(100), 100.0);
+    }
+
+    #[test]
+    fn calibration_rejects_conflicting_points() {
+        assert!(matches!(
+            Calibration::new(vec![(0, 0.0), (50, 40.0), (50, 45.0), (100, 100.0)]),
+            Err(CalibrationError::Conflicting(50, _, _))
+        ));
+        assert!(
+            serde_json::from_str::<Calibration>("[[0, 0], [50, 40], [50, 45], [100, 100]]")
+                .is_err()
+        );
+        let repeated = Calibration::new(vec![(0, 0.0), (50, 40.0), (50, 40.0), (100, 100.0)]);
+        assert_eq!(repeated.unwrap().points().len(), 3);
+    }
+
+    #[test]
+    fn calibration_rejects_bad_points() {
+        assert!(matches!(
+            Calibration::new(vec![(50, 40.0), (50, 40.0)]),
+            Err(CalibrationError::TooFewPoints)
+        ));
+        assert!(matches!(
+            Calibration::new(vec![(0, 0.0), (101, 100.0)]),
+            Err(CalibrationError::OutOfRange(101, _))
+        ));
+    }
+
+    fn tank(label: &str, uid: &str) -> Tank {
+        let thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": label,
+            "UID": uid,
+            "configuration": {"deviceType": 10},
+        }))
+        .unwrap();
+        Tank::new(thing).unwrap()
+    }
+
+    #[test]
+    fn tank_configs_match_by_uid_or_label() {
+        let configs = TankConfigs::new()
+            .with_tank(
+                "fresh water",
+                TankConfig {
+                    capacity: Some(Volume::gallons(40.0)),
+                    ..Default::default()
+                },
+            )
+            .with_tank(
+                "tank-2",
+                TankConfig {
+                    kind: Some(TankKind::Grey),
+                    ..Default::default()
+                },
+            );
+
+        let mut fresh = tank("Fresh Water", "tank-1");
+        configs.configure(&mut fresh);
+        assert_eq!(fresh.capacity(), Some(Volume::gallons(40.0)));
+        assert_eq!(fresh.kind(), Some(TankKind::Fresh));
+
+        let mut aux = tank("Aux Tank", "tank-2");
+        configs.configure(&mut aux);
+        assert_eq!(aux.kind(), Some(TankKind::Grey));
+        assert_eq!(aux.capacity(), None);
+
+        assert!(configs.get(&tank("Black Tank", "tank-3")).is_none());
+    }
+
+    #[test]
+    fn tank_configs_deserialize_by_name() {
+        let configs: TankConfigs = serde_json::from_str(
+            r#"{"Fresh Water": {"capacity": {"amount": 40, "unit": "Gallons"}, "calibration": [[0, 0], [100, 100]]},
+                "tank-2": {"kind": "Grey"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            configs,
+            TankConfigs::new()
+                .with_tank(
+                    "Fresh Water",
+                    TankConfig {
+                        capacity: Some(Volume::gallons(40.0)),
+                        calibration: Some(Calibration::new(vec![(0, 0.0), (100, 100.0)]).unwrap()),
+                        ..Default::default()
+                    }
+                )
+                .with_tank(
+                    "tank-2",
+                    TankConfig {
+                        kind: Some(TankKind::Grey),
+                        ..Default::default()
+                    }
+                )
+        );
+    }
+}
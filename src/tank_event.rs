@@ -0,0 +1,398 @@
+use super::{common, Tank, TankKind, TankLevelError};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// Something that happened to a tank, worked out from its level readings.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TankEvent {
+    /// The level dropped quickly, ex: the black tank was dumped.
+    Dumped {
+        /// The level before the drop.
+        from: common::Percentage,
+        /// The level after the drop.
+        to: common::Percentage,
+        /// When the end of the drop was read.
+        time: SystemTime,
+    },
+    /// The level rose quickly, ex: the fresh tank was filled.
+    Filled {
+        /// The level before the rise.
+        from: common::Percentage,
+        /// The level after the rise.
+        to: common::Percentage,
+        /// When the end of the rise was read.
+        time: SystemTime,
+    },
+    /// The level of a tank that should not drop on its own slowly dropped.
+    Leaking {
+        /// The highest level in the window.
+        from: common::Percentage,
+        /// The level now.
+        to: common::Percentage,
+        /// When the level was at `from`.
+        since: SystemTime,
+        /// When the drop was read.
+        time: SystemTime,
+    },
+}
+
+/// How big and fast a change has to be to count as an event.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TankEventThresholds {
+    /// Percentage points a dump or fill has to move the level by.
+    pub step: u8,
+    /// Percentage points per hour a dump or fill has to move the level at. A change is timed from when the
+    /// earlier level was last read. A change in the direction the tank moves when used, or either direction
+    /// for a tank of unknown kind, is timed from when the earlier level was first read instead, so a coarse
+    /// sensor stepping after hours at one level is too slow.
+    pub rate: f32,
+    /// How long the level has to hold before a dump or fill is reported. Changes in the same direction
+    /// until then are merged into one event.
+    pub settle: Duration,
+    /// Percentage points of slow drop that count as a leak.
+    pub leak_drop: u8,
+    /// How far back the slow drop is measured.
+    pub leak_window: Duration,
+}
+
+impl Default for TankEventThresholds {
+    fn default() -> Self {
+        Self {
+            step: 25,
+            rate: 60.0,
+            settle: Duration::from_secs(5 * 60),
+            leak_drop: 10,
+            leak_window: Duration::from_secs(6 * 60 * 60),
+        }
+    }
+}
+
+/// Consecutive changes in one direction, not reported yet.
+#[derive(Clone, Copy, Debug)]
+struct Movement {
+    from: u8,
+    to: u8,
+    /// If any of the changes reached the rate.
+    fast: bool,
+    time: SystemTime,
+}
+
+/// Turns a stream of one tank's levels into `TankEvent`s.
+///
+/// Dumps and fills are reported once the level has held for `TankEventThresholds::settle`, and leaks are only
+/// looked for while no change is waiting to be reported.
+#[derive(Clone, Debug)]
+pub struct TankEventDetector {
+    thresholds: TankEventThresholds,
+    detect_leaks: bool,
+    /// If the level rises as the tank is used, ex: a grey tank. None for a tank of unknown kind.
+    rises_with_use: Option<bool>,
+    readings: VecDeque<(SystemTime, u8)>,
+    /// The latest level and when it was first and last read.
+    level: Option<(u8, SystemTime, SystemTime)>,
+    movement: Option<Movement>,
+    leaking: bool,
+}
+
+impl TankEventDetector {
+    /// Create a detector for a tank of the given kind. Leaks are only looked for in grey and black tanks,
+    /// as other tanks drop whenever they are used.
+    pub fn new(kind: Option<TankKind>, thresholds: TankEventThresholds) -> Self {
+        Self {
+            thresholds,
+            detect_leaks: matches!(kind, Some(TankKind::Grey) | Some(TankKind::Black)),
+            rises_with_use: kind.map(|kind| matches!(kind, TankKind::Grey | TankKind::Black)),
+            readings: VecDeque::new(),
+            level: None,
+            movement: None,
+            leaking: false,
+        }
+    }
+
+    /// Create a detector for the tank with the default thresholds.
+    pub fn for_tank(tank: &Tank) -> Self {
+        Self::new(tank.kind(), TankEventThresholds::default())
+    }
+
+    /// Turns leak detection on or off, overriding the tank kind.
+    pub fn with_leak_detection(mut self, detect_leaks: bool) -> Self {
+        self.detect_leaks = detect_leaks;
+        self
+    }
+
+    /// Adds a reading and returns the event it completes, if any. Readings older than the latest one are ignored.
+    pub fn push(&mut self, time: SystemTime, level: common::Percentage) -> Option<TankEvent> {
+        let level = level.value();
+        let Some((last, first_read, last_read)) = self.level else {
+            self.level = Some((level, time, time));
+            self.readings.push_back((time, level));
+            return None;
+        };
+        if time < last_read {
+            return None;
+        }
+        self.readings.push_back((time, level));
+
+        let held = time.duration_since(first_read).unwrap_or_default();
+        let mut event = None;
+        if level == last {
+            self.level = Some((level, first_read, time));
+            if held >= self.thresholds.settle {
+                event = self.settle();
+            }
+        } else {
+            let dropping = level < last;
+            let since = if self.rises_with_use == Some(dropping) {
+                time.duration_since(last_read).unwrap_or_default()
+            } else {
+                held
+            };
+            let change = level.abs_diff(last) as f32;
+            let hours = since.as_secs_f32() / 3600.0;
+            let fast = hours == 0.0 || change / hours >= self.thresholds.rate;
+            match self.movement {
+                Some(movement)
+                    if held < self.thresholds.settle
+                        && (movement.to < movement.from) == dropping =>
+                {
+                    self.movement = Some(Movement {
+                        to: level,
+                        fast: movement.fast || fast,
+                        time,
+                        ..movement
+                    });
+                }
+                _ => {
+                    event = self.settle();
+                    self.movement = Some(Movement {
+                        from: last,
+                        to: level,
+                        fast,
+                        time,
+                    });
+                }
+            }
+            self.level = Some((level, time, time));
+        }
+
+        while let Some((first, _)) = self.readings.front() {
+            match time.duration_since(*first) {
+                Ok(age) if age > self.thresholds.leak_window => {
+                    self.readings.pop_front();
+                }
+                _ => break,
+            }
+        }
+        if event.is_some() || !self.detect_leaks || self.movement.is_some() {
+            return event;
+        }
+        let (since, highest) = self
+            .readings
+            .iter()
+            .copied()
+            .max_by_key(|(time, level)| (*level, std::cmp::Reverse(*time)))?;
+        if highest >= level.saturating_add(self.thresholds.leak_drop) {
+            if !self.leaking {
+                self.leaking = true;
+                return Some(TankEvent::Leaking {
+                    from: common::Percentage::new(highest),
+                    to: common::Percentage::new(level),
+                    since,
+                    time,
+                });
+            }
+        } else if level > last {
+            self.leaking = false;
+        }
+        None
+    }
+
+    /// Ends the pending movement and returns it as a dump or fill if it was big and fast enough.
+    fn settle(&mut self) -> Option<TankEvent> {
+        let movement = self.movement.take()?;
+        if !movement.fast || movement.from.abs_diff(movement.to) < self.thresholds.step {
+            return None;
+        }
+        // A dump or fill starts the leak window over, so the drop is not also counted as a leak.
+        self.readings.retain(|(time, _)| *time >= movement.time);
+        self.leaking = false;
+        let (from, to, time) = (
+            common::Percentage::new(movement.from),
+            common::Percentage::new(movement.to),
+            movement.time,
+        );
+        Some(if movement.to < movement.from {
+            TankEvent::Dumped { from, to, time }
+        } else {
+            TankEvent::Filled { from, to, time }
+        })
+    }
+
+    /// Reads the tank's level now and adds it.
+    pub async fn read(&mut self, tank: &Tank) -> Result<Option<TankEvent>, TankLevelError> {
+        let level = tank.level().await?;
+        Ok(self.push(SystemTime::now(), level))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn at(minutes: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_000_000 + minutes * 60)
+    }
+
+    /// Pushes a reading each minute and returns the events with the minute they came at.
+    fn run(detector: &mut TankEventDetector, levels: &[u8]) -> Vec<(u64, TankEvent)> {
+        levels
+            .iter()
+            .enumerate()
+            .filter_map(|(minute, level)| {
+                let minute = minute as u64;
+                detector
+                    .push(at(minute), common::Percentage::new(*level))
+                    .map(|event| (minute, event))
+            })
+            .collect()
+    }
+
+    fn detector(kind: TankKind) -> TankEventDetector {
+        TankEventDetector::new(Some(kind), TankEventThresholds::default())
+    }
+
+    #[test]
+    fn coarse_slow_rise_is_not_a_fill() {
+        let mut grey = detector(TankKind::Grey);
+        let mut levels = vec![0; 600];
+        levels.extend([33; 60]);
+        assert_eq!(run(&mut grey, &levels), []);
+    }
+
+    #[test]
+    fn coarse_slow_rise_read_rarely_is_not_a_fill() {
+        let mut grey = detector(TankKind::Grey);
+        assert_eq!(grey.push(at(0), common::Percentage::new(0)), None);
+        assert_eq!(grey.push(at(600), common::Percentage::new(33)), None);
+        assert_eq!(grey.push(at(630), common::Percentage::new(33)), None);
+    }
+
+    #[test]
+    fn coarse_dump_is_one_event() {
+        let mut black = detector(TankKind::Black);
+        let mut levels = vec![66; 240];
+        levels.extend([33, 0]);
+        levels.extend([0; 10]);
+        assert_eq!(
+            run(&mut black, &levels),
+            [(
+                246,
+                TankEvent::Dumped {
+                    from: common::Percentage::new(66),
+                    to: common::Percentage::new(0),
+                    time: at(241),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn coarse_single_step_dump_after_a_long_hold() {
+        let mut black = detector(TankKind::Black);
+        let mut levels = vec![33; 120];
+        levels.extend([0; 10]);
+        assert_eq!(
+            run(&mut black, &levels),
+            [(
+                125,
+                TankEvent::Dumped {
+                    from: common::Percentage::new(33),
+                    to: common::Percentage::new(0),
+                    time: at(120),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn coarse_single_step_fill_after_a_long_hold() {
+        let mut fresh = detector(TankKind::Fresh);
+        let mut levels = vec![0; 600];
+        levels.extend([33; 10]);
+        assert_eq!(run(&mut fresh, &levels).len(), 1);
+        // A fresh tank stepping down after a long hold is being used.
+        let mut fresh = detector(TankKind::Fresh);
+        let mut levels = vec![66; 600];
+        levels.extend([33; 10]);
+        assert_eq!(run(&mut fresh, &levels), []);
+    }
+
+    #[test]
+    fn coarse_fill_is_one_event() {
+        let mut fresh = detector(TankKind::Fresh);
+        let mut levels = vec![0; 30];
+        levels.extend([33, 33, 66, 100]);
+        levels.extend([100; 10]);
+        assert_eq!(
+            run(&mut fresh, &levels),
+            [(
+                38,
+                TankEvent::Filled {
+                    from: common::Percentage::new(0),
+                    to: common::Percentage::new(100),
+                    time: at(33),
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn fine_dump_is_one_event() {
+        let mut black = detector(TankKind::Black);
+        let mut levels = vec![80; 60];
+        levels.extend([62, 41, 20, 6, 5]);
+        levels.extend([5; 10]);
+        let events = run(&mut black, &levels);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0].1,
+            TankEvent::Dumped { from, to, .. } if from.value() == 80 && to.value() == 5
+        ));
+    }
+
+    #[test]
+    fn small_changes_are_not_events() {
+        let mut fresh = detector(TankKind::Fresh);
+        assert_eq!(run(&mut fresh, &[50, 45, 40, 40, 40, 40, 40, 40, 40]), []);
+    }
+
+    #[test]
+    fn slow_drop_is_a_leak_once() {
+        let mut black = detector(TankKind::Black);
+        let levels: Vec<u8> = (0..300).map(|minute| 60 - (minute / 20) as u8).collect();
+        let events = run(&mut black, &levels);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0].1,
+            TankEvent::Leaking { from, to, .. } if from.value() == 60 && to.value() == 50
+        ));
+    }
+
+    #[test]
+    fn fresh_tanks_do_not_leak() {
+        let mut fresh = detector(TankKind::Fresh);
+        let levels: Vec<u8> = (0..300).map(|minute| 60 - (minute / 20) as u8).collect();
+        assert_eq!(run(&mut fresh, &levels), []);
+    }
+
+    #[test]
+    fn older_readings_are_ignored() {
+        let mut fresh = detector(TankKind::Fresh);
+        fresh.push(at(10), common::Percentage::new(50));
+        assert_eq!(fresh.push(at(5), common::Percentage::new(0)), None);
+        assert_eq!(fresh.push(at(20), common::Percentage::new(50)), None);
+    }
+}
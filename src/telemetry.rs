@@ -0,0 +1,679 @@
+use super::{Client, DeviceSnapshot, DeviceState, OnlineState, SwitchState, ThingError, Watcher};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// A single reading of a device, ex: a tank's level or an HVAC's mode.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    /// A whole number, ex: a percentage.
+    Integer(i64),
+    /// A decimal number, ex: a temperature.
+    Float(f64),
+    /// On/off or online/offline.
+    Boolean(bool),
+    /// A named state, ex: "Running".
+    Text(String),
+}
+
+/// When readings are recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    /// Every device is recorded on each read.
+    Interval,
+    /// Only devices that changed since the last read are recorded.
+    Changes,
+}
+
+/// Where InfluxDB line protocol is written.
+#[derive(Clone, Debug)]
+pub enum InfluxDestination {
+    /// POSTed to a write endpoint, ex: "http://localhost:8086/api/v2/write?org=rv&bucket=lci&precision=ns".
+    Http {
+        /// The full write URL, including the precision, which must be nanoseconds.
+        url: String,
+        /// Sent as `Authorization: Token ...` when set.
+        token: Option<String>,
+    },
+    /// Appended to a local file.
+    File(PathBuf),
+}
+
+/// Where and how CSV files are written and rotated.
+#[derive(Clone, Debug)]
+pub struct CsvConfig {
+    /// The directory the files are written to, named ex: "lci-1700000000.csv", or "lci-1700000000-1.csv" for
+    /// another file started within the same second.
+    pub directory: PathBuf,
+    /// A new file is started once the current one grows past this size.
+    pub max_bytes: u64,
+    /// The oldest files are removed once there are more than this many.
+    pub max_files: usize,
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("."),
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 10,
+        }
+    }
+}
+
+/// What the logger records and where.
+#[derive(Clone, Debug)]
+pub struct TelemetryConfig {
+    /// How often every device is read.
+    pub interval: Duration,
+    /// Record every reading, or only changes.
+    pub sampling: Sampling,
+    /// Writes InfluxDB line protocol when set.
+    pub influx: Option<InfluxDestination>,
+    /// Writes CSV files when set.
+    pub csv: Option<CsvConfig>,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            sampling: Sampling::Interval,
+            influx: None,
+            csv: None,
+        }
+    }
+}
+
+/// Returns every reading in the snapshot as named fields, including "online".
+/// Readings that are not finite numbers, ex: a relay current of "NaN", are left out.
+pub fn fields(snapshot: &DeviceSnapshot) -> Vec<(&'static str, FieldValue)> {
+    let mut fields = vec![(
+        "online",
+        FieldValue::Boolean(*snapshot.online() == OnlineState::Online),
+    )];
+    match snapshot.state() {
+        DeviceState::Tank { level } => {
+            fields.push(("level", FieldValue::Integer(level.value() as i64)))
+        }
+        DeviceState::Dimmer { brightness } => {
+            fields.push(("brightness", FieldValue::Integer(brightness.value() as i64)))
+        }
+        DeviceState::Switch {
+            state,
+            fault,
+            relay_current,
+        } => {
+            fields.push(("on", FieldValue::Boolean(*state == SwitchState::On)));
+            fields.push(("fault", FieldValue::Boolean(*fault == SwitchState::On)));
+            if let Ok(current) = relay_current.parse() {
+                fields.push(("relay_current", FieldValue::Float(current)));
+            }
+        }
+        DeviceState::Generator { state } => {
+            fields.push(("state", FieldValue::Text(state.to_string())))
+        }
+        DeviceState::Hvac {
+            status,
+            mode,
+            fan,
+            inside_temperature,
+            outside_temperature,
+            high_temperature,
+            low_temperature,
+        } => {
+            fields.push(("status", FieldValue::Text(status.to_string())));
+            fields.push(("mode", FieldValue::Text(mode.to_string())));
+            fields.push(("fan", FieldValue::Text(fan.to_string())));
+            fields.push((
+                "inside_temperature",
+                FieldValue::Float(*inside_temperature as f64),
+            ));
+            fields.push((
+                "outside_temperature",
+                FieldValue::Float(*outside_temperature as f64),
+            ));
+            fields.push((
+                "high_temperature",
+                FieldValue::Float(*high_temperature as f64),
+            ));
+            fields.push((
+                "low_temperature",
+                FieldValue::Float(*low_temperature as f64),
+            ));
+        }
+    }
+    fields.retain(|(_, value)| !matches!(value, FieldValue::Float(value) if !value.is_finite()));
+    fields
+}
+
+/// Returns the snapshot as one InfluxDB line, measured as ex: "lci_tank" and tagged with label, uid and type.
+pub fn line_protocol(snapshot: &DeviceSnapshot, time: SystemTime) -> String {
+    let measurement = match snapshot.state() {
+        DeviceState::Tank { .. } => "lci_tank",
+        DeviceState::Dimmer { .. } => "lci_dimmer",
+        DeviceState::Switch { .. } => "lci_switch",
+        DeviceState::Generator { .. } => "lci_generator",
+        DeviceState::Hvac { .. } => "lci_hvac",
+    };
+    let mut line = format!(
+        "{},label={},uid={},type={} ",
+        measurement,
+        escape_tag(snapshot.label()),
+        escape_tag(snapshot.uid()),
+        escape_tag(&snapshot.device_type().to_string())
+    );
+    for (index, (name, value)) in fields(snapshot).iter().enumerate() {
+        if index > 0 {
+            line.push(',');
+        }
+        let _ = match value {
+            FieldValue::Integer(value) => write!(line, "{}={}i", name, value),
+            FieldValue::Float(value) => write!(line, "{}={}", name, value),
+            FieldValue::Boolean(value) => write!(line, "{}={}", name, value),
+            FieldValue::Text(value) => write!(
+                line,
+                "{}=\"{}\"",
+                name,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+        };
+    }
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let _ = write!(line, " {}", nanos);
+    line
+}
+
+/// Returns the snapshot as CSV rows of `timestamp,label,uid,type,field,value`, one per field.
+/// The timestamp is in seconds since the Unix epoch.
+pub fn csv_rows(snapshot: &DeviceSnapshot, time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut rows = String::new();
+    for (name, value) in fields(snapshot) {
+        let value = match value {
+            FieldValue::Integer(value) => value.to_string(),
+            FieldValue::Float(value) => value.to_string(),
+            FieldValue::Boolean(value) => value.to_string(),
+            FieldValue::Text(value) => escape_csv(&value),
+        };
+        let _ = writeln!(
+            rows,
+            "{},{},{},{},{},{}",
+            seconds,
+            escape_csv(snapshot.label()),
+            escape_csv(snapshot.uid()),
+            snapshot.device_type(),
+            name,
+            value
+        );
+    }
+    rows
+}
+
+/// The header line of every CSV file.
+pub const CSV_HEADER: &str = "timestamp,label,uid,type,field,value";
+
+/// How many InfluxDB lines are kept for retrying while the destination fails. The oldest are dropped first.
+pub const MAX_PENDING_LINES: usize = 100_000;
+
+/// Reads every device on an interval and records the readings.
+pub struct Logger {
+    config: TelemetryConfig,
+    client: Client,
+    http: reqwest::Client,
+    csv: Option<CsvFile>,
+    pending: VecDeque<String>,
+}
+
+impl Logger {
+    /// Create a logger for the gateway behind the client.
+    pub fn new(config: TelemetryConfig, client: Client) -> Self {
+        Self {
+            config,
+            client,
+            http: reqwest::Client::new(),
+            csv: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Records until the process stops. Failed writes are logged, see `record`.
+    pub async fn run(mut self) -> Result<(), TelemetryError> {
+        let mut watcher = Watcher::new(self.client.devices().await?);
+        let mut interval = tokio::time::interval(self.config.interval);
+        loop {
+            interval.tick().await;
+            let changes = watcher.poll().await;
+            let snapshots: Vec<DeviceSnapshot> = match self.config.sampling {
+                Sampling::Interval => watcher.snapshots().into_iter().cloned().collect(),
+                Sampling::Changes => changes
+                    .into_iter()
+                    .map(|change| change.current().clone())
+                    .collect(),
+            };
+            if let Err(err) = self.record(&snapshots, SystemTime::now()).await {
+                log::error!("Could not record telemetry: {}", err);
+            }
+        }
+    }
+
+    /// Writes the snapshots to every configured destination, each on its own, and returns the first failure.
+    /// InfluxDB lines that could not be written are kept and sent again with the next readings, up to
+    /// `MAX_PENDING_LINES`. Lines the endpoint rejects with a 4xx status would be rejected again, so they
+    /// are dropped; only transport errors and 5xx statuses keep them. CSV rows that could not be written are dropped.
+    pub async fn record(
+        &mut self,
+        snapshots: &[DeviceSnapshot],
+        time: SystemTime,
+    ) -> Result<(), TelemetryError> {
+        if snapshots.is_empty() {
+            return Ok(());
+        }
+        let influx = self.write_influx(snapshots, time).await;
+        let csv = self.write_csv(snapshots, time);
+        if let (Err(_), Err(err)) = (&influx, &csv) {
+            log::error!("Could not write CSV telemetry: {}", err);
+        }
+        influx.and(csv)
+    }
+
+    /// Returns how many InfluxDB lines are waiting to be sent again.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    async fn write_influx(
+        &mut self,
+        snapshots: &[DeviceSnapshot],
+        time: SystemTime,
+    ) -> Result<(), TelemetryError> {
+        let Some(influx) = &self.config.influx else {
+            return Ok(());
+        };
+        self.pending.extend(
+            snapshots
+                .iter()
+                .map(|snapshot| line_protocol(snapshot, time)),
+        );
+        let excess = self.pending.len().saturating_sub(MAX_PENDING_LINES);
+        if excess > 0 {
+            log::warn!("Dropping {} unsent InfluxDB lines", excess);
+            self.pending.drain(..excess);
+        }
+        let mut lines = String::new();
+        for line in &self.pending {
+            lines.push_str(line);
+            lines.push('\n');
+        }
+        match influx {
+            InfluxDestination::Http { url, token } => {
+                let mut request = self.http.post(url).body(lines);
+                if let Some(token) = token {
+                    request = request.header("Authorization", format!("Token {}", token));
+                }
+                let response = request.send().await.map_err(TelemetryError::Http)?;
+                let status = response.status();
+                if status.is_client_error() {
+                    log::warn!(
+                        "Dropping {} InfluxDB lines rejected with {}",
+                        self.pending.len(),
+                        status
+                    );
+                    self.pending.clear();
+                }
+                if !status.is_success() {
+                    return Err(TelemetryError::Status(status.as_u16()));
+                }
+            }
+            InfluxDestination::File(path) => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(lines.as_bytes())?,
+        }
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn write_csv(
+        &mut self,
+        snapshots: &[DeviceSnapshot],
+        time: SystemTime,
+    ) -> Result<(), TelemetryError> {
+        let Some(config) = &self.config.csv else {
+            return Ok(());
+        };
+        let mut rows = String::new();
+        for snapshot in snapshots {
+            rows.push_str(&csv_rows(snapshot, time));
+        }
+        if self
+            .csv
+            .as_ref()
+            .is_none_or(|csv| csv.written >= config.max_bytes)
+        {
+            self.csv = Some(CsvFile::create(config, time)?);
+        }
+        if let Some(csv) = &mut self.csv {
+            csv.file.write_all(rows.as_bytes())?;
+            csv.written += rows.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+struct CsvFile {
+    file: File,
+    written: u64,
+}
+
+impl CsvFile {
+    /// Starts a new file and removes the oldest ones past `max_files`.
+    fn create(config: &CsvConfig, time: SystemTime) -> Result<Self, std::io::Error> {
+        fs::create_dir_all(&config.directory)?;
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut existing = csv_files(&config.directory)?;
+        // Files started within the same second are numbered after the latest one.
+        let mut sequence = existing
+            .iter()
+            .filter(|(started, _, _)| *started == seconds)
+            .map(|(_, sequence, _)| sequence + 1)
+            .max()
+            .unwrap_or(0);
+        let (path, mut file) = loop {
+            let name = match sequence {
+                0 => format!("lci-{}.csv", seconds),
+                _ => format!("lci-{}-{}.csv", seconds, sequence),
+            };
+            let path = config.directory.join(name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => sequence += 1,
+                Err(err) => return Err(err),
+            }
+        };
+        writeln!(file, "{}", CSV_HEADER)?;
+        let written = CSV_HEADER.len() as u64 + 1;
+        log::info!("Writing telemetry to {}", path.display());
+
+        existing.push((seconds, sequence, path));
+        existing.sort();
+        let excess = existing.len().saturating_sub(config.max_files.max(1));
+        for (_, _, old) in &existing[..excess] {
+            fs::remove_file(old)?;
+        }
+        Ok(Self { file, written })
+    }
+}
+
+/// Returns the telemetry CSV files in the directory with the second and sequence they were started at.
+fn csv_files(directory: &Path) -> Result<Vec<(u64, u64, PathBuf)>, std::io::Error> {
+    Ok(fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?;
+            let stem = name.strip_prefix("lci-")?.strip_suffix(".csv")?;
+            let (seconds, sequence) = stem.split_once('-').unwrap_or((stem, "0"));
+            Some((seconds.parse().ok()?, sequence.parse().ok()?, path))
+        })
+        .collect())
+}
+
+fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returned when readings could not be recorded.
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+    /// The devices could not be listed from the gateway.
+    #[error("The devices could not be listed. {0}")]
+    Things(ThingError),
+    /// A file could not be written.
+    #[error("The telemetry could not be written. {0}")]
+    Io(std::io::Error),
+    /// The InfluxDB endpoint could not be reached.
+    #[error("The InfluxDB endpoint could not be reached. {0}")]
+    Http(reqwest::Error),
+    /// The InfluxDB endpoint rejected the write.
+    #[error("The InfluxDB endpoint returned status {0}.")]
+    Status(u16),
+}
+
+impl From<ThingError> for TelemetryError {
+    fn from(error: ThingError) -> Self {
+        Self::Things(error)
+    }
+}
+
+impl From<std::io::Error> for TelemetryError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn tank(level: u8) -> DeviceSnapshot {
+        serde_json::from_value(serde_json::json!({
+            "label": "Fresh Water",
+            "uid": "tank-1",
+            "device_type": "Tank",
+            "online": "Online",
+            "state": {"type": "tank", "level": level},
+        }))
+        .unwrap()
+    }
+
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("lci-telemetry-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn file_names(directory: &PathBuf) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Answers each request with the next status and returns the bodies of the accepted ones.
+    async fn influx(statuses: Vec<u16>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/v2/write", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut accepted = Vec::new();
+            for status in statuses {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(socket);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                reader
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+                if status < 300 {
+                    accepted.push(String::from_utf8(body).unwrap());
+                }
+            }
+            accepted
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn formats_line_protocol_and_csv() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            line_protocol(&tank(40), time),
+            "lci_tank,label=Fresh\\ Water,uid=tank-1,type=Tank online=true,level=40i 1700000000000000000"
+        );
+        assert_eq!(
+            csv_rows(&tank(40), time),
+            "1700000000,Fresh Water,tank-1,Tank,online,true\n1700000000,Fresh Water,tank-1,Tank,level,40\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_csv_when_influx_fails() {
+        let directory = directory("independent");
+        let config = TelemetryConfig {
+            influx: Some(InfluxDestination::Http {
+                url: "http://127.0.0.1:1/api/v2/write".to_string(),
+                token: None,
+            }),
+            csv: Some(CsvConfig {
+                directory: directory.clone(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut logger = Logger::new(config, Client::new("http://127.0.0.1:1"));
+        let result = logger.record(&[tank(40)], SystemTime::now()).await;
+        assert!(matches!(result, Err(TelemetryError::Http(_))));
+        assert_eq!(logger.pending(), 1);
+
+        let files = file_names(&directory);
+        assert_eq!(files.len(), 1);
+        let text = fs::read_to_string(directory.join(&files[0])).unwrap();
+        assert!(text.starts_with(CSV_HEADER));
+        assert!(text.contains("Fresh Water,tank-1,Tank,level,40"));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn retries_failed_influx_writes() {
+        let (url, server) = influx(vec![500, 204]).await;
+        let config = TelemetryConfig {
+            influx: Some(InfluxDestination::Http { url, token: None }),
+            ..Default::default()
+        };
+        let mut logger = Logger::new(config, Client::new("http://127.0.0.1:1"));
+        let result = logger.record(&[tank(40)], SystemTime::now()).await;
+        assert!(matches!(result, Err(TelemetryError::Status(500))));
+        assert_eq!(logger.pending(), 1);
+        logger.record(&[tank(35)], SystemTime::now()).await.unwrap();
+        assert_eq!(logger.pending(), 0);
+
+        let accepted = server.await.unwrap();
+        assert_eq!(accepted.len(), 1);
+        let lines: Vec<&str> = accepted[0].lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("level=40i"));
+        assert!(lines[1].contains("level=35i"));
+    }
+
+    #[tokio::test]
+    async fn drops_rejected_influx_writes() {
+        let (url, server) = influx(vec![400, 204]).await;
+        let config = TelemetryConfig {
+            influx: Some(InfluxDestination::Http { url, token: None }),
+            ..Default::default()
+        };
+        let mut logger = Logger::new(config, Client::new("http://127.0.0.1:1"));
+        let result = logger.record(&[tank(40)], SystemTime::now()).await;
+        assert!(matches!(result, Err(TelemetryError::Status(400))));
+        assert_eq!(logger.pending(), 0);
+        logger.record(&[tank(35)], SystemTime::now()).await.unwrap();
+
+        let accepted = server.await.unwrap();
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(accepted[0].lines().count(), 1);
+        assert!(accepted[0].contains("level=35i"));
+    }
+
+    #[test]
+    fn skips_non_finite_fields() {
+        let switch: DeviceSnapshot = serde_json::from_value(serde_json::json!({
+            "label": "Pump",
+            "uid": "switch-1",
+            "device_type": "Switch",
+            "online": "Online",
+            "state": {"type": "switch", "state": "On", "fault": "Off", "relay_current": "NaN"},
+        }))
+        .unwrap();
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            line_protocol(&switch, time),
+            "lci_switch,label=Pump,uid=switch-1,type=Switch online=true,on=true,fault=false 1700000000000000000"
+        );
+    }
+
+    #[tokio::test]
+    async fn rotates_csv_files_within_one_second() {
+        let directory = directory("rotate");
+        let config = TelemetryConfig {
+            csv: Some(CsvConfig {
+                directory: directory.clone(),
+                max_bytes: 1,
+                max_files: 3,
+            }),
+            ..Default::default()
+        };
+        let mut logger = Logger::new(config, Client::new("http://127.0.0.1:1"));
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for level in 0..5 {
+            logger.record(&[tank(level)], time).await.unwrap();
+        }
+        assert_eq!(
+            file_names(&directory),
+            [
+                "lci-1700000000-2.csv",
+                "lci-1700000000-3.csv",
+                "lci-1700000000-4.csv"
+            ]
+        );
+        let newest = fs::read_to_string(directory.join("lci-1700000000-4.csv")).unwrap();
+        assert!(newest.contains("level,4\n"));
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
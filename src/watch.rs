@@ -0,0 +1,188 @@
+use super::{Device, DeviceSnapshot, DeviceStateError};
+use derive_getters::Getters;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A device whose snapshot differs from the previous poll.
+#[derive(Clone, Debug, Getters, PartialEq, Serialize)]
+pub struct DeviceChange {
+    /// The snapshot from the previous poll. None the first time the device is read.
+    previous: Option<DeviceSnapshot>,
+    /// The snapshot from this poll.
+    current: DeviceSnapshot,
+}
+
+/// Reads a set of devices on demand and reports what changed since the last read.
+/// The caller decides how often to poll, so any runtime or timer can drive it.
+pub struct Watcher {
+    devices: Vec<Device>,
+    snapshots: HashMap<String, DeviceSnapshot>,
+}
+
+impl Watcher {
+    /// Create a watcher over the given devices. Nothing is read until `poll`.
+    pub fn new(devices: Vec<Device>) -> Self {
+        Self {
+            devices,
+            snapshots: HashMap::new(),
+        }
+    }
+
+    /// Returns the watched devices.
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Returns the watched devices to send commands to.
+    pub fn devices_mut(&mut self) -> &mut [Device] {
+        &mut self.devices
+    }
+
+    /// Returns the latest snapshot of the device with the given UID.
+    pub fn snapshot(&self, uid: &str) -> Option<&DeviceSnapshot> {
+        self.snapshots.get(uid)
+    }
+
+    /// Returns the latest snapshot of every device that has been read, in device order.
+    pub fn snapshots(&self) -> Vec<&DeviceSnapshot> {
+        self.devices
+            .iter()
+            .filter_map(|device| self.snapshots.get(&device.uid()))
+            .collect()
+    }
+
+    /// Returns the devices that have never been read, in device order.
+    pub fn unread(&self) -> Vec<&Device> {
+        self.devices
+            .iter()
+            .filter(|device| !self.snapshots.contains_key(&device.uid()))
+            .collect()
+    }
+
+    /// Reads every device and returns the ones that changed.
+    /// Devices that can not be read are logged and go offline with their last readings, so the failure
+    /// shows up as a change. Devices that have never been read are skipped until they can be.
+    pub async fn poll(&mut self) -> Vec<DeviceChange> {
+        let results = futures::future::join_all(self.devices.iter().map(Device::snapshot)).await;
+        (0..self.devices.len())
+            .zip(results)
+            .filter_map(|(index, result)| self.update(index, result))
+            .collect()
+    }
+
+    /// Reads only the device with the given UID, ex: after sending it a command, and returns its change.
+    /// Failures are handled as in `poll`.
+    pub async fn poll_device(&mut self, uid: &str) -> Option<DeviceChange> {
+        let index = self.devices.iter().position(|device| device.uid() == uid)?;
+        let result = self.devices[index].snapshot().await;
+        self.update(index, result)
+    }
+
+    fn update(
+        &mut self,
+        index: usize,
+        result: Result<DeviceSnapshot, DeviceStateError>,
+    ) -> Option<DeviceChange> {
+        let device = &self.devices[index];
+        let current = match result {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                log::warn!("Could not read {}: {}", device.label(), err);
+                self.snapshots.get(&device.uid())?.offline()
+            }
+        };
+        let previous = self
+            .snapshots
+            .insert(current.uid().clone(), current.clone());
+        (previous.as_ref() != Some(&current)).then_some(DeviceChange { previous, current })
+    }
+}
+
+#[cfg(all(
+    test,
+    any(feature = "history", feature = "rules", feature = "websocket")
+))]
+impl DeviceChange {
+    pub(crate) fn new(previous: Option<DeviceSnapshot>, current: DeviceSnapshot) -> Self {
+        Self { previous, current }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, OnlineState, Thing};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves a tank's online and level items, or a 500 while the level is None.
+    async fn gateway(level: Arc<Mutex<Option<u8>>>) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buffer = [0; 4096];
+                let read = socket.read(&mut buffer).await.unwrap();
+                let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                let state = match *level.lock().unwrap() {
+                    None => None,
+                    Some(_) if request.contains("_online ") => Some("ON".to_string()),
+                    Some(level) => Some(level.to_string()),
+                };
+                let response = match state {
+                    Some(state) => {
+                        let body = format!("{{\"state\": \"{}\"}}", state);
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    None => "HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        .to_string(),
+                };
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    fn tank(client: Client) -> Device {
+        let mut thing: Thing = serde_json::from_value(serde_json::json!({
+            "label": "Fresh Water",
+            "UID": "idsmyrv:tank-sensor:0001",
+            "configuration": {"deviceType": 10},
+        }))
+        .unwrap();
+        thing.set_client(client);
+        Device::from_thing(thing).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reports_failed_reads_as_offline() {
+        let level = Arc::new(Mutex::new(None));
+        let mut watcher = Watcher::new(vec![tank(gateway(level.clone()).await)]);
+        assert!(watcher.poll().await.is_empty());
+
+        *level.lock().unwrap() = Some(60);
+        let changes = watcher.poll().await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(*changes[0].current().online(), OnlineState::Online);
+
+        *level.lock().unwrap() = None;
+        let changes = watcher.poll().await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(*changes[0].current().online(), OnlineState::Offline);
+        assert_eq!(
+            changes[0].current().state(),
+            changes[0].previous().as_ref().unwrap().state()
+        );
+        assert!(watcher.poll().await.is_empty());
+
+        *level.lock().unwrap() = Some(60);
+        let changes = watcher.poll().await;
+        assert_eq!(*changes[0].current().online(), OnlineState::Online);
+    }
+}
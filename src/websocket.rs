@@ -0,0 +1,544 @@
+use super::{Client, DeviceChange, DeviceCommand, DeviceSnapshot, DeviceType, ThingError, Watcher};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+/// A message pushed to WebSocket clients, as JSON tagged by `type`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Every subscribed device. Sent on connect, on subscribe and when asked for.
+    Snapshot {
+        /// The latest snapshot of each device.
+        devices: Vec<DeviceSnapshot>,
+    },
+    /// A subscribed device changed.
+    Changed(DeviceChange),
+    /// The outcome of a `ClientMessage::Command`.
+    CommandResult {
+        /// The device the command was sent to, as given by the client.
+        device: String,
+        /// The command that was sent.
+        command: DeviceCommand,
+        /// Why the command failed. None when it succeeded.
+        error: Option<String>,
+    },
+    /// The client's message could not be understood.
+    Error {
+        /// What was wrong with the message.
+        message: String,
+    },
+}
+
+/// A message WebSocket clients send, as JSON tagged by `type`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Only push these types of devices. An empty list subscribes to every device, which is the default.
+    Subscribe {
+        /// The device types to push, ex: ["Tank", "Hvac"].
+        device_types: Vec<DeviceType>,
+    },
+    /// Ask for a full snapshot.
+    Snapshot,
+    /// Carry out a command on a device, ex: `{"type": "command", "device": "Kitchen Lights", "command": {"command": "on"}}`.
+    Command {
+        /// The device's UID, object ID or label.
+        device: String,
+        /// What to do.
+        command: DeviceCommand,
+    },
+}
+
+/// Polls the gateway and pushes changes to every connected WebSocket client.
+pub struct PushServer {
+    client: Client,
+    poll_interval: Duration,
+}
+
+struct Shared {
+    snapshots: RwLock<Vec<DeviceSnapshot>>,
+    changes: broadcast::Sender<DeviceChange>,
+    commands: mpsc::Sender<CommandRequest>,
+}
+
+struct CommandRequest {
+    device: String,
+    command: DeviceCommand,
+    reply: oneshot::Sender<Option<String>>,
+}
+
+impl PushServer {
+    /// Create a server for the gateway behind the client, reading every device every 5 seconds.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            poll_interval: Duration::from_secs(5),
+        }
+    }
+
+    /// Sets how often every device is read.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Starts polling the gateway and returns the `/ws` route to serve or nest in another router.
+    pub async fn router(self) -> Result<Router, PushError> {
+        let watcher = Watcher::new(self.client.devices().await?);
+        let (changes, _) = broadcast::channel(256);
+        let (commands, receiver) = mpsc::channel(16);
+        let shared = Arc::new(Shared {
+            snapshots: RwLock::new(Vec::new()),
+            changes,
+            commands,
+        });
+        tokio::spawn(poll(
+            watcher,
+            self.poll_interval,
+            receiver,
+            Arc::clone(&shared),
+        ));
+        Ok(routes(shared))
+    }
+}
+
+fn routes(shared: Arc<Shared>) -> Router {
+    Router::new()
+        .route("/ws", axum::routing::get(upgrade))
+        .with_state(shared)
+}
+
+/// Serves the WebSocket on `/ws` at the given address, ex: "0.0.0.0:8091".
+pub async fn serve(server: PushServer, address: &str) -> Result<(), PushError> {
+    let app = server.router().await?;
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(PushError::Bind)?;
+    log::info!("Serving the WebSocket on ws://{}/ws", address);
+    axum::serve(listener, app).await.map_err(PushError::Serve)
+}
+
+/// Owns the devices: reads them on the interval and carries out commands from clients.
+async fn poll(
+    mut watcher: Watcher,
+    interval: Duration,
+    mut commands: mpsc::Receiver<CommandRequest>,
+    shared: Arc<Shared>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        let changes = tokio::select! {
+            _ = ticker.tick() => watcher.poll().await,
+            request = commands.recv() => {
+                let Some(request) = request else {
+                    return;
+                };
+                let (error, uid) = match watcher
+                    .devices_mut()
+                    .iter_mut()
+                    .find(|device| device.matches(&request.device))
+                {
+                    Some(device) => {
+                        log::info!("{}: {}", device.label(), request.command);
+                        let error = device.execute(&request.command).await.err().map(|err| err.to_string());
+                        (error, Some(device.uid()))
+                    }
+                    None => (Some(format!("No device matches \"{}\".", request.device)), None),
+                };
+                let _ = request.reply.send(error);
+                // Only the commanded device can have changed because of the command.
+                match uid {
+                    Some(uid) => watcher.poll_device(&uid).await.into_iter().collect(),
+                    None => continue,
+                }
+            }
+        };
+        if let Ok(mut snapshots) = shared.snapshots.write() {
+            *snapshots = watcher.snapshots().into_iter().cloned().collect();
+        }
+        for change in changes {
+            // No receivers just means no one is connected.
+            let _ = shared.changes.send(change);
+        }
+    }
+}
+
+async fn upgrade(State(shared): State<Arc<Shared>>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| connection(socket, shared))
+}
+
+/// Pushes to one client until it disconnects.
+async fn connection(mut socket: WebSocket, shared: Arc<Shared>) {
+    let mut changes = shared.changes.subscribe();
+    let mut subscription: HashSet<DeviceType> = HashSet::new();
+    let subscribed = |subscription: &HashSet<DeviceType>, device_type: &DeviceType| {
+        subscription.is_empty() || subscription.contains(device_type)
+    };
+    let snapshot = |subscription: &HashSet<DeviceType>| ServerMessage::Snapshot {
+        devices: shared
+            .snapshots
+            .read()
+            .map(|snapshots| {
+                snapshots
+                    .iter()
+                    .filter(|snapshot| subscribed(subscription, snapshot.device_type()))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    if send(&mut socket, &snapshot(&subscription)).await.is_err() {
+        return;
+    }
+    loop {
+        let message = tokio::select! {
+            change = changes.recv() => match change {
+                Ok(change) if subscribed(&subscription, change.current().device_type()) => {
+                    ServerMessage::Changed(change)
+                }
+                Ok(_) => continue,
+                // Catch the client up on everything it missed.
+                Err(broadcast::error::RecvError::Lagged(_)) => snapshot(&subscription),
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<ClientMessage>(text.as_str()) {
+                        Ok(ClientMessage::Subscribe { device_types }) => {
+                            subscription = device_types.into_iter().collect();
+                            snapshot(&subscription)
+                        }
+                        Ok(ClientMessage::Snapshot) => snapshot(&subscription),
+                        Ok(ClientMessage::Command { device, command }) => {
+                            let (reply, result) = oneshot::channel();
+                            let request = CommandRequest {
+                                device: device.clone(),
+                                command: command.clone(),
+                                reply,
+                            };
+                            let error = match shared.commands.send(request).await {
+                                Ok(()) => result.await.unwrap_or(Some("The server stopped.".to_string())),
+                                Err(_) => Some("The server stopped.".to_string()),
+                            };
+                            ServerMessage::CommandResult { device, command, error }
+                        }
+                        Err(err) => ServerMessage::Error {
+                            message: err.to_string(),
+                        },
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if send(&mut socket, &message).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    socket.send(Message::Text(text.into())).await
+}
+
+/// Returned when the WebSocket server can not start or stops.
+#[derive(Debug, Error)]
+pub enum PushError {
+    /// The devices could not be listed from the gateway.
+    #[error("The devices could not be listed. {0}")]
+    Things(ThingError),
+    /// The address could not be listened on.
+    #[error("The WebSocket address could not be bound. {0}")]
+    Bind(std::io::Error),
+    /// The server failed while running.
+    #[error("The WebSocket server failed. {0}")]
+    Serve(std::io::Error),
+}
+
+impl From<ThingError> for PushError {
+    fn from(error: ThingError) -> Self {
+        Self::Things(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    /// Serves a tank at 40% and a dimmer that takes brightness commands, and records every item read.
+    async fn gateway(reads: Arc<Mutex<Vec<String>>>) -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut brightness = "10".to_string();
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = BufReader::new(socket);
+                let mut request = String::new();
+                reader.read_line(&mut request).await.unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let path = request.split_whitespace().nth(1).unwrap().to_string();
+                let body = if path.starts_with("/rest/things") {
+                    serde_json::json!([
+                        {"label": "Fresh Water", "UID": "idsmyrv:tank-sensor:0001", "configuration": {"deviceType": 10}},
+                        {"label": "Porch Light", "UID": "idsmyrv:dimmer:0002", "configuration": {"deviceType": 20}},
+                    ])
+                    .to_string()
+                } else if request.starts_with("POST") {
+                    brightness = String::from_utf8(body).unwrap();
+                    String::new()
+                } else {
+                    let state = if path.ends_with("_online") {
+                        "ON"
+                    } else if path.contains("tank") {
+                        "40"
+                    } else {
+                        &brightness
+                    };
+                    reads.lock().unwrap().push(path);
+                    format!("{{\"state\": \"{}\"}}", state)
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                reader
+                    .get_mut()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        Client::new(address)
+    }
+
+    fn snapshot(label: &str, device_type: &str, state: serde_json::Value) -> DeviceSnapshot {
+        serde_json::from_value(serde_json::json!({
+            "label": label,
+            "uid": label.to_lowercase(),
+            "device_type": device_type,
+            "online": "Online",
+            "state": state,
+        }))
+        .unwrap()
+    }
+
+    fn tank(level: u8) -> DeviceSnapshot {
+        snapshot(
+            "Fresh Water",
+            "Tank",
+            serde_json::json!({"type": "tank", "level": level}),
+        )
+    }
+
+    fn dimmer(brightness: u8) -> DeviceSnapshot {
+        snapshot(
+            "Porch Light",
+            "Dimmer",
+            serde_json::json!({"type": "dimmer", "brightness": brightness}),
+        )
+    }
+
+    /// Serves the routes and returns the WebSocket URL.
+    async fn serve(router: Router) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        url
+    }
+
+    async fn connect(url: &str) -> Socket {
+        tokio_tungstenite::connect_async(url).await.unwrap().0
+    }
+
+    async fn send_json(socket: &mut Socket, message: serde_json::Value) {
+        socket
+            .send(tungstenite::Message::Text(message.to_string().into()))
+            .await
+            .unwrap();
+    }
+
+    async fn receive(socket: &mut Socket) -> serde_json::Value {
+        loop {
+            if let tungstenite::Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    fn labels(message: &serde_json::Value) -> Vec<&str> {
+        assert_eq!(message["type"], "snapshot");
+        message["devices"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|device| device["label"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn carries_out_commands_and_refreshes_only_that_device() {
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let router = PushServer::new(gateway(reads.clone()).await)
+            .with_poll_interval(Duration::from_secs(3600))
+            .router()
+            .await
+            .unwrap();
+        let url = serve(router).await;
+
+        // Connect until the first poll has read both devices.
+        let mut socket = loop {
+            let mut socket = connect(&url).await;
+            if labels(&receive(&mut socket).await) == ["Fresh Water", "Porch Light"] {
+                break socket;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        reads.lock().unwrap().clear();
+
+        send_json(
+            &mut socket,
+            serde_json::json!({"type": "command", "device": "Porch Light", "command": {"command": "set_brightness", "value": 75}}),
+        )
+        .await;
+        let result = receive(&mut socket).await;
+        assert_eq!(result["type"], "command_result");
+        assert_eq!(result["device"], "Porch Light");
+        assert_eq!(result["error"], serde_json::Value::Null);
+        let changed = receive(&mut socket).await;
+        assert_eq!(changed["type"], "changed");
+        assert_eq!(changed["previous"]["state"]["brightness"], 10);
+        assert_eq!(changed["current"]["state"]["brightness"], 75);
+        let reads = reads.lock().unwrap().clone();
+        assert!(!reads.is_empty());
+        assert!(reads.iter().all(|path| path.contains("dimmer")));
+
+        send_json(
+            &mut socket,
+            serde_json::json!({"type": "command", "device": "Fresh Water", "command": {"command": "on"}}),
+        )
+        .await;
+        let result = receive(&mut socket).await;
+        assert_eq!(result["error"], "A Tank does not support the command 'On'.");
+
+        send_json(
+            &mut socket,
+            serde_json::json!({"type": "command", "device": "Nowhere", "command": {"command": "off"}}),
+        )
+        .await;
+        let result = receive(&mut socket).await;
+        assert_eq!(result["type"], "command_result");
+        assert_eq!(result["error"], "No device matches \"Nowhere\".");
+    }
+
+    #[tokio::test]
+    async fn reports_messages_it_can_not_understand() {
+        let (changes, _) = broadcast::channel(16);
+        let (commands, _receiver) = mpsc::channel(1);
+        let shared = Arc::new(Shared {
+            snapshots: RwLock::new(Vec::new()),
+            changes,
+            commands,
+        });
+        let mut socket = connect(&serve(routes(shared)).await).await;
+        assert!(labels(&receive(&mut socket).await).is_empty());
+
+        for message in [
+            serde_json::json!("not an object"),
+            serde_json::json!({"type": "unsubscribe"}),
+            serde_json::json!({"type": "subscribe", "device_types": ["Toaster"]}),
+            serde_json::json!({"type": "command", "device": "Porch Light"}),
+        ] {
+            send_json(&mut socket, message).await;
+            let reply = receive(&mut socket).await;
+            assert_eq!(reply["type"], "error");
+            assert!(!reply["message"].as_str().unwrap().is_empty());
+        }
+        socket
+            .send(tungstenite::Message::Text("{".into()))
+            .await
+            .unwrap();
+        assert_eq!(receive(&mut socket).await["type"], "error");
+    }
+
+    #[tokio::test]
+    async fn filters_subscriptions_and_catches_up_after_lagging() {
+        let (changes, _) = broadcast::channel(256);
+        let (commands, _receiver) = mpsc::channel(1);
+        let shared = Arc::new(Shared {
+            snapshots: RwLock::new(vec![tank(40), dimmer(10)]),
+            changes,
+            commands,
+        });
+        let mut socket = connect(&serve(routes(Arc::clone(&shared))).await).await;
+        assert_eq!(
+            labels(&receive(&mut socket).await),
+            ["Fresh Water", "Porch Light"]
+        );
+
+        send_json(
+            &mut socket,
+            serde_json::json!({"type": "subscribe", "device_types": ["Dimmer"]}),
+        )
+        .await;
+        assert_eq!(labels(&receive(&mut socket).await), ["Porch Light"]);
+        shared
+            .changes
+            .send(DeviceChange::new(Some(tank(40)), tank(35)))
+            .unwrap();
+        shared
+            .changes
+            .send(DeviceChange::new(Some(dimmer(10)), dimmer(20)))
+            .unwrap();
+        let changed = receive(&mut socket).await;
+        assert_eq!(changed["type"], "changed");
+        assert_eq!(changed["current"]["label"], "Porch Light");
+
+        send_json(&mut socket, serde_json::json!({"type": "snapshot"})).await;
+        assert_eq!(labels(&receive(&mut socket).await), ["Porch Light"]);
+
+        // More changes than the channel holds, sent before the connection can read any of them.
+        for brightness in 0..=100 {
+            for _ in 0..3 {
+                shared
+                    .changes
+                    .send(DeviceChange::new(None, dimmer(brightness)))
+                    .unwrap();
+            }
+        }
+        assert_eq!(labels(&receive(&mut socket).await), ["Porch Light"]);
+        let changed = receive(&mut socket).await;
+        assert_eq!(changed["type"], "changed");
+    }
+}